@@ -93,6 +93,12 @@ class CheckFile:
             finally:
                 pass
             raise Exception("Next line does not match")
+        elif dirname == "CHECK-NOT":
+            for line in copy(state):
+                line = self.ansi_escape.sub("", line)
+                if directive[1] in line:
+                    raise Exception("Excluded text found in `{}`".format(line.strip()))
+            return state
         else:
             raise Exception("Unknown directive `{}`".format(directive[0]))
 
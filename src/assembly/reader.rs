@@ -5,7 +5,7 @@
 use crate::{
     ir::{self, Opcode, Signature, UnitBuilder, UnitName},
     ty::Type,
-    value::{IntValue, TimeValue},
+    value::{IntValue, LogicValue, TimeValue},
 };
 use num::{BigInt, BigRational};
 use std::collections::HashMap;
@@ -57,16 +57,19 @@ pub struct Inst<'a> {
 pub enum InstData<'a> {
     ConstInt(IntValue),
     ConstTime(TimeValue),
+    ConstLogic(LogicValue),
     Aggregate(usize, Vec<TypedValue<'a>>),
     Nullary,
     Unary(TypedValue<'a>),
     Binary(TypedValue<'a>, TypedValue<'a>),
     Ternary(TypedValue<'a>, TypedValue<'a>, TypedValue<'a>),
-    Quaternary(
-        TypedValue<'a>,
+    Signal(Option<ir::Resolution>, TypedValue<'a>),
+    Drive(
+        ir::DelayMode,
         TypedValue<'a>,
         TypedValue<'a>,
         TypedValue<'a>,
+        Option<TypedValue<'a>>,
     ),
     Reg(
         TypedValue<'a>,
@@ -118,6 +121,7 @@ impl<'a> Inst<'a> {
         let result: InstOrValue = match self.data {
             InstData::ConstInt(imm) => builder.ins().const_int(imm).into(),
             InstData::ConstTime(imm) => builder.ins().const_time(imm).into(),
+            InstData::ConstLogic(imm) => builder.ins().const_logic(imm).into(),
             InstData::Aggregate(size, args) => {
                 let args = args
                     .into_iter()
@@ -142,7 +146,6 @@ impl<'a> Inst<'a> {
                     Opcode::Not => builder.ins().not(arg).into(),
                     Opcode::Neg => builder.ins().neg(arg).into(),
                     Opcode::RetValue => builder.ins().ret_value(arg).into(),
-                    Opcode::Sig => builder.ins().sig(arg).into(),
                     Opcode::Prb => builder.ins().prb(arg).into(),
                     Opcode::Var => builder.ins().var(arg).into(),
                     Opcode::Ld => builder.ins().ld(arg).into(),
@@ -187,21 +190,29 @@ impl<'a> Inst<'a> {
                 let arg1 = arg1.build(builder, context);
                 let arg2 = arg2.build(builder, context);
                 match self.opcode {
-                    Opcode::Drv => builder.ins().drv(arg0, arg1, arg2).into(),
                     Opcode::Shl => builder.ins().shl(arg0, arg1, arg2).into(),
                     Opcode::Shr => builder.ins().shr(arg0, arg1, arg2).into(),
                     Opcode::Del => builder.ins().del(arg0, arg1, arg2).into(),
                     x => unreachable!("ternary {:?}", x),
                 }
             }
-            InstData::Quaternary(arg0, arg1, arg2, arg3) => {
-                let arg0 = arg0.build(builder, context);
-                let arg1 = arg1.build(builder, context);
-                let arg2 = arg2.build(builder, context);
-                let arg3 = arg3.build(builder, context);
-                match self.opcode {
-                    Opcode::DrvCond => builder.ins().drv_cond(arg0, arg1, arg2, arg3).into(),
-                    x => unreachable!("quaternary {:?}", x),
+            InstData::Signal(resolution, arg) => {
+                let arg = arg.build(builder, context);
+                builder.ins().sig_resolved(arg, resolution).into()
+            }
+            InstData::Drive(mode, target, value, delay, cond) => {
+                let target = target.build(builder, context);
+                let value = value.build(builder, context);
+                let delay = delay.build(builder, context);
+                match cond {
+                    Some(cond) => {
+                        let cond = cond.build(builder, context);
+                        builder
+                            .ins()
+                            .drv_cond_mode(target, value, delay, cond, mode)
+                            .into()
+                    }
+                    None => builder.ins().drv_mode(target, value, delay, mode).into(),
                 }
             }
             InstData::Reg(init, triggers) => {
@@ -215,6 +215,13 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                 data.opcode(),
                 data.get_const_time().unwrap()
             )?,
+            Opcode::ConstLogic => write!(
+                self.writer.sink,
+                "{} {} {}",
+                data.opcode(),
+                unit.value_type(unit.inst_result(inst)),
+                data.get_const_logic().unwrap()
+            )?,
             Opcode::ArrayUniform => {
                 write!(self.writer.sink, "[{} x ", data.imms()[0])?;
                 self.write_value_use(data.args()[0], true)?;
@@ -272,9 +279,7 @@ impl<'a, T: Write> UnitWriter<'a, T> {
             | Opcode::Uge
             | Opcode::Con
             | Opcode::Del
-            | Opcode::Sig
             | Opcode::Prb
-            | Opcode::Drv
             | Opcode::Var
             | Opcode::Ld
             | Opcode::St
@@ -289,12 +294,24 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                     first = false;
                 }
             }
-            Opcode::DrvCond => {
+            Opcode::Sig => {
                 write!(self.writer.sink, "{} ", data.opcode())?;
+                if let Some(resolution) = data.resolution() {
+                    write!(self.writer.sink, "{} ", resolution)?;
+                }
+                self.write_value_use(data.args()[0], true)?;
+            }
+            Opcode::Drv | Opcode::DrvCond => {
+                write!(self.writer.sink, "{} ", data.opcode())?;
+                if let Some(DelayMode::Transport) = data.delay_mode() {
+                    write!(self.writer.sink, "{} ", DelayMode::Transport)?;
+                }
                 let args = data.args();
                 self.write_value_use(args[0], true)?;
-                write!(self.writer.sink, " if ")?;
-                self.write_value_use(args[3], false)?;
+                if data.opcode() == Opcode::DrvCond {
+                    write!(self.writer.sink, " if ")?;
+                    self.write_value_use(args[3], false)?;
+                }
                 write!(self.writer.sink, ", ")?;
                 self.write_value_use(args[1], false)?;
                 write!(self.writer.sink, ", ")?;
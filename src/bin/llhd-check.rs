@@ -65,6 +65,9 @@ fn process_input(path: &str, matches: &ArgMatches) -> Result<()> {
     // Verify the module.
     let mut verifier = Verifier::new();
     verifier.verify_module(&module);
+    if !verifier.warnings().is_empty() {
+        eprint!("{}: Verification warnings:\n{}", path, verifier.warnings());
+    }
     verifier
         .finish()
         .map_err(|errs| anyhow!("Verification failed:\n{}", errs))?;
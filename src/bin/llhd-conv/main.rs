@@ -242,7 +242,7 @@ fn write_output(module: &llhd::ir::Module, output: &mut impl Write, format: Form
             Ok(())
         }
         Format::Mlir => {
-            llhd::mlir::write_module(output, module);
+            llhd::mlir::write_module(output, module)?;
             Ok(())
         }
         f => bail!("{} outputs not supported", f),
@@ -4,7 +4,8 @@
 
 use anyhow::{bail, Result};
 use itertools::Itertools;
-use llhd::ir::UnitKind;
+use llhd::{ir::UnitKind, value::Timescale};
+use num::Zero;
 use std::{
     collections::{HashMap, HashSet},
     io::Write,
@@ -15,13 +16,11 @@ use std::{
 /// Emit a module as Verilog code.
 pub fn write(output: &mut impl Write, module: &llhd::ir::Module) -> Result<()> {
     debug!("Emitting Verilog code");
+    let timescale = Timescale::for_module(module);
+
     let mut skipped = vec![];
     for unit in module.units() {
-        if unit.kind() == UnitKind::Entity {
-            if unit.name().is_global() {
-                write_entity(output, unit, &mut Context::default())?;
-            }
-        } else {
+        if unit.kind() != UnitKind::Entity {
             let name = unit.name();
             error!("Unit {} not supported", name);
             skipped.push(name);
@@ -33,15 +32,123 @@ pub fn write(output: &mut impl Write, module: &llhd::ir::Module) -> Result<()> {
             skipped.iter().format(", ")
         );
     }
+
+    // Assign a unique module name to each entity. Global entities keep their
+    // name, so they are named before local and anonymous ones.
+    let mut names = HashMap::new();
+    let mut taken = HashSet::new();
+    let global = module.entities().filter(|unit| unit.name().is_global());
+    let local = module.entities().filter(|unit| !unit.name().is_global());
+    for unit in global.chain(local) {
+        let base_name = sanitize_unit_name(unit.name());
+        let mut name = base_name.clone();
+        let mut i = 2;
+        while !taken.insert(name.clone()) {
+            name = format!("{}_{}", base_name, i);
+            i += 1;
+        }
+        names.insert(unit.id(), name);
+    }
+
+    // Emit the entities such that each module is defined before it is
+    // instantiated.
+    let mut ports: HashMap<_, Vec<_>> = HashMap::new();
+    for (i, id) in dependency_order(module)?.into_iter().enumerate() {
+        if i == 0 {
+            write!(output, "`timescale {}/{}\n\n", timescale, timescale)?;
+        }
+        let entity = module.unit(id);
+        let mut ctx = Context {
+            timescale,
+            ..Default::default()
+        };
+        for (ext_unit, _) in entity.extern_units() {
+            let instance = match module.lookup_ext_unit(ext_unit, id) {
+                Some(llhd::ir::LinkedUnit::Def(unit)) => Instance {
+                    module: names[&unit].clone(),
+                    ports: Some(ports[&unit].clone()),
+                },
+                _ => Instance {
+                    module: sanitize_unit_name(entity.extern_name(ext_unit)),
+                    ports: None,
+                },
+            };
+            ctx.instances.insert(ext_unit, instance);
+        }
+        write_entity(output, entity, &names[&id], &mut ctx)?;
+        let entity_ports = entity
+            .args()
+            .map(|v| ctx.value_name(entity, (id, v)))
+            .collect();
+        ports.insert(id, entity_ports);
+    }
     Ok(())
 }
 
+/// Order the entities of a module such that each one comes after the entities
+/// it instantiates.
+fn dependency_order(module: &llhd::ir::Module) -> Result<Vec<llhd::ir::UnitId>> {
+    fn visit(
+        module: &llhd::ir::Module,
+        id: llhd::ir::UnitId,
+        active: &mut HashSet<llhd::ir::UnitId>,
+        order: &mut Vec<llhd::ir::UnitId>,
+    ) -> Result<()> {
+        if order.contains(&id) {
+            return Ok(());
+        }
+        let unit = module.unit(id);
+        if !active.insert(id) {
+            bail!("Entity {} instantiates itself", unit.name());
+        }
+        for inst in unit.all_insts() {
+            if unit[inst].opcode() != llhd::ir::Opcode::Inst {
+                continue;
+            }
+            let ext_unit = unit[inst].get_ext_unit().unwrap();
+            if let Some(llhd::ir::LinkedUnit::Def(child)) = module.lookup_ext_unit(ext_unit, id) {
+                visit(module, child, active, order)?;
+            }
+        }
+        active.remove(&id);
+        order.push(id);
+        Ok(())
+    }
+
+    let mut order = vec![];
+    for unit in module.entities() {
+        visit(module, unit.id(), &mut HashSet::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
 type UnitValue = (llhd::ir::UnitId, llhd::ir::Value);
 
 #[derive(Default)]
 struct Context {
     name_map: HashMap<UnitValue, Rc<String>>,
     name_set: HashSet<Rc<String>>,
+    /// The signals assigned in `always` blocks.
+    regs: HashSet<UnitValue>,
+    /// The signals driven by the inputs of the module or by an instruction
+    /// other than `con`.
+    driven: HashSet<UnitValue>,
+    /// The signal and bit offset each subsignal aliases.
+    subsignals: HashMap<UnitValue, (UnitValue, usize)>,
+    /// The unit of delays.
+    timescale: Timescale,
+    /// The module instantiated for each external unit.
+    instances: HashMap<llhd::ir::ExtUnit, Instance>,
+}
+
+/// A module to be instantiated.
+#[derive(Clone)]
+struct Instance {
+    /// The name of the module.
+    module: String,
+    /// The names of the module's ports, or `None` for a declared module whose
+    /// ports must be connected by position.
+    ports: Option<Vec<Rc<String>>>,
 }
 
 impl Context {
@@ -50,25 +157,70 @@ impl Context {
         if let Some(name) = self.name_map.get(&value).cloned() {
             return name;
         }
-        let base_name: Rc<String> = Rc::new(match unit.get_name(value.1) {
+        let name = self.unique_name(match unit.get_name(value.1) {
             Some(name) => sanitize_name(name).collect(),
             None => format!("__{}", value.1),
         });
+        self.name_map.insert(value, name.clone());
+        name
+    }
+
+    /// Generate a name that does not clash with any other name in the module.
+    fn unique_name(&mut self, base_name: String) -> Rc<String> {
+        let base_name = Rc::new(base_name);
         let mut name = base_name.clone();
         let mut i = 2;
         while self.name_set.contains(&name) {
             name = Rc::new(format!("{}_{}", base_name, i));
             i += 1;
         }
-        self.name_map.insert(value, name.clone());
         self.name_set.insert(name.clone());
         name
     }
+
+    /// Generate an expression for a value, selecting the aliased bits of
+    /// subsignals.
+    fn expr(&mut self, unit: llhd::ir::Unit, value: llhd::ir::Value) -> Rc<String> {
+        let (root, offset) = self.subsignal(unit.id(), value);
+        let name = self.value_name(unit, root);
+        if root.1 == value {
+            return name;
+        }
+        let width = sizeof_type(&unit.value_type(value)).unwrap();
+        let total = sizeof_type(&unit.value_type(root.1)).unwrap();
+        Rc::new(select(&name, offset, width, total))
+    }
+
+    /// Find the signal and bit offset a subsignal aliases.
+    fn subsignal(&self, unit: llhd::ir::UnitId, value: llhd::ir::Value) -> (UnitValue, usize) {
+        self.subsignals
+            .get(&(unit, value))
+            .cloned()
+            .unwrap_or(((unit, value), 0))
+    }
+
+    /// Generate the delay control for a time value.
+    fn delay(&self, unit: llhd::ir::Unit, value: llhd::ir::Value) -> Result<String> {
+        let time = match unit.get_const_time(value) {
+            Some(time) => time,
+            None => bail!("Delay `{}` must be a constant", value.dump(&unit)),
+        };
+        let ticks = self.timescale.ticks(time.time());
+        Ok(if ticks.is_zero() {
+            String::new()
+        } else {
+            format!("#{} ", ticks)
+        })
+    }
 }
 
 /// Emit an LLHD entity as a new Verilog module.
-fn write_entity(output: &mut impl Write, entity: llhd::ir::Unit, ctx: &mut Context) -> Result<()> {
-    let name = sanitize_unit_name(entity.name());
+fn write_entity(
+    output: &mut impl Write,
+    entity: llhd::ir::Unit,
+    name: &str,
+    ctx: &mut Context,
+) -> Result<()> {
     debug!("Creating entity {} as `{}`", entity.name(), name);
 
     // Emit the module header.
@@ -78,12 +230,21 @@ fn write_entity(output: &mut impl Write, entity: llhd::ir::Unit, ctx: &mut Conte
     write!(output, "module {} ({});\n", name, ports.format(", "))?;
 
     // Emit the port declarations.
+    ctx.regs = procedural_signals(entity)
+        .into_iter()
+        .map(|v| (entity.id(), v))
+        .collect();
     let ports = entity
         .input_args()
         .zip(repeat("input"))
         .chain(entity.output_args().zip(repeat("output")));
     for (v, dir) in ports {
         let n = ctx.value_name(entity, (entity.id(), v));
+        let dir = if ctx.regs.contains(&(entity.id(), v)) {
+            "output reg"
+        } else {
+            dir
+        };
         write!(
             output,
             "    {} {} {};\n",
@@ -99,20 +260,517 @@ fn write_entity(output: &mut impl Write, entity: llhd::ir::Unit, ctx: &mut Conte
 }
 
 /// Emit an LLHD entity within an existing Verilog module.
+///
+/// Signals become nets, and all other values become wires that are assigned
+/// once. Signals that are stored by `reg`, delayed by `del`, or driven
+/// conditionally or with transport delay become variables assigned in
+/// `always` blocks instead. Arrays and structs are flattened into bit vectors,
+/// with the first element or field in the least significant bits.
 fn write_entity_body(
     output: &mut impl Write,
     entity: llhd::ir::Unit,
-    _ctx: &mut Context,
+    ctx: &mut Context,
     _bound: HashMap<llhd::ir::Value, llhd::ir::Value>,
 ) -> Result<()> {
     debug!("Emitting entity {}", entity.name());
     write!(output, "\n    // Entity {}\n", entity.name())?;
+
+    // Declare the signals and values. Signals connected by `con` are assigned
+    // from the driven side.
+    let mut driven = driven_signals(entity);
+    ctx.driven = driven
+        .iter()
+        .cloned()
+        .chain(entity.input_args())
+        .map(|v| (entity.id(), v))
+        .collect();
+    for inst in entity.all_insts() {
+        if entity[inst].opcode() == llhd::ir::Opcode::Con {
+            driven.extend(entity[inst].args().iter().map(|&v| signal_root(entity, v)));
+        }
+    }
+    for inst in entity.all_insts() {
+        let data = &entity[inst];
+        let value = match entity.get_inst_result(inst) {
+            Some(value) => value,
+            None => continue,
+        };
+        let ty = entity.value_type(value);
+        match data.opcode() {
+            llhd::ir::Opcode::Sig => {
+                let name = ctx.value_name(entity, (entity.id(), value));
+                let init = data.args()[0];
+                let init = entity
+                    .get_const(init)
+                    .and_then(|v| literal(&v))
+                    .unwrap_or_else(|| ctx.expr(entity, init).to_string());
+                let (kind, init) = if ctx.regs.contains(&(entity.id(), value)) {
+                    ("reg", Some(init))
+                } else if driven.contains(&value) {
+                    (data.resolution().map(net_kind).unwrap_or("wire"), None)
+                } else {
+                    ("wire", Some(init))
+                };
+                writeln!(output, "    {};", declare(kind, &ty, &name, init)?)?;
+            }
+            llhd::ir::Opcode::ExtField | llhd::ir::Opcode::ExtSlice if ty.is_signal() => {
+                let target = data.args()[0];
+                let target_ty = entity.value_type(target);
+                let (offset, _) = match data.opcode() {
+                    llhd::ir::Opcode::ExtField => field_range(&target_ty, data.imms()[0])?,
+                    _ => slice_range(&target_ty, data.imms()[0], data.imms()[1])?,
+                };
+                let (root, base) = ctx.subsignal(entity.id(), target);
+                ctx.subsignals
+                    .insert((entity.id(), value), (root, base + offset));
+            }
+            _ if ty.is_signal() => bail!(
+                "Instruction `{}` not supported in Verilog output",
+                inst.dump(&entity)
+            ),
+            llhd::ir::Opcode::ConstTime => (),
+            _ => {
+                let name = ctx.value_name(entity, (entity.id(), value));
+                writeln!(output, "    {};", declare("wire", &ty, &name, None)?)?;
+            }
+        }
+    }
+    writeln!(output)?;
+
+    // Emit the assignments and storage elements.
     for inst in entity.all_insts() {
-        write!(output, "    // {}\n", inst.dump(&entity))?;
+        write_inst(output, entity, inst, ctx)?;
+    }
+    Ok(())
+}
+
+/// Emit the assignment or `always` block of an instruction.
+fn write_inst(
+    output: &mut impl Write,
+    unit: llhd::ir::Unit,
+    inst: llhd::ir::Inst,
+    ctx: &mut Context,
+) -> Result<()> {
+    use llhd::ir::{DelayMode, Opcode};
+    let data = &unit[inst];
+    let args: Vec<_> = data.args().iter().map(|&v| ctx.expr(unit, v)).collect();
+    let expr = match data.opcode() {
+        Opcode::ConstInt => Some(literal(&data.get_const_int().unwrap().clone().into()).unwrap()),
+        Opcode::ConstLogic => {
+            Some(literal(&data.get_const_logic().unwrap().clone().into()).unwrap())
+        }
+        Opcode::Alias | Opcode::Prb => Some(args[0].to_string()),
+        Opcode::ArrayUniform => Some(format!("{{{}{{{}}}}}", data.imms()[0], args[0])),
+        Opcode::Array | Opcode::Struct => Some(format!("{{{}}}", args.iter().rev().format(", "))),
+        Opcode::Not => Some(format!("~{}", args[0])),
+        Opcode::Neg => Some(format!("-{}", args[0])),
+        Opcode::Add
+        | Opcode::Sub
+        | Opcode::And
+        | Opcode::Or
+        | Opcode::Xor
+        | Opcode::Umul
+        | Opcode::Smul
+        | Opcode::Udiv
+        | Opcode::Umod
+        | Opcode::Urem
+        | Opcode::Eq
+        | Opcode::Neq
+        | Opcode::Ult
+        | Opcode::Ugt
+        | Opcode::Ule
+        | Opcode::Uge => Some(format!(
+            "{} {} {}",
+            args[0],
+            binary_operator(data.opcode()),
+            args[1]
+        )),
+        Opcode::Sdiv | Opcode::Srem | Opcode::Slt | Opcode::Sgt | Opcode::Sle | Opcode::Sge => {
+            Some(format!(
+                "$signed({}) {} $signed({})",
+                args[0],
+                binary_operator(data.opcode()),
+                args[1]
+            ))
+        }
+        Opcode::Smod => {
+            // The remainder takes the sign of the dividend, the modulus the
+            // sign of the divisor. All operands must be signed, since Verilog
+            // evaluates an expression as unsigned if any of them is.
+            let width = sizeof_type(&unit.inst_type(inst))?;
+            let rem = format!("$signed({}) % $signed({})", args[0], args[1]);
+            Some(format!(
+                "{rem} != 0 && {} != {} ? {rem} + $signed({}) : {rem}",
+                select(&args[0], width - 1, 1, width),
+                select(&args[1], width - 1, 1, width),
+                args[1],
+                rem = rem,
+            ))
+        }
+        Opcode::Shl | Opcode::Shr => {
+            let ty = unit.value_type(data.args()[0]);
+            let amount = match ty.as_ref() {
+                llhd::ArrayType(_, elem) => format!("{} * {}", args[2], sizeof_type(elem)?),
+                _ => args[2].to_string(),
+            };
+            Some(if data.opcode() == Opcode::Shl {
+                let hidden = sizeof_type(&unit.value_type(data.args()[1]))?;
+                format!("({{{}, {}}} << {}) >> {}", args[0], args[1], amount, hidden)
+            } else {
+                format!("{{{}, {}}} >> {}", args[1], args[0], amount)
+            })
+        }
+        Opcode::Mux => {
+            let width = sizeof_type(&unit.inst_type(inst))?;
+            let total = sizeof_type(&unit.value_type(data.args()[0]))?;
+            Some(if width == total {
+                args[0].to_string()
+            } else if width == 1 {
+                format!("{}[{}]", args[0], args[1])
+            } else {
+                format!("{}[{} * {} +: {}]", args[0], args[1], width, width)
+            })
+        }
+        Opcode::ExtField | Opcode::ExtSlice if !unit.inst_type(inst).is_signal() => {
+            let ty = unit.value_type(data.args()[0]);
+            let (offset, width) = match data.opcode() {
+                Opcode::ExtField => field_range(&ty, data.imms()[0])?,
+                _ => slice_range(&ty, data.imms()[0], data.imms()[1])?,
+            };
+            Some(select(&args[0], offset, width, sizeof_type(&ty)?))
+        }
+        Opcode::InsField | Opcode::InsSlice => {
+            let ty = unit.value_type(data.args()[0]);
+            let (offset, width) = match data.opcode() {
+                Opcode::InsField => field_range(&ty, data.imms()[0])?,
+                _ => slice_range(&ty, data.imms()[0], data.imms()[1])?,
+            };
+            let total = sizeof_type(&ty)?;
+            let mut parts = vec![];
+            if offset + width < total {
+                parts.push(select(
+                    &args[0],
+                    offset + width,
+                    total - offset - width,
+                    total,
+                ));
+            }
+            parts.push(args[1].to_string());
+            if offset > 0 {
+                parts.push(select(&args[0], 0, offset, total));
+            }
+            Some(format!("{{{}}}", parts.iter().format(", ")))
+        }
+        Opcode::Drv | Opcode::DrvCond | Opcode::Del => {
+            let delay = ctx.delay(unit, data.args()[2])?;
+            let value = &args[1];
+            // A delayed nonblocking assignment has transport semantics, and
+            // Verilog has no conditional continuous assignment that would
+            // provide inertial semantics instead.
+            if data.opcode() == Opcode::DrvCond
+                && data.delay_mode() != Some(DelayMode::Transport)
+                && !delay.is_empty()
+            {
+                bail!(
+                    "Conditional inertial drive `{}` with a delay not supported in Verilog output",
+                    inst.dump(&unit)
+                );
+            }
+            if ctx
+                .regs
+                .contains(&ctx.subsignal(unit.id(), data.args()[0]).0)
+            {
+                let cond = match data.opcode() {
+                    Opcode::DrvCond => format!("if ({}) ", args[3]),
+                    _ => String::new(),
+                };
+                writeln!(
+                    output,
+                    "    always @(*) {}{} <= {}{};",
+                    cond, args[0], delay, value
+                )?;
+            } else {
+                writeln!(output, "    assign {}{} = {};", delay, args[0], value)?;
+            }
+            None
+        }
+        Opcode::Con => {
+            let driven = |v| ctx.driven.contains(&ctx.subsignal(unit.id(), v).0);
+            match (driven(data.args()[0]), driven(data.args()[1])) {
+                (true, false) => writeln!(output, "    assign {} = {};", args[1], args[0])?,
+                (false, true) => writeln!(output, "    assign {} = {};", args[0], args[1])?,
+                _ => bail!(
+                    "`con` not supported between two driven or two undriven signals: `{}`",
+                    inst.dump(&unit)
+                ),
+            }
+            None
+        }
+        Opcode::Reg => {
+            write_reg(output, unit, inst, ctx)?;
+            None
+        }
+        Opcode::Inst => {
+            let instance = ctx.instances[&data.get_ext_unit().unwrap()].clone();
+            let name = ctx.unique_name(format!("{}_inst", instance.module));
+            match instance.ports {
+                Some(ports) if !ports.is_empty() => {
+                    let conns = ports
+                        .iter()
+                        .zip(args.iter())
+                        .map(|(port, arg)| format!("        .{}({})", port, arg));
+                    writeln!(output, "    {} {} (", instance.module, name)?;
+                    writeln!(output, "{}", conns.format(",\n"))?;
+                    writeln!(output, "    );")?;
+                }
+                _ => writeln!(
+                    output,
+                    "    {} {} ({});",
+                    instance.module,
+                    name,
+                    args.iter().format(", ")
+                )?,
+            }
+            None
+        }
+        Opcode::Sig | Opcode::ConstTime | Opcode::ExtField | Opcode::ExtSlice | Opcode::Halt => {
+            None
+        }
+        _ => bail!(
+            "Instruction `{}` not supported in Verilog output",
+            inst.dump(&unit)
+        ),
+    };
+    if let Some(expr) = expr {
+        let name = ctx.value_name(unit, (unit.id(), unit.inst_result(inst)));
+        writeln!(output, "    assign {} = {};", name, expr)?;
+    }
+    Ok(())
+}
+
+/// Emit a `reg` instruction as an `always` block.
+///
+/// Level triggers become asynchronous sets and resets. Since the block cannot
+/// tell which of several edges fired, at most one edge trigger is supported,
+/// and it must come last.
+fn write_reg(
+    output: &mut impl Write,
+    unit: llhd::ir::Unit,
+    inst: llhd::ir::Inst,
+    ctx: &mut Context,
+) -> Result<()> {
+    use llhd::ir::RegMode;
+    let data = &unit[inst];
+    let target = ctx.expr(unit, data.args()[0]);
+    let triggers: Vec<_> = data.triggers().collect();
+    let is_edge = |mode| matches!(mode, RegMode::Rise | RegMode::Fall | RegMode::Both);
+    let edges = triggers.iter().filter(|t| is_edge(t.mode)).count();
+    if edges > 1 || (edges == 1 && !is_edge(triggers.last().unwrap().mode)) {
+        bail!(
+            "Register `{}` not supported in Verilog output; only a single edge trigger after all level triggers can be expressed",
+            inst.dump(&unit)
+        );
+    }
+
+    // Emit the sensitivity list.
+    let mut events = vec![];
+    let mut branches = vec![];
+    for t in &triggers {
+        let trigger = ctx.expr(unit, t.trigger);
+        let (event, mut cond) = match t.mode {
+            RegMode::Low => (
+                format!("negedge {}", trigger),
+                Some(format!("!{}", trigger)),
+            ),
+            RegMode::High => (format!("posedge {}", trigger), Some(trigger.to_string())),
+            RegMode::Rise => (format!("posedge {}", trigger), None),
+            RegMode::Fall => (format!("negedge {}", trigger), None),
+            RegMode::Both => (trigger.to_string(), None),
+        };
+        if let Some(gate) = t.gate {
+            let gate = ctx.expr(unit, gate);
+            cond = Some(match cond {
+                Some(cond) => format!("{} && {}", cond, gate),
+                None => gate.to_string(),
+            });
+        }
+        events.push(event);
+        branches.push((cond, ctx.expr(unit, t.data)));
+    }
+    if edges == 0 {
+        writeln!(output, "    always @(*)")?;
+    } else {
+        writeln!(output, "    always @({})", events.iter().format(", "))?;
+    }
+
+    // Emit the branches in order of precedence.
+    for (i, (cond, value)) in branches.into_iter().enumerate() {
+        let keyword = match (i, cond) {
+            (0, Some(cond)) => format!("if ({}) ", cond),
+            (0, None) => String::new(),
+            (_, Some(cond)) => format!("else if ({}) ", cond),
+            (_, None) => "else ".to_string(),
+        };
+        writeln!(output, "        {}{} <= {};", keyword, target, value)?;
     }
     Ok(())
 }
 
+/// Determine the signals which are assigned in `always` blocks.
+///
+/// These are the targets of `reg` and `del`, and of `drv` with a condition or
+/// transport delay, and must be declared as variables rather than nets. Only
+/// conditional drives without a delay or with transport delay are supported.
+fn procedural_signals(unit: llhd::ir::Unit) -> HashSet<llhd::ir::Value> {
+    use llhd::ir::{DelayMode, Opcode};
+    let mut signals = HashSet::new();
+    for inst in unit.all_insts() {
+        let data = &unit[inst];
+        let procedural = match data.opcode() {
+            Opcode::Reg | Opcode::Del | Opcode::DrvCond => true,
+            Opcode::Drv => data.delay_mode() == Some(DelayMode::Transport),
+            _ => false,
+        };
+        if procedural {
+            signals.insert(signal_root(unit, data.args()[0]));
+        }
+    }
+    signals
+}
+
+/// Determine the signals which are driven within a unit, not counting `con`.
+fn driven_signals(unit: llhd::ir::Unit) -> HashSet<llhd::ir::Value> {
+    use llhd::ir::Opcode;
+    let mut signals = HashSet::new();
+    for inst in unit.all_insts() {
+        let data = &unit[inst];
+        match data.opcode() {
+            Opcode::Reg | Opcode::Del | Opcode::Drv | Opcode::DrvCond => {
+                signals.insert(signal_root(unit, data.args()[0]));
+            }
+            Opcode::Inst => {
+                signals.extend(data.output_args().iter().map(|&v| signal_root(unit, v)));
+            }
+            _ => (),
+        }
+    }
+    signals
+}
+
+/// Find the signal of which a value is a subsignal.
+fn signal_root(unit: llhd::ir::Unit, mut value: llhd::ir::Value) -> llhd::ir::Value {
+    while let Some(inst) = unit.get_value_inst(value) {
+        match unit[inst].opcode() {
+            llhd::ir::Opcode::ExtField | llhd::ir::Opcode::ExtSlice => value = unit[inst].args()[0],
+            _ => break,
+        }
+    }
+    value
+}
+
+/// Format the declaration of a net or variable.
+fn declare(kind: &str, ty: &llhd::Type, name: &str, init: Option<String>) -> Result<String> {
+    let mut decl = kind.to_string();
+    let range = flatten_type(ty)?;
+    if !range.is_empty() {
+        decl.push(' ');
+        decl.push_str(&range);
+    }
+    decl.push(' ');
+    decl.push_str(name);
+    if let Some(init) = init {
+        decl.push_str(" = ");
+        decl.push_str(&init);
+    }
+    Ok(decl)
+}
+
+/// The net type implementing a signal resolution function.
+fn net_kind(resolution: llhd::ir::Resolution) -> &'static str {
+    match resolution {
+        llhd::ir::Resolution::WiredAnd => "wand",
+        llhd::ir::Resolution::WiredOr => "wor",
+        llhd::ir::Resolution::Tristate => "tri",
+    }
+}
+
+/// The Verilog operator of a binary instruction.
+fn binary_operator(opcode: llhd::ir::Opcode) -> &'static str {
+    use llhd::ir::Opcode;
+    match opcode {
+        Opcode::Add => "+",
+        Opcode::Sub => "-",
+        Opcode::And => "&",
+        Opcode::Or => "|",
+        Opcode::Xor => "^",
+        Opcode::Umul | Opcode::Smul => "*",
+        Opcode::Udiv | Opcode::Sdiv => "/",
+        Opcode::Umod | Opcode::Urem | Opcode::Srem => "%",
+        Opcode::Eq => "==",
+        Opcode::Neq => "!=",
+        Opcode::Ult | Opcode::Slt => "<",
+        Opcode::Ugt | Opcode::Sgt => ">",
+        Opcode::Ule | Opcode::Sle => "<=",
+        Opcode::Uge | Opcode::Sge => ">=",
+        _ => unreachable!("{} is not a binary operator", opcode),
+    }
+}
+
+/// Format a constant value as a Verilog literal.
+fn literal(value: &llhd::Value) -> Option<String> {
+    match value {
+        llhd::Value::Int(v) => Some(format!("{}'d{}", v.width, v.value)),
+        llhd::Value::Logic(v) => {
+            let bits = v.0.iter().rev().map(|bit| bit.to_vcd_char());
+            Some(format!("{}'b{}", v.width(), bits.collect::<String>()))
+        }
+        llhd::Value::Array(llhd::ArrayValue(fields))
+        | llhd::Value::Struct(llhd::StructValue(fields)) => {
+            let fields: Option<Vec<_>> = fields.iter().rev().map(literal).collect();
+            Some(format!("{{{}}}", fields?.iter().format(", ")))
+        }
+        llhd::Value::Void | llhd::Value::Time(_) => None,
+    }
+}
+
+/// Select `width` bits at `offset` of a value of `total` bits.
+fn select(name: &str, offset: usize, width: usize, total: usize) -> String {
+    if offset == 0 && width == total {
+        name.to_string()
+    } else if width == 1 {
+        format!("{}[{}]", name, offset)
+    } else {
+        format!("{}[{}:{}]", name, offset + width - 1, offset)
+    }
+}
+
+/// Compute the bit offset and width of a field, element, or bit of a type.
+fn field_range(ty: &llhd::Type, index: usize) -> Result<(usize, usize)> {
+    match ty.as_ref() {
+        llhd::SignalType(ty) => field_range(ty, index),
+        llhd::StructType(fields) => {
+            let offset = fields[..index]
+                .iter()
+                .map(sizeof_type)
+                .sum::<Result<usize>>()?;
+            Ok((offset, sizeof_type(&fields[index])?))
+        }
+        _ => slice_range(ty, index, 1),
+    }
+}
+
+/// Compute the bit offset and width of a slice of elements or bits of a type.
+fn slice_range(ty: &llhd::Type, start: usize, length: usize) -> Result<(usize, usize)> {
+    match ty.as_ref() {
+        llhd::SignalType(ty) => slice_range(ty, start, length),
+        llhd::ArrayType(_, elem) => {
+            let width = sizeof_type(elem)?;
+            Ok((start * width, length * width))
+        }
+        _ => Ok((start, length)),
+    }
+}
+
 /// Make a unit name printable in Verilog.
 fn sanitize_unit_name(name: &llhd::ir::UnitName) -> String {
     let mut out = String::new();
@@ -148,7 +806,7 @@ fn flatten_type(ty: &llhd::Type) -> Result<String> {
 fn sizeof_type(ty: &llhd::Type) -> Result<usize> {
     match ty.as_ref() {
         llhd::VoidType => Ok(0),
-        llhd::IntType(w) => Ok(*w),
+        llhd::IntType(w) | llhd::LogicType(w) => Ok(*w),
         llhd::EnumType(w) => {
             Ok((usize::max_value().count_ones() - w.next_power_of_two().leading_zeros()) as usize)
         }
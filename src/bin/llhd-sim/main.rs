@@ -6,20 +6,16 @@
 
 #[macro_use]
 extern crate clap;
-#[macro_use]
-extern crate log;
 
-use crate::tracer::Tracer;
+mod repl;
+
 use anyhow::{anyhow, Context, Result};
 use clap::Arg;
+use llhd::sim::{
+    coverage::Coverage, stimulus::Stimulus, tracer, value::TimeValue, Backend, Simulation,
+};
 use std::{fs::File, io::prelude::*};
 
-mod builder;
-mod engine;
-mod state;
-pub mod tracer;
-pub mod value;
-
 fn main() -> Result<()> {
     // Configure the logger.
     pretty_env_logger::init_custom_env("LLHD_LOG");
@@ -33,6 +29,20 @@ fn main() -> Result<()> {
                 .long("sequential")
                 .help("Disable parallelization"),
         )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .takes_value(true)
+                .possible_values(&["interpreter", "bytecode"])
+                .help("The backend that executes the design (default: interpreter)"),
+        )
+        .arg(
+            Arg::with_name("interactive")
+                .short("i")
+                .long("interactive")
+                .conflicts_with_all(&["num-steps", "until", "save-checkpoint"])
+                .help("Debug the simulation interactively"),
+        )
         .arg(
             Arg::with_name("OUTPUT")
                 .short("o")
@@ -40,18 +50,129 @@ fn main() -> Result<()> {
                 .takes_value(true)
                 .help("Trace into an output file"),
         )
+        .arg(
+            Arg::with_name("timescale")
+                .long("timescale")
+                .takes_value(true)
+                .value_name("TIME")
+                .help("The unit of time in traces, e.g. 1ns (default: derived from the design)"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN")
+                .help("Only trace scopes whose path matches a glob pattern, e.g. @top.@cpu*"),
+        )
+        .arg(
+            Arg::with_name("trace-depth")
+                .long("trace-depth")
+                .takes_value(true)
+                .value_name("N")
+                .help("Only trace N levels of hierarchy of each traced scope"),
+        )
+        .arg(
+            Arg::with_name("coverage")
+                .long("coverage")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Collect toggle and block coverage, and write it to a JSON file"),
+        )
+        .arg(
+            Arg::with_name("merge-coverage")
+                .long("merge-coverage")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FILE")
+                .requires("coverage")
+                .help("Merge the coverage of an earlier run into the written coverage"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .help("Report the activity of each instance, unit, and signal"),
+        )
         .arg(
             Arg::with_name("INPUT")
                 .help("The input file to simulate")
                 .required(true)
                 .index(1),
         )
+        .arg(
+            Arg::with_name("stimulus")
+                .long("stimulus")
+                .takes_value(true)
+                .help("Apply values to the top-level inputs from a .csv or .vcd file"),
+        )
+        .arg(
+            Arg::with_name("init")
+                .long("init")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("SIGNAL=VALUE")
+                .help("Set the initial value of a signal"),
+        )
         .arg(
             Arg::with_name("num-steps")
                 .short("N")
                 .takes_value(true)
                 .help("Terminate after a fixed number of steps"),
         )
+        .arg(
+            Arg::with_name("until")
+                .long("until")
+                .takes_value(true)
+                .value_name("TIME")
+                .help("Terminate after a point in simulated time, e.g. 100ns"),
+        )
+        .arg(
+            Arg::with_name("delta-limit")
+                .long("delta-limit")
+                .takes_value(true)
+                .value_name("N")
+                .help(
+                    "Abort after N delta cycles in one time step, or never if 0 (default: 10000)",
+                ),
+        )
+        .arg(
+            Arg::with_name("xprop")
+                .long("xprop")
+                .help("Start the top-level ports and registers as X, and propagate unknown values"),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .takes_value(true)
+                .value_name("UNIT")
+                .help("The process or entity to simulate (default: the last one)"),
+        )
+        .arg(
+            Arg::with_name("save-checkpoint")
+                .long("save-checkpoint")
+                .takes_value(true)
+                .value_name("TIME")
+                .requires("checkpoint-file")
+                .help("Save the simulation state once a point in simulated time has passed"),
+        )
+        .arg(
+            Arg::with_name("checkpoint-file")
+                .long("checkpoint-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("The file the checkpoint is saved to"),
+        )
+        .arg(
+            Arg::with_name("restore")
+                .long("restore")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["top", "init", "stimulus"])
+                .help("Resume the simulation from a saved checkpoint"),
+        )
         .get_matches();
 
     // Load the input file.
@@ -71,6 +192,9 @@ fn main() -> Result<()> {
         // Verify the file for integrity.
         let mut verifier = llhd::verifier::Verifier::new();
         verifier.verify_module(&module);
+        if !verifier.warnings().is_empty() {
+            eprint!("{}: Verification warnings:\n{}", path, verifier.warnings());
+        }
         verifier
             .finish()
             .map_err(|e| anyhow!("{}", e))
@@ -79,39 +203,279 @@ fn main() -> Result<()> {
         module
     };
 
-    // Build the simulation state for this module.
-    let mut state = builder::build(&module).with_context(|| "failed to initialize simulation")?;
+    // Build the simulation for this module, or restore it from a checkpoint.
+    let mut sim = if let Some(path) = matches.value_of("restore") {
+        let file =
+            File::open(path).with_context(|| format!("failed to open checkpoint {}", path))?;
+        Simulation::restore(&module, std::io::BufReader::new(file))
+            .with_context(|| format!("failed to restore checkpoint from {}", path))?
+    } else {
+        match matches.value_of("top") {
+            Some(top) => Simulation::with_top(&module, top),
+            None => Simulation::new(&module),
+        }
+        .with_context(|| "failed to initialize simulation")?
+    };
+    sim.set_parallelize(!matches.is_present("sequential"));
+    if matches.value_of("backend") == Some("bytecode") {
+        sim.set_backend(Backend::Bytecode);
+    }
+    if let Some(limit) = matches.value_of("delta-limit") {
+        let limit = limit
+            .parse()
+            .with_context(|| format!("invalid delta limit `{}`", limit))?;
+        sim.set_delta_limit(if limit == 0 { None } else { Some(limit) });
+    }
+    if matches.is_present("xprop") {
+        sim.enable_xprop();
+    }
+
+    // Print the messages of the design over the progress report, rather than
+    // appending them to it.
+    sim.register_extern("llhd.print", |call| {
+        println!("\r{}\x1b[0K", call.message(0));
+        Ok(None)
+    });
+
+    // Apply the initial values and stimulus to the simulation.
+    for init in matches.values_of("init").into_iter().flatten() {
+        let (name, value) = init
+            .split_once('=')
+            .ok_or_else(|| anyhow!("initial value `{}` is not of the form SIGNAL=VALUE", init))?;
+        let value = sim.parse_value(name, value)?;
+        sim.initialize(name, value)?;
+    }
+    if let Some(path) = matches.value_of("stimulus") {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .with_context(|| format!("failed to read stimulus from {}", path))?;
+        let stimulus = if path.ends_with(".vcd") {
+            Stimulus::parse_vcd(&contents)
+        } else if path.ends_with(".csv") {
+            Stimulus::parse_csv(&contents)
+        } else {
+            return Err(anyhow!(
+                "Cannot determine stimulus format from file name `{}`",
+                path
+            ));
+        };
+        let stimulus = stimulus
+            .map_err(|e| anyhow!("{}", e))
+            .with_context(|| format!("failed to parse stimulus from {}", path))?;
+        sim.apply_stimulus(&stimulus)
+            .with_context(|| format!("failed to apply stimulus from {}", path))?;
+    }
+
+    // Read the coverage of earlier runs, such that errors are reported before
+    // simulating.
+    let mut merged_coverage = vec![];
+    for path in matches.values_of("merge-coverage").into_iter().flatten() {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .with_context(|| format!("failed to read coverage from {}", path))?;
+        let coverage = Coverage::parse(&contents)
+            .map_err(|e| anyhow!("{}", e))
+            .with_context(|| format!("failed to parse coverage from {}", path))?;
+        merged_coverage.push((path, coverage));
+    }
+    if matches.is_present("coverage") {
+        sim.enable_coverage();
+    }
+    if matches.is_present("profile") {
+        sim.enable_profile();
+    }
 
-    // Create a new tracer for this state that will generate some waveforms.
-    let mut tracer: Box<dyn Tracer> = if let Some(tracer_path) = matches.value_of("OUTPUT") {
+    // Attach a tracer to the simulation that will generate some waveforms.
+    if let Some(tracer_path) = matches.value_of("OUTPUT") {
         let file = File::create(tracer_path)
             .with_context(|| format!("failed to create output at {}", tracer_path))?;
+
+        // Determine the timescale and the traced scopes.
+        let timescale = match matches.value_of("timescale") {
+            Some(s) => llhd::assembly::parse_time(s)
+                .ok()
+                .and_then(|t| tracer::Timescale::from_time(&t))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "invalid timescale `{}`; expected 1, 10, or 100 of fs, ps, ns, us, ms, or s",
+                        s
+                    )
+                })?,
+            None => tracer::Timescale::for_module(&module),
+        };
+        let mut filter = tracer::ScopeFilter::new();
+        for pattern in matches.values_of("trace").into_iter().flatten() {
+            filter.add_pattern(pattern);
+        }
+        if let Some(depth) = matches.value_of("trace-depth") {
+            let depth = depth
+                .parse()
+                .with_context(|| format!("invalid trace depth `{}`", depth))?;
+            filter.set_depth(Some(depth));
+        }
+
         if tracer_path.ends_with(".vcd") {
-            Box::new(tracer::VcdTracer::new(file))
+            let mut tracer = tracer::VcdTracer::new(file);
+            tracer.set_timescale(timescale);
+            tracer.set_filter(filter);
+            sim.add_tracer(Box::new(tracer));
+        } else if tracer_path.ends_with(".fst") {
+            let mut tracer = tracer::FstTracer::new(file);
+            tracer.set_timescale(timescale);
+            tracer.set_filter(filter);
+            sim.add_tracer(Box::new(tracer));
+        } else if tracer_path.ends_with(".saif") {
+            let mut tracer = tracer::SaifTracer::new(file);
+            tracer.set_timescale(timescale);
+            tracer.set_filter(filter);
+            sim.add_tracer(Box::new(tracer));
         } else if tracer_path.ends_with(".dump") {
-            Box::new(tracer::DumpTracer::new(file))
+            sim.add_tracer(Box::new(tracer::DumpTracer::new(file)));
         } else {
             return Err(anyhow!(
                 "Cannot determine output format from file name `{}`",
                 tracer_path
             ));
         }
+    }
+
+    // Run the simulation to completion.
+    let parse_time = |s: &str| {
+        llhd::assembly::parse_time(s)
+            .map_err(|e| anyhow!("{}", e))
+            .with_context(|| format!("invalid time `{}`", s))
+    };
+    let step_limit = matches
+        .value_of("num-steps")
+        .map(|s| s.parse::<usize>().unwrap());
+    let time_limit = matches.value_of("until").map(parse_time).transpose()?;
+    let checkpoint = match matches.value_of("save-checkpoint") {
+        Some(time) => {
+            let path = matches.value_of("checkpoint-file").unwrap();
+            let file = File::create(path)
+                .with_context(|| format!("failed to create checkpoint at {}", path))?;
+            Some((parse_time(time)?, std::io::BufWriter::new(file)))
+        }
+        None => None,
+    };
+    let result = if matches.is_present("interactive") {
+        let stdin = std::io::stdin();
+        repl::run(&mut sim, stdin.lock(), std::io::stdout())
     } else {
-        Box::new(tracer::NullTracer)
+        run(&mut sim, step_limit, time_limit, checkpoint).map_err(Into::into)
     };
-    tracer.init(&state);
-
-    // Create the simulation engine and run the simulation to completion.
-    {
-        let step_limit = matches
-            .value_of("num-steps")
-            .map(|s| s.parse::<usize>().unwrap());
-        let mut engine = engine::Engine::new(&mut state, !matches.is_present("sequential"));
-        engine.run(&mut *tracer, step_limit);
+
+    // Flush the tracer, such that the trace leading up to an error is kept.
+    sim.finish();
+    result.with_context(|| format!("simulation failed at {}", sim.time()))?;
+
+    // Report the coverage.
+    if let Some(path) = matches.value_of("coverage") {
+        let mut coverage = sim.coverage().unwrap();
+        for (merged_path, merged) in &merged_coverage {
+            coverage
+                .merge(merged)
+                .map_err(|e| anyhow!("{}", e))
+                .with_context(|| format!("failed to merge coverage from {}", merged_path))?;
+        }
+        std::fs::write(path, coverage.to_json() + "\n")
+            .with_context(|| format!("failed to write coverage to {}", path))?;
+        print!("{}", coverage);
     }
 
-    // Flush the tracer.
-    tracer.finish(&state);
+    // Report the profile.
+    if let Some(profile) = sim.profile() {
+        print!("{}", profile);
+    }
+
+    // Report the unknown values that could not be propagated.
+    if let Some(warnings) = sim.xprop_warnings() {
+        if !warnings.is_empty() {
+            println!("X-propagation warnings:");
+        }
+        for warning in warnings {
+            println!("- {}", warning);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a simulation to completion, until a number of steps have been
+/// performed, or until a point in time has passed, periodically reporting
+/// progress.
+///
+/// If a checkpoint is requested, it is saved once its point in time has passed,
+/// or when the simulation stops before that.
+fn run(
+    sim: &mut Simulation,
+    until_step: Option<usize>,
+    until_time: Option<TimeValue>,
+    mut checkpoint: Option<(TimeValue, impl Write)>,
+) -> Result<(), llhd::sim::SimulationError> {
+    let mut last_heartbeat = std::time::UNIX_EPOCH;
+    loop {
+        if checkpoint
+            .as_ref()
+            .map(|(t, _)| sim.time().time() > t.time())
+            .unwrap_or(false)
+        {
+            save_checkpoint(sim, checkpoint.take().unwrap().1)?;
+        }
+        if until_step.map(|n| sim.steps() >= n).unwrap_or(false) {
+            break;
+        }
+        if until_time
+            .as_ref()
+            .map(|t| sim.time().time() > t.time())
+            .unwrap_or(false)
+        {
+            break;
+        }
+        let now = std::time::SystemTime::now();
+        if now
+            .duration_since(last_heartbeat)
+            .map(|x| x.as_millis())
+            .unwrap_or(0)
+            > 250
+        {
+            print!("\rSimulating -- {} (#{})\x1b[0K", sim.time(), sim.steps());
+            let _ = std::io::stdout().flush();
+            last_heartbeat = now;
+        }
+        if !sim.step()? {
+            break;
+        }
+    }
+
+    // If the simulation was stopped by the time limit, report that time rather
+    // than the time of the next pending step.
+    let time = match until_time {
+        Some(ref t) if sim.time().time() > t.time() => t,
+        _ => sim.time(),
+    };
+    println!("\rSimulating -- {} (#{})\x1b[0K", time, sim.steps());
+    if let Some((_, writer)) = checkpoint {
+        save_checkpoint(sim, writer)?;
+    }
+    Ok(())
+}
 
+/// Save a checkpoint of a simulation and report the point in time.
+fn save_checkpoint(
+    sim: &Simulation,
+    mut writer: impl Write,
+) -> Result<(), llhd::sim::SimulationError> {
+    sim.save_checkpoint(&mut writer)?;
+    writer
+        .flush()
+        .map_err(|e| llhd::sim::SimulationError::Checkpoint(e.to_string()))?;
+    println!(
+        "\rSaved checkpoint -- {} (#{})\x1b[0K",
+        sim.time(),
+        sim.steps()
+    );
     Ok(())
 }
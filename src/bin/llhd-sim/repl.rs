@@ -0,0 +1,408 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! An interactive debugger for simulations
+//!
+//! The debugger reads commands line by line and executes the simulation one
+//! step at a time. Each step either applies the events of a delta cycle or
+//! advances the physical time. Execution stops at breakpoints, which trigger
+//! when a signal changes or when a point in simulated time is reached.
+
+use anyhow::Result;
+use llhd::sim::{
+    tracer::Tracer,
+    value::{TimeValue, Value},
+    Scope, SignalRef, Simulation, State,
+};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+const HELP: &str = "\
+Commands:
+  step [N]             Simulate the next N steps (default 1), printing changes
+  continue             Simulate until a breakpoint triggers or no events remain
+  break SIGNAL|TIME    Stop when a signal changes or a time is reached
+  break                List the breakpoints
+  delete N             Remove breakpoint N
+  print PATH           Print a signal, or all signals in a scope
+  where [PATH]         Show the state of the instances whose path starts with PATH
+  time                 Show the time of the next step
+  help                 Show this message
+  quit                 Exit the debugger
+Commands may be abbreviated to their first letter.";
+
+/// A condition that stops the simulation.
+enum Breakpoint {
+    /// Stop after a step in which a signal changed.
+    Signal(SignalRef, String),
+    /// Stop before the first step at or after a point in time. Removed once it
+    /// has triggered.
+    Time(TimeValue),
+}
+
+/// A tracer that records which signals changed in the last step.
+struct ChangeTracer(Rc<RefCell<HashSet<SignalRef>>>);
+
+impl Tracer for ChangeTracer {
+    fn init(&mut self, _: &State) {}
+
+    fn step(&mut self, _: &State, changed: &HashSet<SignalRef>) {
+        *self.0.borrow_mut() = changed.clone();
+    }
+
+    fn finish(&mut self, _: &State) {}
+}
+
+/// Run the interactive debugger on a simulation until the input ends or the
+/// user quits.
+pub fn run(sim: &mut Simulation, input: impl BufRead, mut output: impl Write) -> Result<()> {
+    let changed = Rc::new(RefCell::new(HashSet::new()));
+    sim.add_tracer(Box::new(ChangeTracer(changed.clone())));
+    let mut debugger = Debugger {
+        sim,
+        changed,
+        breakpoints: vec![],
+        finished: false,
+    };
+    writeln!(output, "Type `help` for a list of commands.")?;
+    write!(output, "(llhd-sim) ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        if let Some(cmd) = words.next() {
+            let args: Vec<_> = words.collect();
+            match debugger.execute(cmd, &args, &mut output) {
+                Ok(true) => (),
+                Ok(false) => return Ok(()),
+                Err(e) => writeln!(output, "error: {}", e)?,
+            }
+        }
+        write!(output, "(llhd-sim) ")?;
+        output.flush()?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+struct Debugger<'a, 'll> {
+    sim: &'a mut Simulation<'ll>,
+    changed: Rc<RefCell<HashSet<SignalRef>>>,
+    breakpoints: Vec<Breakpoint>,
+    finished: bool,
+}
+
+impl Debugger<'_, '_> {
+    /// Execute a command. Returns false if the debugger should exit.
+    fn execute(&mut self, cmd: &str, args: &[&str], out: &mut impl Write) -> Result<bool> {
+        match (cmd, args) {
+            ("s", []) | ("step", []) => self.step(1, out)?,
+            ("s", [n]) | ("step", [n]) => self.step(n.parse()?, out)?,
+            ("c", []) | ("continue", []) => self.resume(out)?,
+            ("b", []) | ("break", []) => self.list_breakpoints(out)?,
+            ("b", [what]) | ("break", [what]) => self.add_breakpoint(what, out)?,
+            ("d", [n]) | ("delete", [n]) => {
+                let n: usize = n.parse()?;
+                if n == 0 || n > self.breakpoints.len() {
+                    anyhow::bail!("no breakpoint {}", n);
+                }
+                self.breakpoints.remove(n - 1);
+            }
+            ("p", [path]) | ("print", [path]) => self.print(path, out)?,
+            ("w", []) | ("where", []) => self.show_instances("", out)?,
+            ("w", [path]) | ("where", [path]) => self.show_instances(path, out)?,
+            ("t", []) | ("time", []) => self.show_time(out)?,
+            ("h", []) | ("help", []) => writeln!(out, "{}", HELP)?,
+            ("q", []) | ("quit", []) => return Ok(false),
+            _ => anyhow::bail!("invalid command `{}`; type `help` for a list", cmd),
+        }
+        Ok(true)
+    }
+
+    /// Simulate a single step. Returns false if the simulation has finished.
+    fn step_once(&mut self) -> Result<bool> {
+        if self.finished {
+            return Ok(false);
+        }
+        self.changed.borrow_mut().clear();
+        self.finished = !self.sim.step()?;
+        Ok(true)
+    }
+
+    /// Simulate a number of steps and print the signals changed by each.
+    fn step(&mut self, n: usize, out: &mut impl Write) -> Result<()> {
+        for _ in 0..n {
+            let time = self.sim.time().clone();
+            if !self.step_once()? {
+                break;
+            }
+            let changed = self.changed_signals();
+            writeln!(out, "{} (#{})", time, self.sim.steps())?;
+            for (name, value) in changed {
+                writeln!(out, "  {} = {}", name, value)?;
+            }
+        }
+        self.show_finished(out)
+    }
+
+    /// Simulate until a breakpoint triggers or the simulation finishes.
+    fn resume(&mut self, out: &mut impl Write) -> Result<()> {
+        loop {
+            let now = self.sim.time().clone();
+            let reached = self.breakpoints.iter().position(|bp| match bp {
+                Breakpoint::Time(t) => now.time() >= t.time(),
+                _ => false,
+            });
+            if let Some(i) = reached {
+                self.breakpoints.remove(i);
+                writeln!(out, "Reached {}", now)?;
+                return Ok(());
+            }
+            if !self.step_once()? {
+                break;
+            }
+            let changed = self.changed.borrow();
+            let hits: Vec<_> = self
+                .breakpoints
+                .iter()
+                .filter_map(|bp| match bp {
+                    Breakpoint::Signal(sig, name) if changed.contains(sig) => Some(name),
+                    _ => None,
+                })
+                .collect();
+            if !hits.is_empty() {
+                writeln!(out, "{} (#{})", now, self.sim.steps())?;
+                for name in hits {
+                    writeln!(out, "  {} = {}", name, self.sim.peek(name).unwrap())?;
+                }
+                return Ok(());
+            }
+        }
+        self.show_finished(out)
+    }
+
+    /// The names and values of the signals that changed in the last step.
+    fn changed_signals(&self) -> Vec<(String, Value)> {
+        let mut changed: Vec<_> = self
+            .changed
+            .borrow()
+            .iter()
+            .filter_map(|&sig| self.sim.signal_name(sig))
+            .map(|name| {
+                let value = self.sim.peek(&name).unwrap().clone();
+                (name, value)
+            })
+            .collect();
+        changed.sort_by(|a, b| a.0.cmp(&b.0));
+        changed
+    }
+
+    fn add_breakpoint(&mut self, what: &str, out: &mut impl Write) -> Result<()> {
+        let bp = if let Some(sig) = self.sim.signal(what) {
+            Breakpoint::Signal(sig, self.sim.signal_name(sig).unwrap())
+        } else if let Ok(time) = llhd::assembly::parse_time(what) {
+            Breakpoint::Time(time)
+        } else {
+            anyhow::bail!("`{}` is neither a signal nor a time", what);
+        };
+        self.breakpoints.push(bp);
+        writeln!(out, "Breakpoint {}", self.breakpoints.len()).map_err(Into::into)
+    }
+
+    fn list_breakpoints(&self, out: &mut impl Write) -> Result<()> {
+        if self.breakpoints.is_empty() {
+            writeln!(out, "No breakpoints")?;
+        }
+        for (i, bp) in self.breakpoints.iter().enumerate() {
+            match bp {
+                Breakpoint::Signal(_, name) => writeln!(out, "{}: change of {}", i + 1, name)?,
+                Breakpoint::Time(time) => writeln!(out, "{}: time {}", i + 1, time)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Print a signal, or all signals visible in a scope.
+    fn print(&self, path: &str, out: &mut impl Write) -> Result<()> {
+        if let Some(sig) = self.sim.signal(path) {
+            writeln!(out, "{} = {}", path, self.sim.state()[sig].value())?;
+            return Ok(());
+        }
+
+        // Look for a scope with the given path, which may omit the root.
+        fn find<'a>(scope: &'a Scope, prefix: &str, path: &str) -> Option<(&'a Scope, String)> {
+            let name = format!("{}{}", prefix, scope.name);
+            if name == path {
+                return Some((scope, name));
+            }
+            if !path.starts_with(&format!("{}.", name)) {
+                return None;
+            }
+            let prefix = format!("{}.", name);
+            scope.subscopes.iter().find_map(|s| find(s, &prefix, path))
+        }
+        let root = &self.sim.state().scope;
+        let (scope, name) = find(root, "", path)
+            .or_else(|| find(root, "", &format!("{}.{}", root.name, path)))
+            .ok_or_else(|| anyhow::anyhow!("no signal or scope named `{}`", path))?;
+        let mut signals: Vec<_> = scope
+            .probes
+            .iter()
+            .flat_map(|(&sig, names)| names.iter().map(move |n| (n, sig)))
+            .collect();
+        signals.sort();
+        for (n, sig) in signals {
+            writeln!(out, "{}.{} = {}", name, n, self.sim.state()[sig].value())?;
+        }
+        for subscope in &scope.subscopes {
+            writeln!(out, "{}.{} (scope)", name, subscope.name)?;
+        }
+        Ok(())
+    }
+
+    /// Show the state of all instances whose path starts with a prefix.
+    fn show_instances(&self, prefix: &str, out: &mut impl Write) -> Result<()> {
+        for inst in self.sim.instances() {
+            if !inst.path.starts_with(prefix) {
+                continue;
+            }
+            writeln!(out, "{} ({}): {}", inst.path, inst.unit, inst.state)?;
+            if let (Some(block), Some(i)) = (inst.block, inst.inst) {
+                writeln!(out, "  {}: {}", block, i)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn show_time(&self, out: &mut impl Write) -> Result<()> {
+        writeln!(out, "{} (#{})", self.sim.time(), self.sim.steps())?;
+        self.show_finished(out)
+    }
+
+    fn show_finished(&self, out: &mut impl Write) -> Result<()> {
+        if self.finished {
+            writeln!(out, "Simulation finished")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A process that increments a counter every nanosecond until it is 3.
+    const COUNTER: &str = "
+        proc @tb () -> (i2$ %count) {
+        entry:
+            %one = const i2 1
+            %two = const i2 2
+            %delay = const time 1ns
+            br %loop
+        loop:
+            %c = prb i2$ %count
+            %n = add i2 %c, %one
+            drv i2$ %count, %n, %delay
+            %done = eq i2 %c, %two
+            br %done, %wait, %stop
+        wait:
+            wait %loop for %delay
+        stop:
+            halt
+        }
+
+        entity @top () -> () {
+            %zero = const i2 0
+            %count = sig i2 %zero
+            inst @tb () -> (i2$ %count)
+        }
+    ";
+
+    /// Run the debugger on the counter with a script of commands and return
+    /// the output of each command.
+    fn debug(script: &str) -> Vec<String> {
+        let module = llhd::assembly::parse_module(COUNTER).unwrap();
+        let mut sim = Simulation::new(&module).unwrap();
+        let mut output = vec![];
+        run(&mut sim, Cursor::new(script), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .split("(llhd-sim) ")
+            .skip(1)
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn step() {
+        assert_eq!(
+            debug("step\nstep 2\nstep 5\n"),
+            vec![
+                "0s (#1)\n",
+                "1ns (#2)\n  @top.count = i2 1\n2ns (#3)\n  @top.count = i2 2\n",
+                "3ns (#4)\n  @top.count = i2 3\nSimulation finished\n",
+                "\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn break_on_signal() {
+        assert_eq!(
+            debug("break count\ncontinue\ncontinue\nbreak\n"),
+            vec![
+                "Breakpoint 1\n",
+                "1ns (#2)\n  @top.count = i2 1\n",
+                "2ns (#3)\n  @top.count = i2 2\n",
+                "1: change of @top.count\n",
+                "\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn break_on_time() {
+        assert_eq!(
+            debug("break 2ns\ncontinue\nprint count\ncontinue\n"),
+            vec![
+                "Breakpoint 1\n",
+                "Reached 2ns\n",
+                "count = i2 1\n",
+                "Simulation finished\n",
+                "\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn print() {
+        assert_eq!(
+            debug("step 3\nprint @top.count\nprint @top\nprint @tb\nprint nope\n")[1..],
+            [
+                "@top.count = i2 2\n",
+                "@top.count = i2 2\n@top.@tb (scope)\n",
+                "@top.@tb.count = i2 2\n",
+                "error: no signal or scope named `nope`\n",
+                "\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn show_instances() {
+        assert_eq!(
+            debug("step 2\nwhere\nstep 5\nwhere @top.@tb\n")[1..],
+            [
+                "@top.@tb (@tb): waiting until 2ns\n  wait: wait %delay, loop\n\
+                 @top (@top): waiting for @top.count\n",
+                "2ns (#3)\n  @top.count = i2 2\n3ns (#4)\n  @top.count = i2 3\nSimulation finished\n",
+                "@top.@tb (@tb): done\n  stop: halt\n",
+                "\n",
+            ]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+#[macro_use]
+extern crate clap;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Arg;
+use llhd::sim::tracediff::{diff, DiffOptions, Trace};
+
+fn main() {
+    // Exit with 1 if the traces differ and 2 if they cannot be compared, as
+    // `diff` does.
+    match main_inner() {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn main_inner() -> Result<bool> {
+    // Configure the logger.
+    pretty_env_logger::init_custom_env("LLHD_LOG");
+
+    // Parse the command line arguments.
+    let matches = app_from_crate!()
+        .about("Compares two simulation traces in the dump or VCD format.")
+        .arg(
+            Arg::with_name("ignore-deltas")
+                .short("d")
+                .long("ignore-deltas")
+                .help("Only compare the values at the end of each time step"),
+        )
+        .arg(
+            Arg::with_name("signal")
+                .short("s")
+                .long("signal")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN")
+                .help("Only compare the signals matching a pattern such as `top.*.q`"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .short("x")
+                .long("exclude")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATTERN")
+                .help("Do not compare the signals matching a pattern"),
+        )
+        .arg(
+            Arg::with_name("left")
+                .help("The reference trace")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("right")
+                .help("The trace to compare against the reference")
+                .required(true),
+        )
+        .get_matches();
+
+    let mut options = DiffOptions::new();
+    options.set_ignore_deltas(matches.is_present("ignore-deltas"));
+    for pattern in matches.values_of("signal").into_iter().flatten() {
+        options.add_include(pattern);
+    }
+    for pattern in matches.values_of("exclude").into_iter().flatten() {
+        options.add_exclude(pattern);
+    }
+
+    let left = read_trace(matches.value_of("left").unwrap())?;
+    let right = read_trace(matches.value_of("right").unwrap())?;
+    let divergences = diff(&left, &right, &options);
+    for divergence in &divergences {
+        println!("{}", divergence);
+    }
+    Ok(divergences.is_empty())
+}
+
+fn read_trace(path: &str) -> Result<Trace> {
+    let input = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read trace from {}", path))?;
+    Trace::parse(&input)
+        .map_err(|e| anyhow!("{}", e))
+        .with_context(|| format!("failed to parse trace from {}", path))
+}
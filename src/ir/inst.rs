@@ -7,8 +7,8 @@
 
 use crate::{
     ir::{Block, ExtUnit, Inst, Unit, UnitBuilder, Value},
-    ty::{array_ty, int_ty, pointer_ty, signal_ty, struct_ty, void_ty, Type},
-    value::{IntValue, TimeValue},
+    ty::{array_ty, int_ty, logic_ty, pointer_ty, signal_ty, struct_ty, void_ty, Type},
+    value::{IntValue, LogicValue, TimeValue},
 };
 use bitflags::bitflags;
 use std::borrow::Cow;
@@ -46,6 +46,7 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         match ty.as_ref() {
             TimeType => self.const_time(TimeValue::zero()),
             IntType(w) => self.const_int(IntValue::zero(*w)),
+            LogicType(w) => self.const_logic(LogicValue::zero(*w)),
             ArrayType(l, ty) => {
                 let name = self.name.take();
                 let elem = self.const_zero(ty);
@@ -92,6 +93,21 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.inst_result(inst)
     }
 
+    /// Construct the given value for logic type.
+    ///
+    /// This is a convenience function that creates the appropriate instruction
+    /// sequence to generate the given value for logic type.
+    pub fn const_logic(&mut self, value: impl Into<LogicValue>) -> Value {
+        let value = value.into();
+        let ty = value.ty();
+        let data = InstData::ConstLogic {
+            opcode: Opcode::ConstLogic,
+            imm: value,
+        };
+        let inst = self.build(data, ty);
+        self.inst_result(inst)
+    }
+
     /// Creates alias instruction to assign a new name to a value.
     pub fn alias(&mut self, x: Value) -> Value {
         let ty = self.value_type(x);
@@ -428,8 +444,10 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
                 array_ty(imm1, ty.unwrap_array().1.clone())
             } else if ty.is_int() {
                 int_ty(imm1)
+            } else if ty.is_logic() {
+                logic_ty(imm1)
             } else {
-                panic!("argument to `exts` must be of array or integer type");
+                panic!("argument to `exts` must be of array, integer, or logic type");
             }
         });
         let inst = self.build(
@@ -483,9 +501,22 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
 
     /// Creates sig instruction to allocate a signal
     pub fn sig(&mut self, x: Value) -> Value {
+        self.sig_resolved(x, None)
+    }
+
+    /// Creates sig instruction to allocate a signal that may have multiple
+    /// drivers, whose values are combined with a resolution function
+    pub fn sig_resolved(&mut self, x: Value, resolution: Option<Resolution>) -> Value {
         let ty = self.value_type(x);
         let ty = if ty.is_signal() { ty } else { signal_ty(ty) };
-        let inst = self.build_unary(Opcode::Sig, ty, x);
+        let inst = self.build(
+            InstData::Signal {
+                opcode: Opcode::Sig,
+                args: [x],
+                resolution,
+            },
+            ty,
+        );
         self.inst_result(inst)
     }
 
@@ -498,15 +529,49 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.inst_result(inst)
     }
 
-    /// Creates drv instruction to shedule signal value to change after a delay
+    /// Creates drv instruction to shedule signal value to change after an
+    /// inertial delay
     pub fn drv(&mut self, signal: Value, value: Value, delay: Value) -> Inst {
-        self.build_ternary(Opcode::Drv, void_ty(), signal, value, delay)
+        self.drv_mode(signal, value, delay, DelayMode::Inertial)
+    }
+
+    /// Creates drv instruction to shedule signal value to change after a delay
+    /// of the given mode
+    pub fn drv_mode(&mut self, signal: Value, value: Value, delay: Value, mode: DelayMode) -> Inst {
+        self.build(
+            InstData::Drive {
+                opcode: Opcode::Drv,
+                args: vec![signal, value, delay],
+                mode,
+            },
+            void_ty(),
+        )
     }
 
-    /// Creates drv_cond instruction to shedule signal value to change after a delay
-    /// if given condition is satisfied
+    /// Creates drv_cond instruction to shedule signal value to change after an
+    /// inertial delay if given condition is satisfied
     pub fn drv_cond(&mut self, signal: Value, value: Value, delay: Value, cond: Value) -> Inst {
-        self.build_quaternary(Opcode::DrvCond, void_ty(), signal, value, delay, cond)
+        self.drv_cond_mode(signal, value, delay, cond, DelayMode::Inertial)
+    }
+
+    /// Creates drv_cond instruction to shedule signal value to change after a
+    /// delay of the given mode if given condition is satisfied
+    pub fn drv_cond_mode(
+        &mut self,
+        signal: Value,
+        value: Value,
+        delay: Value,
+        cond: Value,
+        mode: DelayMode,
+    ) -> Inst {
+        self.build(
+            InstData::Drive {
+                opcode: Opcode::DrvCond,
+                args: vec![signal, value, delay, cond],
+                mode,
+            },
+            void_ty(),
+        )
     }
 
     /// Creates var instruction to allocate memory on stack with the initial value
@@ -636,23 +701,6 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         };
         self.build(data, ty)
     }
-
-    /// `a = opcode type x, y, z, w`
-    fn build_quaternary(
-        &mut self,
-        opcode: Opcode,
-        ty: Type,
-        x: Value,
-        y: Value,
-        z: Value,
-        w: Value,
-    ) -> Inst {
-        let data = InstData::Quaternary {
-            opcode,
-            args: [x, y, z, w],
-        };
-        self.build(data, ty)
-    }
 }
 
 /// Fundamental convenience forwards to the wrapped builder.
@@ -702,6 +750,8 @@ pub enum InstData {
     ConstInt { opcode: Opcode, imm: IntValue },
     /// `a = const time imm`
     ConstTime { opcode: Opcode, imm: TimeValue },
+    /// `a = const lN imm`
+    ConstLogic { opcode: Opcode, imm: LogicValue },
     /// `opcode imm, type x`
     Array {
         opcode: Opcode,
@@ -718,8 +768,6 @@ pub enum InstData {
     Binary { opcode: Opcode, args: [Value; 2] },
     /// `opcode type x, y, z`
     Ternary { opcode: Opcode, args: [Value; 3] },
-    /// `opcode type x, y, z, w`
-    Quaternary { opcode: Opcode, args: [Value; 4] },
     /// `opcode bb`
     Jump { opcode: Opcode, bbs: [Block; 1] },
     /// `opcode type [x, bb],*`
@@ -759,6 +807,18 @@ pub enum InstData {
         args: Vec<Value>,
         modes: Vec<RegMode>,
     },
+    /// `opcode [mode] type x (if w)?, y, z`
+    Drive {
+        opcode: Opcode,
+        args: Vec<Value>,
+        mode: DelayMode,
+    },
+    /// `a = opcode [resolution] type x`
+    Signal {
+        opcode: Opcode,
+        args: [Value; 1],
+        resolution: Option<Resolution>,
+    },
 }
 
 impl InstData {
@@ -767,13 +827,13 @@ impl InstData {
         match *self {
             InstData::ConstInt { opcode, .. } => opcode,
             InstData::ConstTime { opcode, .. } => opcode,
+            InstData::ConstLogic { opcode, .. } => opcode,
             InstData::Array { opcode, .. } => opcode,
             InstData::Aggregate { opcode, .. } => opcode,
             InstData::Nullary { opcode, .. } => opcode,
             InstData::Unary { opcode, .. } => opcode,
             InstData::Binary { opcode, .. } => opcode,
             InstData::Ternary { opcode, .. } => opcode,
-            InstData::Quaternary { opcode, .. } => opcode,
             InstData::Phi { opcode, .. } => opcode,
             InstData::Jump { opcode, .. } => opcode,
             InstData::Branch { opcode, .. } => opcode,
@@ -781,6 +841,8 @@ impl InstData {
             InstData::Call { opcode, .. } => opcode,
             InstData::InsExt { opcode, .. } => opcode,
             InstData::Reg { opcode, .. } => opcode,
+            InstData::Drive { opcode, .. } => opcode,
+            InstData::Signal { opcode, .. } => opcode,
         }
     }
 
@@ -789,13 +851,13 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &[],
             InstData::ConstTime { .. } => &[],
+            InstData::ConstLogic { .. } => &[],
             InstData::Array { args, .. } => args,
             InstData::Aggregate { args, .. } => args,
             InstData::Nullary { .. } => &[],
             InstData::Unary { args, .. } => args,
             InstData::Binary { args, .. } => args,
             InstData::Ternary { args, .. } => args,
-            InstData::Quaternary { args, .. } => args,
             InstData::Phi { args, .. } => args,
             InstData::Jump { .. } => &[],
             InstData::Branch { args, .. } => args,
@@ -813,6 +875,8 @@ impl InstData {
             } => &args[0..1],
             InstData::InsExt { args, .. } => args,
             InstData::Reg { args, .. } => args,
+            InstData::Drive { args, .. } => args,
+            InstData::Signal { args, .. } => args,
         }
     }
 
@@ -822,13 +886,13 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &mut [],
             InstData::ConstTime { .. } => &mut [],
+            InstData::ConstLogic { .. } => &mut [],
             InstData::Array { args, .. } => args,
             InstData::Aggregate { args, .. } => args,
             InstData::Nullary { .. } => &mut [],
             InstData::Unary { args, .. } => args,
             InstData::Binary { args, .. } => args,
             InstData::Ternary { args, .. } => args,
-            InstData::Quaternary { args, .. } => args,
             InstData::Phi { args, .. } => args,
             InstData::Jump { .. } => &mut [],
             InstData::Branch { args, .. } => args,
@@ -846,6 +910,8 @@ impl InstData {
             } => &mut args[0..1],
             InstData::InsExt { args, .. } => args,
             InstData::Reg { args, .. } => args,
+            InstData::Drive { args, .. } => args,
+            InstData::Signal { args, .. } => args,
         }
     }
 
@@ -854,13 +920,13 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &[],
             InstData::ConstTime { .. } => &[],
+            InstData::ConstLogic { .. } => &[],
             InstData::Array { imms, .. } => imms,
             InstData::Aggregate { .. } => &[],
             InstData::Nullary { .. } => &[],
             InstData::Unary { .. } => &[],
             InstData::Binary { .. } => &[],
             InstData::Ternary { .. } => &[],
-            InstData::Quaternary { .. } => &[],
             InstData::Phi { .. } => &[],
             InstData::Jump { .. } => &[],
             InstData::Branch { .. } => &[],
@@ -878,6 +944,8 @@ impl InstData {
             } => &imms[0..1],
             InstData::InsExt { imms, .. } => imms,
             InstData::Reg { .. } => &[],
+            InstData::Drive { .. } => &[],
+            InstData::Signal { .. } => &[],
         }
     }
 
@@ -937,6 +1005,22 @@ impl InstData {
         .cloned()
     }
 
+    /// Get the delay mode of a drive instruction.
+    pub fn delay_mode(&self) -> Option<DelayMode> {
+        match *self {
+            InstData::Drive { mode, .. } => Some(mode),
+            _ => None,
+        }
+    }
+
+    /// Get the resolution function of a signal instruction.
+    pub fn resolution(&self) -> Option<Resolution> {
+        match *self {
+            InstData::Signal { resolution, .. } => resolution,
+            _ => None,
+        }
+    }
+
     /// Get the register triggers.
     pub fn triggers(&self) -> impl Iterator<Item = RegTrigger> + '_ {
         self.data_args()
@@ -956,13 +1040,13 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &[],
             InstData::ConstTime { .. } => &[],
+            InstData::ConstLogic { .. } => &[],
             InstData::Array { .. } => &[],
             InstData::Aggregate { .. } => &[],
             InstData::Nullary { .. } => &[],
             InstData::Unary { .. } => &[],
             InstData::Binary { .. } => &[],
             InstData::Ternary { .. } => &[],
-            InstData::Quaternary { .. } => &[],
             InstData::Phi { bbs, .. } => bbs,
             InstData::Jump { bbs, .. } => bbs,
             InstData::Branch { bbs, .. } => bbs,
@@ -970,6 +1054,8 @@ impl InstData {
             InstData::Call { .. } => &[],
             InstData::InsExt { .. } => &[],
             InstData::Reg { .. } => &[],
+            InstData::Drive { .. } => &[],
+            InstData::Signal { .. } => &[],
         }
     }
 
@@ -979,13 +1065,13 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &mut [],
             InstData::ConstTime { .. } => &mut [],
+            InstData::ConstLogic { .. } => &mut [],
             InstData::Array { .. } => &mut [],
             InstData::Aggregate { .. } => &mut [],
             InstData::Nullary { .. } => &mut [],
             InstData::Unary { .. } => &mut [],
             InstData::Binary { .. } => &mut [],
             InstData::Ternary { .. } => &mut [],
-            InstData::Quaternary { .. } => &mut [],
             InstData::Phi { bbs, .. } => bbs,
             InstData::Jump { bbs, .. } => bbs,
             InstData::Branch { bbs, .. } => bbs,
@@ -993,6 +1079,8 @@ impl InstData {
             InstData::Call { .. } => &mut [],
             InstData::InsExt { .. } => &mut [],
             InstData::Reg { .. } => &mut [],
+            InstData::Drive { .. } => &mut [],
+            InstData::Signal { .. } => &mut [],
         }
     }
 
@@ -1063,6 +1151,14 @@ impl InstData {
         }
     }
 
+    /// Return the const logic value constructed by this instruction.
+    pub fn get_const_logic(&self) -> Option<&LogicValue> {
+        match self {
+            InstData::ConstLogic { imm, .. } => Some(imm),
+            _ => None,
+        }
+    }
+
     /// Return the external unit being called or instantiated by this
     /// instruction.
     pub fn get_ext_unit(&self) -> Option<ExtUnit> {
@@ -1123,6 +1219,46 @@ impl std::fmt::Display for RegMode {
     }
 }
 
+/// The delay modes for signal drives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DelayMode {
+    /// Pulses shorter than the delay are swallowed, as for a gate.
+    #[default]
+    Inertial,
+    /// Pulses of any width are propagated, as for a wire.
+    Transport,
+}
+
+impl std::fmt::Display for DelayMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DelayMode::Inertial => write!(f, "inertial"),
+            DelayMode::Transport => write!(f, "transport"),
+        }
+    }
+}
+
+/// The resolution functions for signals with multiple drivers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Resolution {
+    /// A wire that is zero if any driver drives a zero.
+    WiredAnd,
+    /// A wire that is one if any driver drives a one.
+    WiredOr,
+    /// A bus whose drivers release it by driving `Z`.
+    Tristate,
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Resolution::WiredAnd => write!(f, "wand"),
+            Resolution::WiredOr => write!(f, "wor"),
+            Resolution::Tristate => write!(f, "tri"),
+        }
+    }
+}
+
 /// The trigger for register data acquisition.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct RegTrigger {
@@ -1145,6 +1281,7 @@ pub struct RegTrigger {
 pub enum Opcode {
     ConstInt,
     ConstTime,
+    ConstLogic,
     Alias,
     ArrayUniform,
     Array,
@@ -1219,6 +1356,7 @@ impl std::fmt::Display for Opcode {
             match *self {
                 Opcode::ConstInt => "const",
                 Opcode::ConstTime => "const",
+                Opcode::ConstLogic => "const",
                 Opcode::Alias => "alias",
                 Opcode::ArrayUniform => "array",
                 Opcode::Array => "array",
@@ -1320,6 +1458,7 @@ impl Opcode {
         match self {
             Opcode::ConstInt => true,
             Opcode::ConstTime => true,
+            Opcode::ConstLogic => true,
             _ => false,
         }
     }
@@ -1436,6 +1575,12 @@ impl std::fmt::Display for InstDumper<'_> {
                 comma = true;
             }
         } else {
+            if let Some(DelayMode::Transport) = data.delay_mode() {
+                write!(f, " transport")?;
+            }
+            if let Some(resolution) = data.resolution() {
+                write!(f, " {}", resolution)?;
+            }
             let mut comma = false;
             for arg in data.args() {
                 if comma {
@@ -1461,6 +1606,7 @@ impl std::fmt::Display for InstDumper<'_> {
             match data {
                 InstData::ConstInt { imm, .. } => write!(f, " {}", imm.value)?,
                 InstData::ConstTime { imm, .. } => write!(f, " {}", imm)?,
+                InstData::ConstLogic { imm, .. } => write!(f, " {}", imm)?,
                 InstData::Array { imms, .. } => write!(f, ", {}", imms[0])?,
                 _ => (),
             }
@@ -4,6 +4,6 @@
 
 #[allow(deprecated)]
 pub use crate::ir::{
-    Arg, Block, DeclData, DeclId, Inst, Module, Opcode, RegMode, RegTrigger, Signature, Unit,
-    UnitBuilder, UnitData, UnitId, UnitKind, UnitName, Value,
+    Arg, Block, DeclData, DeclId, DelayMode, Inst, Module, Opcode, RegMode, RegTrigger, Resolution,
+    Signature, Unit, UnitBuilder, UnitData, UnitId, UnitKind, UnitName, Value,
 };
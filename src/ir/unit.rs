@@ -446,6 +446,7 @@ impl<'a> Unit<'a> {
         match self[inst].opcode() {
             Opcode::ConstInt => self.get_const_int(value).cloned().map(Into::into),
             Opcode::ConstTime => self.get_const_time(value).cloned().map(Into::into),
+            Opcode::ConstLogic => self.get_const_logic(value).cloned().map(Into::into),
             Opcode::Array | Opcode::ArrayUniform => self.get_const_array(value).map(Into::into),
             Opcode::Struct => self.get_const_struct(value).map(Into::into),
             _ => None,
@@ -472,6 +473,16 @@ impl<'a> Unit<'a> {
         self.data.dfg[inst].get_const_int()
     }
 
+    /// Resolve a constant logic value.
+    ///
+    /// Returns `None` if the value is not constant. Note that this *does not*
+    /// perform constant folding. Rather, the value must resolve to an
+    /// instruction which produces a constant value.
+    pub fn get_const_logic(self, value: Value) -> Option<&'a crate::LogicValue> {
+        let inst = self.get_value_inst(value)?;
+        self.data.dfg[inst].get_const_logic()
+    }
+
     /// Resolve a constant array value.
     ///
     /// Returns `None` if the value is not constant. Note that this *does not*
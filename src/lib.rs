@@ -18,6 +18,7 @@ pub mod ir;
 pub mod mlir;
 pub mod opt;
 pub mod pass;
+pub mod sim;
 pub mod table;
 pub mod ty;
 pub mod value;
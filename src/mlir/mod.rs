@@ -7,13 +7,16 @@ use crate::ir::Module;
 mod writer;
 
 /// Emit CIRCT IR for a module.
-pub fn write_module(sink: impl std::io::Write, module: &Module) {
-    writer::Writer::new(sink).write_module(module).unwrap();
+///
+/// Fails if the module uses features that have no equivalent in CIRCT, such as
+/// signals with a resolution function.
+pub fn write_module(sink: impl std::io::Write, module: &Module) -> std::io::Result<()> {
+    writer::Writer::new(sink).write_module(module)
 }
 
 /// Emit CIRCT IR for a module as string.
-pub fn write_module_string(module: &Module) -> String {
+pub fn write_module_string(module: &Module) -> std::io::Result<String> {
     let mut asm = vec![];
-    write_module(&mut asm, &module);
-    String::from_utf8(asm).expect("writer should emit proper utf8")
+    write_module(&mut asm, module)?;
+    Ok(String::from_utf8(asm).expect("writer should emit proper utf8"))
 }
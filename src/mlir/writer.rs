@@ -10,7 +10,7 @@ use itertools::Itertools;
 use num::{cast::FromPrimitive, BigInt, BigRational, One};
 use std::{
     collections::{HashMap, HashSet},
-    io::{Result, Write},
+    io::{Error, ErrorKind, Result, Write},
     rc::Rc,
 };
 
@@ -52,6 +52,7 @@ impl std::fmt::Display for MLIRType<'_> {
             TypeKind::TimeType => write!(f, "!llhd.time"),
             TypeKind::IntType(l) => write!(f, "i{}", l),
             TypeKind::EnumType(l) => write!(f, "n{}", l),
+            TypeKind::LogicType(l) => write!(f, "l{}", l),
             TypeKind::PointerType(ref ty) => write!(f, "!llhd.ptr<{}>", MLIRType(ty)),
             TypeKind::SignalType(ref ty) => write!(f, "!llhd.sig<{}>", MLIRType(ty)),
             TypeKind::ArrayType(l, ref ty) => write!(f, "!hw.array<{}x{}>", l, MLIRType(ty)),
@@ -650,6 +651,7 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                     data.get_const_time().unwrap().epsilon
                 )?
             }
+            Opcode::ConstLogic => panic!("No corresponding op for logic constants in CIRCT!"),
             Opcode::ArrayUniform => {
                 self.write_value_name(unit.inst_result(inst))?;
                 write!(self.writer.sink, " = ")?;
@@ -859,6 +861,12 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                 }
             }
             Opcode::Sig => {
+                if let Some(resolution) = data.resolution() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("signals with `{}` resolution are not supported", resolution),
+                    ));
+                }
                 self.write_value_name(unit.inst_result(inst))?;
                 write!(self.writer.sink, " = ")?;
                 let sig_name = if let Some(name) = self.value_names.get(&unit.inst_result(inst)) {
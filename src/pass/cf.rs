@@ -4,7 +4,11 @@
 
 use crate::ir::prelude::*;
 use crate::opt::prelude::*;
-use crate::{ir::InstData, ty::*, value::IntValue};
+use crate::{
+    ir::InstData,
+    ty::*,
+    value::{IntValue, LogicValue},
+};
 use std::cmp::min;
 
 /// Constant Folding
@@ -84,6 +88,8 @@ pub fn run_on_value(unit: &mut UnitBuilder, value: Value) -> bool {
 fn fold_unary(unit: &mut UnitBuilder, op: Opcode, ty: Type, arg: Value) -> Option<Value> {
     if ty.is_int() {
         fold_unary_int(unit, op, arg)
+    } else if ty.is_logic() {
+        fold_unary_logic(unit, op, arg)
     } else {
         None
     }
@@ -96,10 +102,19 @@ fn fold_unary_int(unit: &mut UnitBuilder, op: Opcode, arg: Value) -> Option<Valu
     Some(unit.ins().const_int(result))
 }
 
+/// Fold a unary instruction on logic values.
+fn fold_unary_logic(unit: &mut UnitBuilder, op: Opcode, arg: Value) -> Option<Value> {
+    let imm = unit.get_const_logic(arg)?;
+    let result = LogicValue::try_unary_op(op, imm)?;
+    Some(unit.ins().const_logic(result))
+}
+
 /// Fold a binary instruction.
 fn fold_binary(unit: &mut UnitBuilder, op: Opcode, ty: Type, args: [Value; 2]) -> Option<Value> {
     if ty.is_int() {
         fold_binary_int(unit, op, ty.unwrap_int(), args)
+    } else if ty.is_logic() {
+        fold_binary_logic(unit, op, args)
     } else {
         None
     }
@@ -163,6 +178,14 @@ fn fold_binary_int(
     Some(unit.ins().const_int(result))
 }
 
+/// Fold a binary instruction on logic values.
+fn fold_binary_logic(unit: &mut UnitBuilder, op: Opcode, args: [Value; 2]) -> Option<Value> {
+    let imm0 = unit.get_const_logic(args[0])?;
+    let imm1 = unit.get_const_logic(args[1])?;
+    let result = LogicValue::try_binary_op(op, imm0, imm1)?;
+    Some(unit.ins().const_logic(result))
+}
+
 /// Fold a shift instruction.
 fn fold_shift(unit: &mut UnitBuilder, inst: Inst, ty: &Type) -> Option<Value> {
     let base = unit[inst].args()[0];
@@ -246,8 +269,8 @@ fn fold_ins_slice(unit: &mut UnitBuilder, inst: Inst) -> Option<Value> {
     // Handle the trivial cases where we override the entire value, or nothing
     // at all.
     match unit.value_type(target).as_ref() {
-        IntType(_) | ArrayType(..) if len == 0 => return Some(target),
-        IntType(w) | ArrayType(w, _) if len == *w => return Some(value),
+        IntType(_) | LogicType(_) | ArrayType(..) if len == 0 => return Some(target),
+        IntType(w) | LogicType(w) | ArrayType(w, _) if len == *w => return Some(value),
         _ => (),
     }
 
@@ -258,6 +281,14 @@ fn fold_ins_slice(unit: &mut UnitBuilder, inst: Inst) -> Option<Value> {
         return Some(unit.ins().const_int(r));
     }
 
+    // Handle the case where both operands are constant logic values.
+    if let (Some(target), Some(value)) = (unit.get_const_logic(target), unit.get_const_logic(value))
+    {
+        let mut r = target.clone();
+        r.insert_slice(data.imms()[0], len, value);
+        return Some(unit.ins().const_logic(r));
+    }
+
     None
 }
 
@@ -271,8 +302,10 @@ fn fold_ext_slice(unit: &mut UnitBuilder, inst: Inst) -> Option<Value> {
     // Handle the trivial case where we extract the entire value, or nothing
     // at all.
     match unit.value_type(target).as_ref() {
-        IntType(..) | ArrayType(..) if len == 0 => return Some(unit.ins().const_zero(ty)),
-        IntType(w) | ArrayType(w, _) if len == *w => return Some(target),
+        IntType(..) | LogicType(..) | ArrayType(..) if len == 0 => {
+            return Some(unit.ins().const_zero(ty))
+        }
+        IntType(w) | LogicType(w) | ArrayType(w, _) if len == *w => return Some(target),
         _ => (),
     }
 
@@ -282,6 +315,12 @@ fn fold_ext_slice(unit: &mut UnitBuilder, inst: Inst) -> Option<Value> {
         return Some(unit.ins().const_int(r));
     }
 
+    // Handle the case where the target is a constant logic value.
+    if let Some(imm) = unit.get_const_logic(target) {
+        let r = imm.extract_slice(data.imms()[0], len);
+        return Some(unit.ins().const_logic(r));
+    }
+
     None
 }
 
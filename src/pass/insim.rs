@@ -22,7 +22,8 @@ impl Pass for InstSimplification {
                         let signal = unit[inst].args()[0];
                         let value = unit[inst].args()[1];
                         let delay = unit[inst].args()[2];
-                        unit.ins().drv(signal, value, delay);
+                        let mode = unit[inst].delay_mode().unwrap();
+                        unit.ins().drv_mode(signal, value, delay, mode);
                     }
                     unit.delete_inst(inst);
                 }
@@ -398,7 +398,8 @@ fn push_drive(
         let signal = args[0];
         let value = args[1];
         let delay = args[2];
-        unit.ins().drv_cond(signal, value, delay, cond);
+        let mode = unit[drive].delay_mode().unwrap();
+        unit.ins().drv_cond_mode(signal, value, delay, cond, mode);
     }
 
     // Remove the old drive instruction.
@@ -410,18 +411,19 @@ fn push_drive(
 fn coalesce_drives(_ctx: &PassContext, block: Block, unit: &mut UnitBuilder) -> bool {
     let mut modified = false;
 
-    // Group the drives by delay.
-    let mut delay_groups = HashMap::<Value, Vec<Inst>>::new();
+    // Group the drives by delay and delay mode.
+    let mut delay_groups = HashMap::<(Value, DelayMode), Vec<Inst>>::new();
     for inst in unit.insts(block) {
         if let Opcode::Drv | Opcode::DrvCond = unit[inst].opcode() {
             let delay = unit[inst].args()[2];
-            delay_groups.entry(delay).or_default().push(inst);
+            let mode = unit[inst].delay_mode().unwrap();
+            delay_groups.entry((delay, mode)).or_default().push(inst);
         }
     }
 
     // Coalesce each delay group individually. Split the instructions into runs
     // of drives to the exact same signal.
-    for (delay, drives) in delay_groups {
+    for ((delay, mode), drives) in delay_groups {
         let runs: Vec<_> = drives
             .into_iter()
             .group_by(|&inst| unit[inst].args()[0])
@@ -462,7 +464,7 @@ fn coalesce_drives(_ctx: &PassContext, block: Block, unit: &mut UnitBuilder) ->
             }
 
             // Build the final drive.
-            unit.ins().drv_cond(target, value, delay, cond);
+            unit.ins().drv_cond_mode(target, value, delay, cond, mode);
             modified = true;
         }
     }
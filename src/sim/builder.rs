@@ -0,0 +1,456 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Simulation structure creation
+//!
+//! The simulation builder creates the structure necessary for simulating a
+//! design.
+
+use crate::ir::Resolution;
+use crate::sim::{
+    state::{Instance, InstanceKind, InstanceState, Scope, Signal, SignalRef, State, ValueSlot},
+    value::{ArrayValue, IntValue, StructValue, TimeValue, Value},
+    SimulationError,
+};
+use num::bigint::ToBigInt;
+use std::{collections::HashMap, sync::Mutex};
+
+struct Builder<'ll> {
+    module: &'ll crate::ir::Module,
+    signals: Vec<Signal>,
+    nets: Vec<SignalRef>,
+    probes: HashMap<SignalRef, Vec<String>>,
+    insts: Vec<Instance<'ll>>,
+    scope_stack: Vec<Scope>,
+    uninitialized: Vec<SignalRef>,
+}
+
+impl<'ll> Builder<'ll> {
+    /// Create a new builder for the given module.
+    fn new(module: &crate::ir::Module) -> Builder<'_> {
+        Builder {
+            module: module,
+            signals: Vec::new(),
+            nets: Vec::new(),
+            probes: HashMap::new(),
+            insts: Vec::new(),
+            scope_stack: Vec::new(),
+            uninitialized: Vec::new(),
+        }
+    }
+
+    /// Build the root unit for a simulation.
+    fn build_root(&mut self, unit: crate::ir::Unit<'ll>) -> Result<(), SimulationError> {
+        let sig = unit.sig();
+
+        // Allocate the input and output signals for the top-level module. These
+        // start out as zero and may later be overridden by the user.
+        let mut alloc = |arg| {
+            let ty = sig.arg_type(arg);
+            let init = Value::zero(if ty.is_signal() {
+                ty.unwrap_signal()
+            } else {
+                &ty
+            });
+            self.alloc_signal(ty, init, None)
+        };
+        let inputs: Vec<_> = sig.inputs().map(&mut alloc).collect();
+        let outputs: Vec<_> = sig.outputs().map(&mut alloc).collect();
+        self.uninitialized.extend(inputs.iter().chain(&outputs));
+
+        // Instantiate the top-level module.
+        self.push_scope(unit.name().to_string());
+        self.instantiate(unit, inputs, outputs)
+    }
+
+    /// Allocate a new signal in the simulation and return a reference to it.
+    fn alloc_signal(
+        &mut self,
+        ty: crate::Type,
+        init: Value,
+        resolution: Option<Resolution>,
+    ) -> SignalRef {
+        let id = SignalRef::new(self.signals.len());
+        self.signals
+            .push(Signal::new_resolved(ty, init, resolution));
+        self.nets.push(id);
+        id
+    }
+
+    /// Find the signal that represents the net a signal is connected to.
+    fn resolve_net(&self, mut signal: SignalRef) -> SignalRef {
+        while self.nets[signal.as_usize()] != signal {
+            signal = self.nets[signal.as_usize()];
+        }
+        signal
+    }
+
+    /// Connect two signals such that they form a single net.
+    ///
+    /// The signal allocated first represents the net and provides its initial
+    /// value. All references to the other signal are redirected to the net when
+    /// the builder finishes.
+    fn connect_signals(&mut self, a: SignalRef, b: SignalRef) {
+        let a = self.resolve_net(a);
+        let b = self.resolve_net(b);
+        let (root, other) = if a.as_usize() < b.as_usize() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.nets[other.as_usize()] = root;
+
+        // The net is resolved if any of its signals is.
+        let resolution = self.signals[other.as_usize()].resolution();
+        if self.signals[root.as_usize()].resolution().is_none() {
+            self.signals[root.as_usize()].set_resolution(resolution);
+        }
+    }
+
+    /// Allocate a new signal probe in the simulation. This essentially assigns
+    /// a name to a signal which is also known to the user.
+    pub fn alloc_signal_probe(&mut self, signal: SignalRef, name: String) {
+        self.probes
+            .entry(signal)
+            .or_insert(Vec::new())
+            .push(name.clone());
+        self.scope_stack.last_mut().unwrap().add_probe(signal, name);
+    }
+
+    /// Instantiate a process or entity for simulation. This recursively builds
+    /// the simulation structure for all subunits as necessary.
+    pub fn instantiate(
+        &mut self,
+        unit: crate::ir::Unit<'ll>,
+        inputs: Vec<SignalRef>,
+        outputs: Vec<SignalRef>,
+    ) -> Result<(), SimulationError> {
+        debug!("Instantiating {}", unit.name());
+
+        // Create signal probes for the input and output arguments of the unit.
+        let input_iter = unit.sig().inputs().zip(inputs.iter());
+        let output_iter = unit.sig().outputs().zip(outputs.iter());
+        let args_iter = input_iter.chain(output_iter);
+        let mut values: HashMap<crate::ir::Value, ValueSlot> = args_iter
+            .map(|(arg, &sig)| {
+                let v = unit.arg_value(arg);
+                if let Some(name) = unit.get_name(v) {
+                    self.alloc_signal_probe(sig, name.to_string());
+                }
+                (v, ValueSlot::Signal(sig))
+            })
+            .collect();
+
+        // Make a list of signals that this instance is sensitive to.
+        let mut signals = inputs;
+        signals.extend(outputs);
+
+        // Gather the process-/entity-specific information.
+        let kind = if unit.is_process() {
+            // Allocate signals.
+            for block in unit.blocks() {
+                for inst in unit.insts(block) {
+                    if unit[inst].opcode() == crate::ir::Opcode::Sig {
+                        let value = unit.inst_result(inst);
+                        let init = self.const_value(unit, unit[inst].args()[0])?;
+                        let resolution = unit[inst].resolution();
+                        let sig = self.alloc_signal(unit.value_type(value), init, resolution);
+                        signals.push(sig); // entity is re-evaluated when this signal changes
+                        if let Some(name) = unit.get_name(value) {
+                            self.alloc_signal_probe(sig, name.to_string());
+                        }
+                        values.insert(value, ValueSlot::Signal(sig));
+                    }
+                    // Hotfix for const insts in moore output not dominating their uses
+                    else if unit[inst].opcode() == crate::ir::Opcode::ConstInt
+                        || unit[inst].opcode() == crate::ir::Opcode::ConstTime
+                    {
+                        let value = unit.inst_result(inst);
+                        values.insert(value, ValueSlot::Const(self.const_value(unit, value)?));
+                    }
+                }
+            }
+
+            InstanceKind::Process {
+                prok: unit,
+                next_block: unit.first_block(),
+                prev_block: None,
+            }
+        } else if unit.is_entity() {
+            // Allocate signals and instantiate subunits.
+            let mut cons = vec![];
+            let mut regs = vec![];
+            for inst in unit.all_insts() {
+                if unit[inst].opcode() == crate::ir::Opcode::Sig {
+                    let value = unit.inst_result(inst);
+                    let init = self.const_value(unit, unit[inst].args()[0])?;
+                    let resolution = unit[inst].resolution();
+                    let sig = self.alloc_signal(unit.value_type(value), init, resolution);
+                    signals.push(sig); // entity is re-evaluated when this signal changes
+                    if let Some(name) = unit.get_name(value) {
+                        self.alloc_signal_probe(sig, name.to_string());
+                    }
+                    values.insert(value, ValueSlot::Signal(sig));
+                } else if unit[inst].opcode() == crate::ir::Opcode::Inst {
+                    let ext_unit = unit[inst].get_ext_unit().unwrap();
+                    let name = &unit[ext_unit].name;
+                    let mod_subunit = match self.module.lookup_ext_unit(ext_unit, unit.id()) {
+                        Some(crate::ir::LinkedUnit::Def(s)) => s,
+                        _ => panic!("external unit {} not linked", name),
+                    };
+                    self.push_scope(name.to_string());
+                    let resolve_signal = |v| match values[v] {
+                        ValueSlot::Signal(sig) => sig,
+                        _ => panic!("value does not resolve to a signal"),
+                    };
+                    let inputs = unit[inst]
+                        .input_args()
+                        .iter()
+                        .map(&resolve_signal)
+                        .collect();
+                    let outputs = unit[inst]
+                        .output_args()
+                        .iter()
+                        .map(&resolve_signal)
+                        .collect();
+                    self.instantiate(self.module.unit(mod_subunit), inputs, outputs)?;
+                    self.pop_scope();
+                } else if unit[inst].opcode() == crate::ir::Opcode::Con {
+                    cons.push(inst);
+                } else if unit[inst].opcode() == crate::ir::Opcode::Reg {
+                    regs.push(inst);
+                }
+            }
+
+            // Registers have no initial value in hardware.
+            for inst in regs {
+                if let Some(&ValueSlot::Signal(sig)) = values.get(&unit[inst].args()[0]) {
+                    self.uninitialized.push(sig);
+                }
+            }
+
+            // Merge connected signals into one net.
+            for inst in cons {
+                let args = unit[inst].args();
+                match (&values[&args[0]], &values[&args[1]]) {
+                    (&ValueSlot::Signal(a), &ValueSlot::Signal(b)) => self.connect_signals(a, b),
+                    _ => return Err(self.unsupported(unit, inst)),
+                }
+            }
+
+            InstanceKind::Entity { entity: unit }
+        } else {
+            unreachable!()
+        };
+
+        // Create a mapping from signals to the values which correspond to them.
+        // This resolves signals to arguments or `sig` instructions.
+        let mut signal_values: HashMap<SignalRef, Vec<crate::ir::Value>> = HashMap::new();
+        for (&v, s) in &values {
+            if let &ValueSlot::Signal(s) = s {
+                signal_values.entry(s).or_default().push(v);
+            }
+        }
+
+        // Create the unit instance.
+        self.insts.push(Instance {
+            path: self.path(),
+            values,
+            kind,
+            state: InstanceState::Ready,
+            signals,
+            signal_values,
+            registers: Default::default(),
+            frame: Default::default(),
+        });
+        Ok(())
+    }
+
+    /// Consume the builder and assemble the simulation state.
+    pub fn finish(mut self) -> State<'ll> {
+        self.merge_nets();
+        State {
+            module: self.module,
+            signals: self.signals,
+            probes: self.probes,
+            scope: self.scope_stack.into_iter().next().unwrap(),
+            insts: self.insts.into_iter().map(Mutex::new).collect(),
+            time: TimeValue::new(num::zero(), 0, 0),
+            events: Default::default(),
+            pending: Default::default(),
+            timed: Default::default(),
+            coverage: None,
+            profile: None,
+            deltas: crate::sim::engine::DeltaCycles::new(),
+            externs: crate::sim::externs::Externs::new(),
+            uninitialized: self.uninitialized,
+            xprop: None,
+        }
+    }
+
+    /// Redirect all references to connected signals to the signal representing
+    /// their net.
+    fn merge_nets(&mut self) {
+        let nets: Vec<_> = (0..self.nets.len())
+            .map(|i| self.resolve_net(SignalRef::new(i)))
+            .collect();
+        let net = |sig: SignalRef| nets[sig.as_usize()];
+        if nets.iter().enumerate().all(|(i, n)| n.as_usize() == i) {
+            return;
+        }
+
+        for inst in &mut self.insts {
+            for slot in inst.values.values_mut() {
+                if let ValueSlot::Signal(sig) = slot {
+                    *sig = net(*sig);
+                }
+            }
+            let mut signals = vec![];
+            for sig in inst.signals.drain(..).map(net) {
+                if !signals.contains(&sig) {
+                    signals.push(sig);
+                }
+            }
+            inst.signals = signals;
+            let mut signal_values: HashMap<_, Vec<_>> = HashMap::new();
+            for (sig, values) in inst.signal_values.drain() {
+                signal_values.entry(net(sig)).or_default().extend(values);
+            }
+            inst.signal_values = signal_values;
+        }
+        for sig in &mut self.uninitialized {
+            *sig = net(*sig);
+        }
+
+        merge_probes(&mut self.probes, &net);
+        fn merge_scope(scope: &mut Scope, net: &dyn Fn(SignalRef) -> SignalRef) {
+            merge_probes(&mut scope.probes, net);
+            for subscope in &mut scope.subscopes {
+                merge_scope(subscope, net);
+            }
+        }
+        merge_scope(&mut self.scope_stack[0], &net);
+    }
+
+    /// Determine the hierarchical name of the current scope.
+    fn path(&self) -> String {
+        self.scope_stack
+            .iter()
+            .map(|scope| scope.name.as_str())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Report an instruction as not supported by the simulator.
+    fn unsupported(&self, unit: crate::ir::Unit, inst: crate::ir::Inst) -> SimulationError {
+        SimulationError::Unsupported {
+            instance: self.path(),
+            unit: unit.name().to_string(),
+            inst: inst.dump(&unit).to_string(),
+        }
+    }
+
+    /// Push a new scope onto the stack.
+    fn push_scope(&mut self, name: impl Into<String>) {
+        self.scope_stack.push(Scope::new(name));
+    }
+
+    /// Pop a scope off the stack.
+    fn pop_scope(&mut self) {
+        let scope = self.scope_stack.pop().unwrap();
+        self.scope_stack.last_mut().unwrap().add_subscope(scope);
+    }
+
+    /// Map an LLHD value to a constant value.
+    ///
+    /// This is useful for initializing the value of variables and signals.
+    fn const_value(
+        &self,
+        unit: crate::ir::Unit,
+        value: crate::ir::Value,
+    ) -> Result<Value, SimulationError> {
+        use crate::ir::Opcode;
+        let ty = unit.value_type(value);
+        let inst = unit.value_inst(value);
+        let data = &unit[inst];
+        Ok(match data.opcode() {
+            Opcode::ConstInt => IntValue::from_signed(
+                ty.unwrap_int(),
+                data.get_const_int()
+                    .unwrap()
+                    .value
+                    .to_bigint()
+                    .unwrap()
+                    .clone(),
+            )
+            .into(),
+            Opcode::ConstTime => data.get_const_time().unwrap().clone().into(),
+            Opcode::ConstLogic => data.get_const_logic().unwrap().clone().into(),
+            Opcode::ArrayUniform => {
+                ArrayValue::new_uniform(data.imms()[0], self.const_value(unit, data.args()[0])?)
+                    .into()
+            }
+            Opcode::Array => ArrayValue::new(
+                data.args()
+                    .iter()
+                    .map(|&arg| self.const_value(unit, arg))
+                    .collect::<Result<_, _>>()?,
+            )
+            .into(),
+            Opcode::Struct => StructValue::new(
+                data.args()
+                    .iter()
+                    .map(|&arg| self.const_value(unit, arg))
+                    .collect::<Result<_, _>>()?,
+            )
+            .into(),
+            _ => return Err(self.unsupported(unit, inst)),
+        })
+    }
+}
+
+/// Redirect the probes of connected signals to the signal representing their
+/// net, keeping the names in signal allocation order.
+fn merge_probes(
+    probes: &mut HashMap<SignalRef, Vec<String>>,
+    net: &dyn Fn(SignalRef) -> SignalRef,
+) {
+    let mut old: Vec<_> = probes.drain().collect();
+    old.sort_by_key(|&(sig, _)| sig);
+    for (sig, names) in old {
+        probes.entry(net(sig)).or_default().extend(names);
+    }
+}
+
+/// Build the simulation for a module.
+pub fn build<'ll>(
+    module: &'ll crate::ir::Module,
+    top: Option<&str>,
+) -> Result<State<'ll>, SimulationError> {
+    let mut builder = Builder::new(module);
+
+    // Find the requested unit, or otherwise the last process or entity in the
+    // module, which we will use as the simulation's root unit.
+    let mut candidates = module
+        .units()
+        .filter(|&unit| unit.is_process() || unit.is_entity());
+    let root = match top {
+        Some(top) => {
+            let name = |unit: &crate::ir::Unit| unit.name().to_string();
+            match candidates.find(|unit| name(unit) == top || name(unit) == format!("@{}", top)) {
+                Some(r) => r,
+                None => return Err(SimulationError::UnknownUnit(top.to_string())),
+            }
+        }
+        None => match candidates.last() {
+            Some(r) => r,
+            None => return Err(SimulationError::NoRoot),
+        },
+    };
+    info!("Found simulation root: {}", root.name());
+
+    // Build the simulation for this root module.
+    builder.build_root(root)?;
+
+    // Build the simulation state.
+    Ok(builder.finish())
+}
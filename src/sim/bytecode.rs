@@ -0,0 +1,797 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Bytecode execution backend
+//!
+//! The reference interpreter in `engine` executes the instructions of a unit
+//! directly from the IR, and looks up the operands of every instruction in a
+//! hash map. This backend instead compiles each unit once into a flat list of
+//! operations whose operands are resolved to register indices up front, and
+//! executes them in a tight dispatch loop on a register file per instance.
+//!
+//! The register of a value is its index in the unit, such that the pointers
+//! and value tables used by the interpreter remain valid. Operations without a
+//! dedicated fast path are delegated to the interpreter, which keeps the two
+//! backends in agreement.
+
+use crate::ir::{Block, DelayMode, Inst, LinkedUnit, Module, Opcode, Unit, UnitId};
+use crate::sim::{
+    coverage::BlockHits,
+    engine::{store_pointer, Action, ExecError, InstContext},
+    externs::Externs,
+    profile::ProfileState,
+    state::{
+        Event, Instance, InstanceKind, InstanceState, Registers, Signal, SignalRef, State,
+        ValuePointer, ValueSlice, ValueSlot, ValueTarget,
+    },
+    value::{IntValue, TimeValue, Value},
+    xprop::XpropState,
+    SimulationError,
+};
+use crate::table::TableKey;
+use num::bigint::ToBigInt;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Instant,
+};
+
+/// The index of a register.
+type Reg = usize;
+
+/// The compiled programs of all units in a module.
+pub(crate) struct Programs<'ll> {
+    module: &'ll Module,
+    units: HashMap<UnitId, Program<'ll>>,
+}
+
+/// A unit compiled to bytecode.
+struct Program<'ll> {
+    unit: Unit<'ll>,
+    /// The operations, one per instruction, in layout order.
+    ops: Vec<Op>,
+    /// The number of registers required to hold all values of the unit.
+    num_regs: usize,
+    /// The index of the first operation of each block.
+    block_starts: Vec<usize>,
+    /// The operations that use the result of each operation.
+    uses: Vec<Vec<usize>>,
+    /// The operations that react to a change of the signal in each register.
+    triggers: Vec<Vec<usize>>,
+}
+
+/// The parts of the simulation state an instance executes against.
+#[derive(Clone, Copy)]
+struct Env<'a> {
+    signals: &'a [Signal],
+    time: &'a TimeValue,
+    /// The counters to record entered blocks into, if coverage is collected.
+    block_hits: Option<&'a BlockHits>,
+    /// The profile to record into, if profiling is enabled.
+    profile: Option<&'a ProfileState>,
+    externs: &'a Externs<'a>,
+    /// The X propagation state, if enabled.
+    xprop: Option<&'a XpropState>,
+}
+
+/// A single operation.
+struct Op {
+    /// The instruction the operation was compiled from.
+    inst: Inst,
+    /// The register that receives the result.
+    dst: Option<Reg>,
+    kind: OpKind,
+}
+
+enum OpKind {
+    /// An instruction that is handled by the builder.
+    Nop,
+    Const(Value),
+    Alias(Reg),
+    Unary(Opcode, Reg),
+    Binary(Opcode, Reg, Reg),
+    Compare(Opcode, Reg, Reg),
+    Mux(Reg, Reg),
+    Phi(Vec<(Block, Reg)>),
+    Br(Block),
+    BrCond(Reg, Block, Block),
+    Wait(Block, Option<Reg>, Vec<Reg>),
+    Halt,
+    Prb(Reg),
+    Drv {
+        signal: Reg,
+        value: Reg,
+        delay: Reg,
+        cond: Option<Reg>,
+        width: usize,
+        mode: DelayMode,
+    },
+    /// A call, with the callee and pairs of callee and caller registers that
+    /// bind the arguments.
+    Call(UnitId, Vec<(Reg, Reg)>),
+    Ret(Option<Reg>),
+    Reg,
+    /// An instruction executed by the interpreter.
+    Fallback,
+}
+
+impl<'ll> Programs<'ll> {
+    /// Compile all processes, entities, and functions in a module.
+    pub fn compile(module: &'ll Module) -> Self {
+        let units = module
+            .units()
+            .map(|unit| (unit.id(), Program::compile(module, unit)))
+            .collect();
+        Programs { module, units }
+    }
+
+    /// Continue execution of one single process or entity instance, until it
+    /// is suspended by an instruction.
+    pub fn step_instance(
+        &self,
+        instance: &mut Instance,
+        state: &State,
+        changed_signals: &HashSet<SignalRef>,
+        first: bool,
+    ) -> Result<Vec<Event>, SimulationError> {
+        let env = Env {
+            signals: &state.signals,
+            time: &state.time,
+            block_hits: state.coverage.as_ref().map(|c| &c.blocks),
+            profile: state.profile.as_ref(),
+            externs: &state.externs,
+            xprop: state.xprop.as_ref(),
+        };
+        let unit = match instance.kind {
+            InstanceKind::Process { prok, .. } => prok,
+            InstanceKind::Entity { entity } => entity,
+        };
+        let prog = &self.units[&unit.id()];
+        if instance.frame.is_empty() {
+            instance.frame = Registers::from_map(prog.num_regs, &instance.values);
+        }
+        let result = if unit.is_process() {
+            self.step_process(prog, instance, env)
+        } else {
+            self.step_entity(prog, instance, env, changed_signals, first)
+        };
+        result.map_err(|e| e.in_instance(instance))
+    }
+
+    /// Continue execution of one single process until it is suspended.
+    fn step_process(
+        &self,
+        prog: &Program<'ll>,
+        instance: &mut Instance,
+        env: Env,
+    ) -> Result<Vec<Event>, ExecError> {
+        let (mut block, mut pred) = match instance.kind {
+            InstanceKind::Process {
+                next_block,
+                prev_block,
+                ..
+            } => (
+                next_block.expect("process starved of instructions"),
+                prev_block,
+            ),
+            _ => unreachable!(),
+        };
+        let started = env.profile.map(|_| Instant::now());
+        let mut executed = 0;
+        let regs = &mut instance.frame;
+        let mut events = Vec::new();
+        let mut phis = vec![];
+        let mut pc = prog.block_starts[block.index()];
+        let mut entered = vec![];
+        if env.block_hits.is_some() {
+            entered.push(block);
+        }
+        loop {
+            let op = &prog.ops[pc];
+            pc += 1;
+            executed += 1;
+            let is_phi = matches!(op.kind, OpKind::Phi(_));
+            if !is_phi {
+                for (r, slot) in phis.drain(..) {
+                    regs.0[r] = Some(slot);
+                }
+            }
+            match self.exec_op(prog, op, regs, pred, env)? {
+                Action::None => (),
+                Action::Value(slot) if is_phi => phis.push((op.dst.unwrap(), slot)),
+                Action::Value(slot) => regs.0[op.dst.unwrap()] = Some(slot),
+                Action::Store(ptr, value) => store_pointer(regs, &ptr, &value),
+                Action::Event(e) => events.push(e),
+                Action::Call(slot, evs) => {
+                    if let Some(slot) = slot {
+                        regs.0[op.dst.unwrap()] = Some(slot);
+                    }
+                    events.extend(evs);
+                }
+                Action::Jump(bb) => {
+                    pred = Some(block);
+                    block = bb;
+                    pc = prog.block_starts[bb.index()];
+                    if env.block_hits.is_some() {
+                        entered.push(bb);
+                    }
+                }
+                Action::Suspend(bb, st) => {
+                    instance.state = st;
+                    match instance.kind {
+                        InstanceKind::Process {
+                            ref mut next_block,
+                            ref mut prev_block,
+                            ..
+                        } => {
+                            *next_block = bb;
+                            *prev_block = Some(block);
+                        }
+                        _ => unreachable!(),
+                    }
+                    if let Some(hits) = env.block_hits {
+                        hits.record_process(&instance.path, &entered);
+                    }
+                    if let (Some(profile), Some(started)) = (env.profile, started) {
+                        profile.record_instance(&instance.path, executed, started.elapsed());
+                    }
+                    return Ok(events);
+                }
+            }
+        }
+    }
+
+    /// Continue execution of one single entity.
+    fn step_entity(
+        &self,
+        prog: &Program<'ll>,
+        instance: &mut Instance,
+        env: Env,
+        changed_signals: &HashSet<SignalRef>,
+        first: bool,
+    ) -> Result<Vec<Event>, ExecError> {
+        let started = env.profile.map(|_| Instant::now());
+        let mut executed = 0;
+        let mut events = Vec::new();
+
+        // Collect the operations that react to the changed signals.
+        let mut dirty = VecDeque::new();
+        let mut dirty_set = vec![false; prog.ops.len()];
+        if first {
+            dirty.extend(0..prog.ops.len());
+            dirty_set.iter_mut().for_each(|d| *d = true);
+        } else {
+            for sig in instance
+                .signals
+                .iter()
+                .filter(|sig| changed_signals.contains(sig))
+            {
+                for &value in &instance.signal_values[sig] {
+                    for &i in &prog.triggers[value.index()] {
+                        if !dirty_set[i] {
+                            dirty.push_back(i);
+                            dirty_set[i] = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Execute dirty operations until their results no longer change.
+        while let Some(i) = dirty.pop_front() {
+            dirty_set[i] = false;
+            executed += 1;
+            let op = &prog.ops[i];
+            let regs = &instance.frame;
+            let mut action = if let OpKind::Reg = op.kind {
+                let (state, action) = prog
+                    .context(self.module, regs, None, env)
+                    .exec_reg(op.inst, instance.registers.get(&op.inst));
+                instance.registers.insert(op.inst, state);
+                action
+            } else {
+                self.exec_op(prog, op, regs, None, env)?
+            };
+            if let Action::Call(slot, evs) = action {
+                events.extend(evs);
+                action = slot.map(Action::Value).unwrap_or(Action::None);
+            }
+            match action {
+                Action::None => (),
+                Action::Value(new) => {
+                    let r = op.dst.unwrap();
+                    let changed = match &instance.frame.0[r] {
+                        Some(old @ ValueSlot::Const(_)) => old != &new,
+                        _ => true,
+                    };
+                    instance.frame.0[r] = Some(new);
+                    if changed {
+                        for &i in &prog.uses[i] {
+                            if !dirty_set[i] {
+                                dirty.push_back(i);
+                                dirty_set[i] = true;
+                            }
+                        }
+                    }
+                }
+                Action::Event(e) => events.push(e),
+                Action::Store(..) => panic!("cannot store in entity"),
+                Action::Jump(..) => panic!("cannot jump in entity"),
+                Action::Suspend(..) => panic!("cannot suspend entity"),
+                Action::Call(..) => unreachable!(),
+            }
+        }
+
+        // Suspend entity execution until any of the input and output signals
+        // change.
+        instance.state = InstanceState::Wait(None, instance.signals.clone());
+        if let (Some(profile), Some(started)) = (env.profile, started) {
+            profile.record_instance(&instance.path, executed, started.elapsed());
+        }
+        Ok(events)
+    }
+
+    /// Execute a function call to completion.
+    fn call(
+        &self,
+        callee: UnitId,
+        args: &[(Reg, Reg)],
+        regs: &Registers,
+        env: Env,
+    ) -> Result<Action, ExecError> {
+        let mut events = vec![];
+        let mut stack = vec![self.call_frame(callee, args, regs, None)];
+        let record = env.block_hits.is_some();
+        let mut entered = vec![];
+        if record {
+            entered.push(stack[0].location());
+        }
+        loop {
+            let frame = stack.last_mut().unwrap();
+            let op = match frame.prog.ops.get(frame.pc) {
+                Some(op) => op,
+                None => panic!(
+                    "function {} starved of instructions",
+                    frame.prog.unit.name()
+                ),
+            };
+            frame.pc += 1;
+            let is_phi = matches!(op.kind, OpKind::Phi(_));
+            if !is_phi {
+                for (r, slot) in frame.phis.drain(..) {
+                    frame.regs.0[r] = Some(slot);
+                }
+            }
+            match op.kind {
+                OpKind::Call(callee, ref args) => {
+                    let callee = self.call_frame(callee, args, &frame.regs, op.dst);
+                    if record {
+                        entered.push(callee.location());
+                    }
+                    stack.push(callee);
+                }
+                OpKind::Ret(value) => {
+                    let value = value.map(|r| ValueSlot::Const(constant(&frame.regs, r).clone()));
+                    let frame = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some(caller) => {
+                            if let (Some(result), Some(value)) = (frame.result, value) {
+                                caller.regs.0[result] = Some(value);
+                            }
+                        }
+                        None => {
+                            if let Some(hits) = env.block_hits {
+                                hits.record_functions(&entered);
+                            }
+                            return Ok(Action::Call(value, events));
+                        }
+                    }
+                }
+                _ => {
+                    let pred = frame.pred;
+                    match self.exec_op(frame.prog, op, &frame.regs, pred, env)? {
+                        Action::None => (),
+                        Action::Value(slot) if is_phi => frame.phis.push((op.dst.unwrap(), slot)),
+                        Action::Value(slot) => frame.regs.0[op.dst.unwrap()] = Some(slot),
+                        Action::Store(ptr, value) => store_pointer(&mut frame.regs, &ptr, &value),
+                        Action::Event(e) => events.push(e),
+                        Action::Jump(bb) => {
+                            frame.jump(bb);
+                            if record {
+                                entered.push(frame.location());
+                            }
+                        }
+                        Action::Suspend(..) => panic!("cannot suspend function"),
+                        // Calls of extern functions are executed by the
+                        // interpreter.
+                        Action::Call(slot, evs) => {
+                            if let Some(slot) = slot {
+                                frame.regs.0[op.dst.unwrap()] = Some(slot);
+                            }
+                            events.extend(evs);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create the call frame for a function, binding its arguments to the
+    /// registers of the caller.
+    fn call_frame(
+        &self,
+        callee: UnitId,
+        args: &[(Reg, Reg)],
+        regs: &Registers,
+        result: Option<Reg>,
+    ) -> Frame<'_, 'll> {
+        let prog = &self.units[&callee];
+        let mut callee_regs = Registers(vec![None; prog.num_regs]);
+        for &(to, from) in args {
+            callee_regs.0[to] = regs.0[from].clone();
+        }
+        let entry = prog
+            .unit
+            .first_block()
+            .unwrap_or_else(|| panic!("function {} has no blocks", prog.unit.name()));
+        Frame {
+            prog,
+            regs: callee_regs,
+            block: entry,
+            pred: None,
+            pc: prog.block_starts[entry.index()],
+            phis: vec![],
+            result,
+        }
+    }
+
+    /// Execute a single operation. Returns an action to be taken in response.
+    fn exec_op(
+        &self,
+        prog: &Program<'ll>,
+        op: &Op,
+        regs: &Registers,
+        pred: Option<Block>,
+        env: Env,
+    ) -> Result<Action, ExecError> {
+        let ctx = || prog.context(self.module, regs, pred, env);
+        let value = |r| constant(regs, r);
+        let signal = |r: Reg| match regs.0[r] {
+            Some(ValueSlot::Signal(sig)) => Some(sig),
+            _ => None,
+        };
+        let action = match op.kind {
+            OpKind::Nop => Action::None,
+            OpKind::Const(ref v) => Action::Value(ValueSlot::Const(v.clone())),
+            OpKind::Alias(a) => Action::Value(ValueSlot::Const(value(a).clone())),
+            // Integers with unknown bits are left to the interpreter.
+            OpKind::Unary(opc, a) => match value(a) {
+                Value::Int(a) => Action::Value(ValueSlot::Const(IntValue::unary_op(opc, a).into())),
+                _ => ctx().exec(op.inst)?,
+            },
+            // So are zero divisors.
+            OpKind::Binary(opc, a, b) => match (value(a), value(b)) {
+                (Value::Int(a), Value::Int(b)) => match IntValue::binary_op(opc, a, b) {
+                    Some(v) => Action::Value(ValueSlot::Const(v.into())),
+                    None => ctx().exec(op.inst)?,
+                },
+                _ => ctx().exec(op.inst)?,
+            },
+            OpKind::Compare(_, a, b)
+                if value(a).get_logic().is_some() || value(b).get_logic().is_some() =>
+            {
+                ctx().exec(op.inst)?
+            }
+            OpKind::Compare(opc, a, b) => match Value::compare_op(opc, value(a), value(b)) {
+                Some(v) => Action::Value(ValueSlot::Const(v.into())),
+                None => return Err(ctx().unsupported(op.inst)),
+            },
+            OpKind::Mux(ways, index) => match (value(ways), value(index)) {
+                (Value::Array(v), Value::Int(index)) => {
+                    let index = std::cmp::min(v.len() - 1, index.to_usize());
+                    Action::Value(ValueSlot::Const(v.extract_field(index)))
+                }
+                _ => ctx().exec(op.inst)?,
+            },
+            OpKind::Phi(ref incoming) => {
+                let slot = incoming
+                    .iter()
+                    .find(|&&(bb, _)| Some(bb) == pred)
+                    .and_then(|&(_, r)| regs.0[r].as_ref());
+                match slot {
+                    Some(slot) => Action::Value(slot.clone()),
+                    None => return Err(ctx().unsupported(op.inst)),
+                }
+            }
+            OpKind::Br(bb) => Action::Jump(bb),
+            OpKind::BrCond(cond, if_false, if_true) => {
+                if ctx().is_false(op.inst, value(cond)) {
+                    Action::Jump(if_false)
+                } else {
+                    Action::Jump(if_true)
+                }
+            }
+            OpKind::Wait(bb, delay, ref sigs) => {
+                let sigs = sigs
+                    .iter()
+                    .map(|&r| signal(r).expect("wait on a value that is not a signal"))
+                    .collect();
+                let time = delay.map(|r| {
+                    let delay = value(r).get_time().expect("delay is not a time");
+                    ctx().time_after_delay(delay)
+                });
+                Action::Suspend(Some(bb), InstanceState::Wait(time, sigs))
+            }
+            OpKind::Halt => Action::Suspend(None, InstanceState::Done),
+            OpKind::Prb(sig) => match signal(sig) {
+                Some(sig) => Action::Value(ValueSlot::Const(
+                    env.signals[sig.as_usize()].value().clone(),
+                )),
+                None => ctx().exec(op.inst)?,
+            },
+            OpKind::Drv {
+                signal: sig,
+                value: v,
+                delay,
+                cond,
+                width,
+                mode,
+            } => match signal(sig) {
+                _ if cond
+                    .map(|c| ctx().is_false(op.inst, value(c)))
+                    .unwrap_or(false) =>
+                {
+                    Action::None
+                }
+                Some(sig) => {
+                    let delay = value(delay).get_time().expect("delay is not a time");
+                    Action::Event(Event {
+                        time: ctx().time_after_delay(delay),
+                        signal: ValuePointer(vec![ValueSlice {
+                            target: ValueTarget::Signal(sig),
+                            select: vec![],
+                            width,
+                        }]),
+                        value: value(v).clone(),
+                        mode,
+                        drive: ctx().drive_of(op.inst),
+                    })
+                }
+                None => ctx().exec(op.inst)?,
+            },
+            OpKind::Call(callee, ref args) => self.call(callee, args, regs, env)?,
+            OpKind::Ret(..) | OpKind::Reg | OpKind::Fallback => ctx().exec(op.inst)?,
+        };
+        Ok(action)
+    }
+}
+
+impl<'ll> Program<'ll> {
+    /// Compile a unit.
+    fn compile(module: &'ll Module, unit: Unit<'ll>) -> Self {
+        let mut ops = vec![];
+        let mut block_starts = vec![usize::MAX; unit.block_id_bound()];
+        let mut op_index = HashMap::new();
+        for bb in unit.blocks() {
+            block_starts[bb.index()] = ops.len();
+            for inst in unit.insts(bb) {
+                op_index.insert(inst, ops.len());
+                ops.push(compile_inst(module, unit, inst));
+            }
+        }
+
+        // Allocate one register per value.
+        let num_regs = unit
+            .args()
+            .chain(
+                unit.all_insts()
+                    .filter_map(|inst| unit.get_inst_result(inst)),
+            )
+            .map(|v| v.index() + 1)
+            .max()
+            .unwrap_or(0);
+
+        // Determine which operations have to be re-executed when a value
+        // changes, either as the result of another operation or as a signal.
+        let sorted = |insts: &mut dyn Iterator<Item = Inst>| {
+            let mut indices: Vec<_> = insts.map(|inst| op_index[&inst]).collect();
+            indices.sort_unstable();
+            indices
+        };
+        let uses = ops
+            .iter()
+            .map(|op| match unit.get_inst_result(op.inst) {
+                Some(v) => sorted(&mut unit.uses(v).iter().cloned()),
+                None => vec![],
+            })
+            .collect();
+        let mut triggers = vec![vec![]; num_regs];
+        for value in unit.args().chain(
+            unit.all_insts()
+                .filter_map(|inst| unit.get_inst_result(inst)),
+        ) {
+            triggers[value.index()] =
+                sorted(&mut unit.uses(value).iter().cloned().filter(|&inst| {
+                    match unit[inst].opcode() {
+                        Opcode::Drv | Opcode::Inst | Opcode::Sig | Opcode::Con => false,
+                        Opcode::Del => unit[inst].args()[0] != value,
+                        _ => true,
+                    }
+                }));
+        }
+
+        Program {
+            unit,
+            ops,
+            num_regs,
+            block_starts,
+            uses,
+            triggers,
+        }
+    }
+
+    /// Create the context in which the interpreter executes an instruction of
+    /// this program.
+    fn context<'a>(
+        &'a self,
+        module: &'a Module,
+        regs: &'a Registers,
+        pred: Option<Block>,
+        env: Env<'a>,
+    ) -> InstContext<'a> {
+        InstContext {
+            module,
+            unit: self.unit,
+            values: regs,
+            signals: env.signals,
+            time: env.time,
+            pred,
+            block_hits: env.block_hits,
+            externs: env.externs,
+            xprop: env.xprop,
+        }
+    }
+}
+
+/// Compile a single instruction.
+fn compile_inst(module: &Module, unit: Unit, inst: Inst) -> Op {
+    let data = &unit[inst];
+    let ty = unit.inst_type(inst);
+    let arg = |i: usize| data.args()[i].index();
+    let kind = match data.opcode() {
+        Opcode::Inst | Opcode::Sig | Opcode::Con => OpKind::Nop,
+        Opcode::ConstInt => {
+            let value = data.get_const_int().unwrap().value.to_bigint().unwrap();
+            OpKind::Const(IntValue::from_signed(ty.unwrap_int(), value).into())
+        }
+        Opcode::ConstTime => OpKind::Const(data.get_const_time().unwrap().clone().into()),
+        Opcode::ConstLogic => OpKind::Const(data.get_const_logic().unwrap().clone().into()),
+        Opcode::Alias => OpKind::Alias(arg(0)),
+        Opcode::Not | Opcode::Neg if ty.is_int() => OpKind::Unary(data.opcode(), arg(0)),
+        Opcode::Add
+        | Opcode::Sub
+        | Opcode::And
+        | Opcode::Or
+        | Opcode::Xor
+        | Opcode::Smul
+        | Opcode::Sdiv
+        | Opcode::Smod
+        | Opcode::Srem
+        | Opcode::Umul
+        | Opcode::Udiv
+        | Opcode::Umod
+        | Opcode::Urem
+            if ty.is_int() =>
+        {
+            OpKind::Binary(data.opcode(), arg(0), arg(1))
+        }
+        Opcode::Eq
+        | Opcode::Neq
+        | Opcode::Slt
+        | Opcode::Sgt
+        | Opcode::Sle
+        | Opcode::Sge
+        | Opcode::Ult
+        | Opcode::Ugt
+        | Opcode::Ule
+        | Opcode::Uge => OpKind::Compare(data.opcode(), arg(0), arg(1)),
+        Opcode::Mux => OpKind::Mux(arg(0), arg(1)),
+        Opcode::Phi => OpKind::Phi(
+            data.blocks()
+                .iter()
+                .zip(data.args())
+                .map(|(&bb, arg)| (bb, arg.index()))
+                .collect(),
+        ),
+        Opcode::Br => OpKind::Br(data.blocks()[0]),
+        Opcode::BrCond => OpKind::BrCond(arg(0), data.blocks()[0], data.blocks()[1]),
+        Opcode::Wait => OpKind::Wait(
+            data.blocks()[0],
+            None,
+            data.args().iter().map(|a| a.index()).collect(),
+        ),
+        Opcode::WaitTime => OpKind::Wait(
+            data.blocks()[0],
+            Some(arg(0)),
+            data.args()[1..].iter().map(|a| a.index()).collect(),
+        ),
+        Opcode::Halt if unit.is_entity() => OpKind::Nop,
+        Opcode::Halt => OpKind::Halt,
+        Opcode::Prb => OpKind::Prb(arg(0)),
+        Opcode::Drv | Opcode::DrvCond => {
+            let ty = unit.value_type(data.args()[0]);
+            let width = match **ty.unwrap_signal() {
+                crate::IntType(w) | crate::LogicType(w) | crate::ArrayType(w, _) => Some(w),
+                crate::StructType(..) => Some(0),
+                _ => None,
+            };
+            match width {
+                Some(width) => OpKind::Drv {
+                    signal: arg(0),
+                    value: arg(1),
+                    delay: arg(2),
+                    cond: data.args().get(3).map(|a| a.index()),
+                    width,
+                    mode: data.delay_mode().unwrap(),
+                },
+                None => OpKind::Fallback,
+            }
+        }
+        Opcode::Call => {
+            let ext_unit = data.get_ext_unit().unwrap();
+            match module.lookup_ext_unit(ext_unit, unit.id()) {
+                Some(LinkedUnit::Def(id)) if module.unit(id).is_function() => {
+                    let callee = module.unit(id);
+                    let args = callee
+                        .sig()
+                        .inputs()
+                        .zip(data.input_args())
+                        .map(|(a, v)| (callee.arg_value(a).index(), v.index()))
+                        .collect();
+                    OpKind::Call(id, args)
+                }
+                _ => OpKind::Fallback,
+            }
+        }
+        Opcode::Ret => OpKind::Ret(None),
+        Opcode::RetValue => OpKind::Ret(Some(arg(0))),
+        Opcode::Reg => OpKind::Reg,
+        _ => OpKind::Fallback,
+    };
+    Op {
+        inst,
+        dst: unit.get_inst_result(inst).map(|v| v.index()),
+        kind,
+    }
+}
+
+/// Get the constant value held by a register.
+fn constant(regs: &Registers, r: Reg) -> &Value {
+    match &regs.0[r] {
+        Some(ValueSlot::Const(v)) => v,
+        x => panic!("expected register {} to hold a constant, got {:?}", r, x),
+    }
+}
+
+/// A function activation on the call stack.
+struct Frame<'p, 'll> {
+    prog: &'p Program<'ll>,
+    regs: Registers,
+    block: Block,
+    pred: Option<Block>,
+    pc: usize,
+    phis: Vec<(Reg, ValueSlot)>,
+    /// The register in the caller which receives the return value.
+    result: Option<Reg>,
+}
+
+impl Frame<'_, '_> {
+    /// Transfer control to a different block.
+    fn jump(&mut self, block: Block) {
+        self.pc = self.prog.block_starts[block.index()];
+        self.pred = Some(std::mem::replace(&mut self.block, block));
+    }
+
+    /// The function and block currently being executed.
+    fn location(&self) -> (UnitId, Block) {
+        (self.prog.unit.id(), self.block)
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Simulation checkpoints
+//!
+//! A checkpoint captures the complete dynamic state of a simulation: the
+//! current time and the delta cycles performed at it, the signal values and
+//! the values of the drivers of resolved signals, the event and wakeup queues,
+//! the internal state of every instance, a pending request of an extern
+//! function to end the simulation, and the coverage and profile collected so
+//! far. The static structure of the design, such as the signal types and the
+//! design hierarchy, is not stored. Instead, it is rebuilt from the module upon
+//! restore, which therefore has to be the same module the checkpoint was taken
+//! from. Settings such as the delta cycle limit and X propagation are not
+//! stored either, and have to be applied to the restored simulation again.
+
+use crate::sim::{
+    coverage::CoverageState,
+    engine::DeltaCycles,
+    profile::ProfileState,
+    state::{
+        Driver, InstanceKind, InstanceRef, InstanceState, RegisterState, State, ValuePointer,
+        ValueSlot,
+    },
+    value::{TimeValue, Value},
+    SimulationError,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The magic number at the beginning of a checkpoint file.
+const MAGIC: [u8; 8] = *b"LLHDCKPT";
+
+/// The version of the checkpoint format.
+const VERSION: u32 = 1;
+
+/// A snapshot of the dynamic state of a simulation.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Checkpoint {
+    /// The name of the root unit of the design hierarchy.
+    pub top: String,
+    /// The number of steps simulated so far.
+    pub steps: usize,
+    /// Whether the current simulation time has already been simulated.
+    pub settled: bool,
+    time: TimeValue,
+    deltas: DeltaCycles,
+    signals: Vec<(Value, BTreeMap<Option<Driver>, Value>)>,
+    insts: Vec<InstanceCheckpoint>,
+    events: BTreeMap<TimeValue, HashMap<(ValuePointer, Option<Driver>), Value>>,
+    timed: BTreeMap<TimeValue, HashSet<InstanceRef>>,
+    finish: bool,
+    coverage: Option<CoverageState>,
+    profile: Option<ProfileState>,
+}
+
+/// A snapshot of the dynamic state of an instance.
+#[derive(Serialize, Deserialize)]
+struct InstanceCheckpoint {
+    path: String,
+    values: HashMap<crate::ir::Value, ValueSlot>,
+    state: InstanceState,
+    registers: HashMap<crate::ir::Inst, RegisterState>,
+    next_block: Option<crate::ir::Block>,
+    prev_block: Option<crate::ir::Block>,
+}
+
+impl Checkpoint {
+    /// Capture the state of a simulation.
+    pub fn capture(state: &State, steps: usize, settled: bool) -> Self {
+        Self {
+            top: state.scope.name.clone(),
+            steps,
+            settled,
+            time: state.time.clone(),
+            deltas: state.deltas.clone(),
+            signals: state
+                .signals
+                .iter()
+                .map(|s| (s.value().clone(), s.drivers.clone()))
+                .collect(),
+            insts: state
+                .insts
+                .iter()
+                .map(|inst| {
+                    let inst = inst.lock().unwrap();
+                    let (next_block, prev_block) = match inst.kind {
+                        InstanceKind::Process {
+                            next_block,
+                            prev_block,
+                            ..
+                        } => (next_block, prev_block),
+                        InstanceKind::Entity { .. } => (None, None),
+                    };
+                    InstanceCheckpoint {
+                        path: inst.path.clone(),
+                        values: inst.current_values(),
+                        state: inst.state.clone(),
+                        registers: inst.registers.clone(),
+                        next_block,
+                        prev_block,
+                    }
+                })
+                .collect(),
+            events: state.events.clone(),
+            timed: state.timed.clone(),
+            finish: state.externs.finish_requested(),
+            coverage: state.coverage.clone(),
+            profile: state.profile.clone(),
+        }
+    }
+
+    /// Serialize the checkpoint.
+    pub fn write(&self, mut writer: impl std::io::Write) -> Result<(), SimulationError> {
+        writer.write_all(&MAGIC).map_err(error)?;
+        writer.write_all(&VERSION.to_le_bytes()).map_err(error)?;
+        bincode::serialize_into(writer, self).map_err(error)
+    }
+
+    /// Deserialize a checkpoint.
+    pub fn read(mut reader: impl std::io::Read) -> Result<Self, SimulationError> {
+        let mut magic = [0; 8];
+        let mut version = [0; 4];
+        reader.read_exact(&mut magic).map_err(error)?;
+        if magic != MAGIC {
+            return Err(SimulationError::Checkpoint(
+                "not a simulation checkpoint".to_string(),
+            ));
+        }
+        reader.read_exact(&mut version).map_err(error)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(SimulationError::Checkpoint(format!(
+                "unsupported checkpoint version {}",
+                version
+            )));
+        }
+        bincode::deserialize_from(reader).map_err(error)
+    }
+
+    /// Apply the checkpoint to a freshly built simulation state.
+    ///
+    /// Fails if the state was built from a different design than the one the
+    /// checkpoint was taken from.
+    pub fn apply(self, state: &mut State) -> Result<(), SimulationError> {
+        let mismatch = || SimulationError::Checkpoint("checkpoint does not match design".into());
+        if self.signals.len() != state.signals.len() || self.insts.len() != state.insts.len() {
+            return Err(mismatch());
+        }
+        for (signal, (value, drivers)) in state.signals.iter_mut().zip(self.signals) {
+            signal.set_value(value);
+            signal.drivers = drivers;
+        }
+        for (inst, cp) in state.insts.iter_mut().zip(self.insts) {
+            let inst = inst.get_mut().unwrap();
+            if inst.path != cp.path {
+                return Err(mismatch());
+            }
+            inst.values = cp.values;
+            inst.frame = Default::default();
+            inst.state = cp.state;
+            inst.registers = cp.registers;
+            if let InstanceKind::Process {
+                ref mut next_block,
+                ref mut prev_block,
+                ..
+            } = inst.kind
+            {
+                *next_block = cp.next_block;
+                *prev_block = cp.prev_block;
+            }
+        }
+        state.time = self.time;
+        let limit = state.deltas.limit;
+        state.deltas = self.deltas;
+        state.deltas.limit = limit;
+        state.set_events(self.events);
+        state.timed = self.timed;
+        state.externs.set_finish(self.finish);
+        state.coverage = self.coverage;
+        state.profile = self.profile;
+        Ok(())
+    }
+}
+
+/// Wrap an I/O or serialization error.
+fn error(err: impl std::fmt::Display) -> SimulationError {
+    SimulationError::Checkpoint(err.to_string())
+}
@@ -0,0 +1,547 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Coverage of a simulation
+//!
+//! Two kinds of coverage are collected while a design is simulated:
+//!
+//! - Toggle coverage records for every bit of every signal whether it rose
+//!   from 0 to 1 and fell from 1 to 0. A bit is covered once it did both.
+//! - Block coverage records how often each block of a process or function was
+//!   entered. A block is covered once it was entered at least once.
+//!
+//! The coverage is reported along the design hierarchy, with one scope per
+//! instance. Reports can be written as JSON, read back, and merged, such that
+//! the coverage of several simulation runs can be combined.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "root": {
+//!     "name": "@top",
+//!     "signals": {
+//!       "clk": {"rose": "1", "fell": "1"}
+//!     },
+//!     "blocks": {"unit": "@top", "blocks": [["entry", 1], ["loop", 8]]},
+//!     "subscopes": []
+//!   },
+//!   "functions": []
+//! }
+//! ```
+//!
+//! The toggles of a signal are given as bit strings with the most significant
+//! bit first, just like a binary literal.
+
+use crate::ir::{Block, UnitId};
+use crate::sim::state::{InstanceKind, Signal, SignalRef, State};
+use crate::value::LogicBit;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    sync::Mutex,
+};
+
+/// The version of the JSON report format.
+const VERSION: u64 = 1;
+
+/// The coverage collected during one or more simulation runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    /// The coverage of the root scope of the design hierarchy.
+    pub root: ScopeCoverage,
+    /// The block coverage of the functions in the module, ordered by name.
+    pub functions: Vec<BlockCoverage>,
+}
+
+/// The coverage of a scope in the design hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopeCoverage {
+    /// The name of the scope.
+    pub name: String,
+    /// The toggle coverage of the signals visible in this scope, by name.
+    pub signals: BTreeMap<String, ToggleCoverage>,
+    /// The block coverage of the process instantiated in this scope.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<BlockCoverage>,
+    /// The subscopes.
+    pub subscopes: Vec<ScopeCoverage>,
+}
+
+/// The toggle coverage of a signal.
+///
+/// Both vectors hold one entry per bit, least significant bit first. The bits
+/// of arrays and structs are the bits of their elements, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToggleCoverage {
+    /// Whether each bit rose from 0 to 1.
+    #[serde(with = "bits")]
+    pub rose: Vec<bool>,
+    /// Whether each bit fell from 1 to 0.
+    #[serde(with = "bits")]
+    pub fell: Vec<bool>,
+}
+
+/// The block coverage of a process or function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockCoverage {
+    /// The name of the process or function.
+    pub unit: String,
+    /// The name of each block and how often it was entered, in layout order.
+    pub blocks: Vec<(String, u64)>,
+}
+
+impl Coverage {
+    /// Parse a coverage report from its JSON representation.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let header: Header = serde_json::from_str(input).map_err(|e| e.to_string())?;
+        match header.version {
+            Some(VERSION) => (),
+            Some(v) => return Err(format!("unsupported coverage report version {}", v)),
+            None => return Err("not a coverage report".to_string()),
+        }
+        serde_json::from_str(input).map_err(|e| e.to_string())
+    }
+
+    /// Render the coverage report as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&Report {
+            version: VERSION,
+            coverage: self,
+        })
+        .unwrap()
+    }
+
+    /// Merge the coverage of another run of the same design into this report.
+    ///
+    /// A bit is considered toggled if it toggled in either run, and the
+    /// number of times a block was entered is the sum of both runs. Scopes,
+    /// signals, and blocks that only appear in one of the reports are kept.
+    pub fn merge(&mut self, other: &Coverage) -> Result<(), String> {
+        if self.root.name != other.root.name {
+            return Err(format!(
+                "cannot merge coverage of {} into coverage of {}",
+                other.root.name, self.root.name
+            ));
+        }
+        self.root.merge(&other.root)?;
+        for func in &other.functions {
+            match self.functions.iter_mut().find(|f| f.unit == func.unit) {
+                Some(f) => f.merge(func),
+                None => self.functions.push(func.clone()),
+            }
+        }
+        self.functions.sort_by(|a, b| a.unit.cmp(&b.unit));
+        Ok(())
+    }
+}
+
+/// Prints a human-readable summary of the coverage.
+///
+/// Each scope lists the totals of its entire subtree, followed by the signals
+/// that did not fully toggle and the blocks that were never entered within the
+/// scope itself.
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.root.fmt_summary(f, 0)?;
+        if self.functions.is_empty() {
+            return Ok(());
+        }
+        let (covered, total) = self
+            .functions
+            .iter()
+            .fold((0, 0), |(c, t), b| (c + b.covered(), t + b.blocks.len()));
+        writeln!(f, "functions: {}", ratio(covered, total, "blocks entered"))?;
+        for func in &self.functions {
+            writeln!(
+                f,
+                "  {}: {}",
+                func.unit,
+                ratio(func.covered(), func.blocks.len(), "blocks entered")
+            )?;
+            func.fmt_missed(f, 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl ScopeCoverage {
+    /// Count the toggled and total bits of the signals in this scope and its
+    /// subscopes.
+    pub fn toggle_totals(&self) -> (usize, usize) {
+        let own = self
+            .signals
+            .values()
+            .fold((0, 0), |(c, t), s| (c + s.covered(), t + s.width()));
+        self.subscopes
+            .iter()
+            .map(|s| s.toggle_totals())
+            .fold(own, |(c, t), (sc, st)| (c + sc, t + st))
+    }
+
+    /// Count the entered and total blocks of the processes in this scope and
+    /// its subscopes.
+    pub fn block_totals(&self) -> (usize, usize) {
+        let own = self
+            .blocks
+            .as_ref()
+            .map(|b| (b.covered(), b.blocks.len()))
+            .unwrap_or((0, 0));
+        self.subscopes
+            .iter()
+            .map(|s| s.block_totals())
+            .fold(own, |(c, t), (sc, st)| (c + sc, t + st))
+    }
+
+    fn merge(&mut self, other: &ScopeCoverage) -> Result<(), String> {
+        for (name, toggles) in &other.signals {
+            match self.signals.get_mut(name) {
+                Some(t) if t.width() != toggles.width() => {
+                    return Err(format!(
+                        "signal {} in {} has {} bits in one report and {} in the other",
+                        name,
+                        self.name,
+                        t.width(),
+                        toggles.width()
+                    ))
+                }
+                Some(t) => t.merge(toggles),
+                None => {
+                    self.signals.insert(name.clone(), toggles.clone());
+                }
+            }
+        }
+        match (&mut self.blocks, &other.blocks) {
+            (Some(a), Some(b)) => a.merge(b),
+            (a @ None, b) => *a = b.clone(),
+            (Some(_), None) => (),
+        }
+        for sub in &other.subscopes {
+            match self.subscopes.iter_mut().find(|s| s.name == sub.name) {
+                Some(s) => s.merge(sub)?,
+                None => self.subscopes.push(sub.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    fn fmt_summary(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        let (toggled, bits) = self.toggle_totals();
+        let (entered, blocks) = self.block_totals();
+        writeln!(
+            f,
+            "{}{}: {}, {}",
+            indent,
+            self.name,
+            ratio(toggled, bits, "bits toggled"),
+            ratio(entered, blocks, "blocks entered")
+        )?;
+        for (name, toggles) in &self.signals {
+            if toggles.covered() < toggles.width() {
+                writeln!(
+                    f,
+                    "{}  not toggled: {} ({} of {} bits)",
+                    indent,
+                    name,
+                    toggles.width() - toggles.covered(),
+                    toggles.width()
+                )?;
+            }
+        }
+        if let Some(ref blocks) = self.blocks {
+            blocks.fmt_missed(f, depth + 1)?;
+        }
+        for sub in &self.subscopes {
+            sub.fmt_summary(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToggleCoverage {
+    /// The number of bits of the signal.
+    pub fn width(&self) -> usize {
+        self.rose.len()
+    }
+
+    /// The number of bits that both rose and fell.
+    pub fn covered(&self) -> usize {
+        self.rose
+            .iter()
+            .zip(&self.fell)
+            .filter(|&(&r, &f)| r && f)
+            .count()
+    }
+
+    fn merge(&mut self, other: &ToggleCoverage) {
+        for (a, &b) in self.rose.iter_mut().zip(&other.rose) {
+            *a |= b;
+        }
+        for (a, &b) in self.fell.iter_mut().zip(&other.fell) {
+            *a |= b;
+        }
+    }
+}
+
+impl BlockCoverage {
+    /// The number of blocks that were entered at least once.
+    pub fn covered(&self) -> usize {
+        self.blocks.iter().filter(|&&(_, n)| n > 0).count()
+    }
+
+    fn merge(&mut self, other: &BlockCoverage) {
+        for (name, count) in &other.blocks {
+            match self.blocks.iter_mut().find(|(n, _)| n == name) {
+                Some((_, n)) => *n += count,
+                None => self.blocks.push((name.clone(), *count)),
+            }
+        }
+    }
+
+    fn fmt_missed(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let missed: Vec<_> = self
+            .blocks
+            .iter()
+            .filter(|&&(_, n)| n == 0)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !missed.is_empty() {
+            writeln!(
+                f,
+                "{}not entered: {} {}",
+                "  ".repeat(depth),
+                self.unit,
+                missed.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The fields that precede the coverage in a JSON report.
+#[derive(Serialize)]
+struct Report<'a> {
+    version: u64,
+    #[serde(flatten)]
+    coverage: &'a Coverage,
+}
+
+/// The version of a JSON report, read before the coverage itself.
+#[derive(Deserialize)]
+struct Header {
+    version: Option<u64>,
+}
+
+/// Serialize bits as a string with the most significant bit first, just like
+/// a binary literal.
+mod bits {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bits: &[bool], serializer: S) -> Result<S::Ok, S::Error> {
+        let s: String = bits
+            .iter()
+            .rev()
+            .map(|&b| if b { '1' } else { '0' })
+            .collect();
+        serializer.serialize_str(&s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+        String::deserialize(deserializer)?
+            .chars()
+            .rev()
+            .map(|c| match c {
+                '0' => Ok(false),
+                '1' => Ok(true),
+                _ => Err(D::Error::custom(format!("invalid bit `{}`", c))),
+            })
+            .collect()
+    }
+}
+
+/// Format a covered and total count, with a percentage if there is anything to
+/// cover.
+fn ratio(covered: usize, total: usize, what: &str) -> String {
+    if total == 0 {
+        format!("{}/{} {}", covered, total, what)
+    } else {
+        format!(
+            "{}/{} {} ({:.1}%)",
+            covered,
+            total,
+            what,
+            covered as f64 * 100.0 / total as f64
+        )
+    }
+}
+
+/// The coverage collected while a simulation runs.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct CoverageState {
+    /// How often the blocks of processes and functions were entered.
+    pub blocks: BlockHits,
+    /// The toggles observed on each signal, indexed by signal.
+    toggles: Vec<Toggles>,
+}
+
+/// The toggles observed on a signal.
+#[derive(Clone, Serialize, Deserialize)]
+struct Toggles {
+    /// The bits of the signal's value when it was last observed.
+    last: Vec<LogicBit>,
+    rose: Vec<bool>,
+    fell: Vec<bool>,
+}
+
+/// How often the blocks of processes and functions were entered.
+///
+/// Instances record the blocks they entered during a step all at once, such
+/// that they can run in parallel without contending for the counters.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct BlockHits {
+    /// The counts of each process instance, by hierarchical name.
+    processes: Mutex<HashMap<String, HashMap<Block, u64>>>,
+    /// The counts of each function, across all calls.
+    functions: Mutex<HashMap<UnitId, HashMap<Block, u64>>>,
+}
+
+impl Clone for BlockHits {
+    fn clone(&self) -> Self {
+        BlockHits {
+            processes: Mutex::new(self.processes.lock().unwrap().clone()),
+            functions: Mutex::new(self.functions.lock().unwrap().clone()),
+        }
+    }
+}
+
+impl BlockHits {
+    /// Record the blocks a process instance entered.
+    pub fn record_process(&self, path: &str, blocks: &[Block]) {
+        let mut processes = self.processes.lock().unwrap();
+        let counts = processes.entry(path.to_string()).or_default();
+        for &block in blocks {
+            *counts.entry(block).or_default() += 1;
+        }
+    }
+
+    /// Record the blocks functions entered.
+    pub fn record_functions(&self, blocks: &[(UnitId, Block)]) {
+        let mut functions = self.functions.lock().unwrap();
+        for &(unit, block) in blocks {
+            *functions.entry(unit).or_default().entry(block).or_default() += 1;
+        }
+    }
+}
+
+impl CoverageState {
+    /// Start collecting coverage, taking the current signal values as the
+    /// reference for the first toggles.
+    pub fn new(signals: &[Signal]) -> Self {
+        CoverageState {
+            blocks: Default::default(),
+            toggles: signals
+                .iter()
+                .map(|signal| {
+                    let mut last = vec![];
+                    signal.value().flatten(&mut last);
+                    Toggles {
+                        rose: vec![false; last.len()],
+                        fell: vec![false; last.len()],
+                        last,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Record the toggles of the signals that changed in a step.
+    pub fn record_toggles(&mut self, signals: &[Signal], changed: &HashSet<SignalRef>) {
+        let mut bits = vec![];
+        for sig in changed {
+            let toggles = &mut self.toggles[sig.as_usize()];
+            bits.clear();
+            signals[sig.as_usize()].value().flatten(&mut bits);
+            for (i, (&old, &new)) in toggles.last.iter().zip(&bits).enumerate() {
+                // Logic bits that do not carry a one count as zero.
+                let (old, new) = (old.to_bool() == Some(true), new.to_bool() == Some(true));
+                toggles.rose[i] |= !old && new;
+                toggles.fell[i] |= old && !new;
+            }
+            std::mem::swap(&mut toggles.last, &mut bits);
+        }
+    }
+
+    /// Assemble a coverage report along the design hierarchy.
+    pub fn report(&self, state: &State) -> Coverage {
+        let mut processes = HashMap::new();
+        for inst in &state.insts {
+            let inst = inst.lock().unwrap();
+            if let InstanceKind::Process { prok, .. } = inst.kind {
+                processes.insert(inst.path.clone(), prok);
+            }
+        }
+        let process_hits = self.blocks.processes.lock().unwrap();
+        let function_hits = self.blocks.functions.lock().unwrap();
+        let no_hits = HashMap::new();
+        let blocks = |unit: crate::ir::Unit, hits: &HashMap<Block, u64>| BlockCoverage {
+            unit: unit.name().to_string(),
+            blocks: unit
+                .blocks()
+                .map(|bb| {
+                    let count = hits.get(&bb).copied().unwrap_or(0);
+                    (bb.dump(&unit).to_string(), count)
+                })
+                .collect(),
+        };
+
+        let scope = |scope: &crate::sim::Scope, path: &str| {
+            let mut signals = BTreeMap::new();
+            for (sig, names) in &scope.probes {
+                let toggles = &self.toggles[sig.as_usize()];
+                if toggles.last.is_empty() {
+                    continue;
+                }
+                for name in names {
+                    signals.insert(
+                        name.clone(),
+                        ToggleCoverage {
+                            rose: toggles.rose.clone(),
+                            fell: toggles.fell.clone(),
+                        },
+                    );
+                }
+            }
+            ScopeCoverage {
+                name: scope.name.clone(),
+                signals,
+                blocks: processes
+                    .get(path)
+                    .map(|&unit| blocks(unit, process_hits.get(path).unwrap_or(&no_hits))),
+                subscopes: vec![],
+            }
+        };
+        fn walk(
+            scope: &crate::sim::Scope,
+            path: String,
+            f: &dyn Fn(&crate::sim::Scope, &str) -> ScopeCoverage,
+        ) -> ScopeCoverage {
+            let mut cov = f(scope, &path);
+            cov.subscopes = scope
+                .subscopes
+                .iter()
+                .map(|s| walk(s, format!("{}.{}", path, s.name), f))
+                .collect();
+            cov
+        }
+
+        let mut functions: Vec<_> = state
+            .module
+            .functions()
+            .map(|unit| blocks(unit, function_hits.get(&unit.id()).unwrap_or(&no_hits)))
+            .collect();
+        functions.sort_by(|a, b| a.unit.cmp(&b.unit));
+        Coverage {
+            root: walk(&state.scope, state.scope.name.clone(), &scope),
+            functions,
+        }
+    }
+}
@@ -0,0 +1,1982 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Simulation execution engine
+//!
+//! The execution engine that advances the simulation step by step.
+
+use crate::ir::{DelayMode, Opcode};
+use crate::sim::{
+    bytecode::Programs,
+    coverage::BlockHits,
+    externs::Externs,
+    profile::ProfileState,
+    state::{
+        Event, Instance, InstanceKind, InstanceRef, InstanceState, RegisterState, Registers,
+        Signal, SignalRef, State, TimedInstance, ValuePointer, ValueSelect, ValueSlice, ValueSlot,
+        ValueTarget,
+    },
+    tracer::Tracer,
+    value::{ArrayValue, IntValue, LogicValue, StructValue, TimeValue, Value},
+    xprop::XpropState,
+    SimulationError,
+};
+use num::bigint::ToBigInt;
+use rayon::prelude::*;
+use std::{
+    borrow::BorrowMut,
+    collections::VecDeque,
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
+
+pub struct Engine<'ts, 'tm: 'ts> {
+    state: &'ts mut State<'tm>,
+    parallelize: bool,
+    programs: Option<&'ts Programs<'tm>>,
+}
+
+impl<'ts, 'tm> Engine<'ts, 'tm> {
+    /// Create a new engine to advance some simulation state.
+    ///
+    /// If compiled programs are provided, instances are executed by the
+    /// bytecode backend instead of being interpreted.
+    pub fn new(
+        state: &'ts mut State<'tm>,
+        parallelize: bool,
+        programs: Option<&'ts Programs<'tm>>,
+    ) -> Engine<'ts, 'tm> {
+        Engine {
+            state,
+            parallelize,
+            programs,
+        }
+    }
+
+    /// Perform one simulation step. Returns true if there are remaining events
+    /// in the queue, false otherwise. This can be used as an indication as to
+    /// when the simulation is finished.
+    ///
+    /// During the first step, all instances are executed in their entirety.
+    pub fn step(
+        &mut self,
+        tracers: &mut [Box<dyn Tracer + '_>],
+        first: bool,
+    ) -> Result<bool, SimulationError> {
+        info!("STEP {}", self.state.time);
+
+        // Apply events at this time, note changed signals.
+        let mut changed_signals = HashSet::new();
+        for (driver, signal, value) in self.state.take_next_events() {
+            // Determine the current state of all targeted signals, as seen by
+            // the driver.
+            let signals = signal.0.iter().map(|s| s.target.unwrap_signal());
+            let mut modified: Vec<_> = signals
+                .clone()
+                .map(|sig| self.state[sig].driven_value(driver))
+                .collect();
+            for sig in signals.clone() {
+                trace!("Event: {}", self.state.probes[&sig][0]);
+                if let Some(ref mut profile) = self.state.profile {
+                    profile.record_event(sig);
+                }
+            }
+
+            // Modify the signals.
+            write_pointer(&signal, &mut modified, &value);
+
+            // Store the modified state back.
+            for (sig, modified) in signals.zip(modified) {
+                let modified = match self.state.xprop {
+                    Some(_) => modified.into_known(self.state[sig].ty().unwrap_signal()),
+                    None => modified,
+                };
+                let old = self.state[sig].value().clone();
+                if self.state[sig].drive(driver, modified) {
+                    changed_signals.insert(sig);
+                    debug!(
+                        "Change {} {} -> {}",
+                        self.state.probes[&sig][0],
+                        old,
+                        self.state[sig].value()
+                    );
+                }
+            }
+        }
+
+        // Wake up units whose timed wait has run out.
+        for inst in self.state.take_next_timed() {
+            debug!("Wakeup {} (time)", self.state[inst].lock().unwrap().name(),);
+            self.state[inst].lock().unwrap().state = InstanceState::Ready;
+        }
+
+        // Wake up units that were sensitive to one of the changed signals.
+        for inst in &mut self.state.insts {
+            let mut inst = inst.lock().unwrap();
+            let trigger = if let InstanceState::Wait(_, ref signals) = inst.state {
+                // Attribute the wakeup to every changed signal when profiling.
+                let mut trigger = false;
+                for &s in signals.iter().filter(|s| changed_signals.contains(s)) {
+                    trigger = true;
+                    match self.state.profile {
+                        Some(ref mut profile) => profile.record_wakeup(s),
+                        None => break,
+                    }
+                }
+                trigger
+            } else {
+                false
+            };
+            if trigger {
+                debug!("Wakeup {} (sense)", inst.name());
+                inst.state = InstanceState::Ready;
+            }
+        }
+
+        // Record the toggles of the changed signals.
+        if let Some(ref mut coverage) = self.state.coverage {
+            coverage.record_toggles(&self.state.signals, &changed_signals);
+        }
+
+        // Call output hook to write simulation trace to disk.
+        for tracer in tracers.iter_mut() {
+            tracer.step(self.state, &changed_signals);
+        }
+
+        // Execute the instances that are ready.
+        let ready_insts: Vec<_> = self
+            .state
+            .insts
+            .iter()
+            .enumerate()
+            .filter(|&(_, u)| u.lock().unwrap().state == InstanceState::Ready)
+            .map(|(i, _)| i)
+            .collect();
+        let events = if self.parallelize {
+            ready_insts
+                .par_iter()
+                .map(|&index| {
+                    let mut lk = self.state.insts[index].lock().unwrap();
+                    let events = self.step_instance(lk.borrow_mut(), &changed_signals, first)?;
+                    let inst = InstanceRef::new(index);
+                    Ok(events.into_iter().map(|e| (inst, e)).collect())
+                })
+                .reduce(
+                    || Ok(Vec::new()),
+                    |a, b| {
+                        let mut a = a?;
+                        a.extend(b?);
+                        Ok(a)
+                    },
+                )?
+        } else {
+            let mut events = Vec::new();
+            for &index in &ready_insts {
+                let mut lk = self.state.insts[index].lock().unwrap();
+                let inst = InstanceRef::new(index);
+                events.extend(
+                    self.step_instance(lk.borrow_mut(), &changed_signals, first)?
+                        .into_iter()
+                        .map(|e| (inst, e)),
+                );
+            }
+            events
+        };
+        self.state.schedule_events(events.into_iter());
+
+        // Gather a list of instances that perform a timed wait and schedule
+        // them as to be woken up.
+        let timed: Vec<_> = ready_insts
+            .iter()
+            .filter_map(
+                |index| match self.state.insts[*index].lock().unwrap().state {
+                    InstanceState::Wait(Some(ref time), _) => Some(TimedInstance {
+                        time: time.clone(),
+                        inst: InstanceRef::new(*index),
+                    }),
+                    _ => None,
+                },
+            )
+            .collect();
+        self.state.schedule_timed(timed.into_iter());
+
+        // End the simulation if an extern function requested so.
+        if self.state.externs.take_finish() {
+            self.state.events.clear();
+            self.state.pending.clear();
+            self.state.timed.clear();
+            return Ok(false);
+        }
+
+        // Advance time to next event or process wake, or finish
+        match self.state.next_time() {
+            Some(t) => {
+                self.count_delta_cycle(&t, &changed_signals, &ready_insts)?;
+                self.state.time = t;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Count the delta cycles of the current time step before advancing to the
+    /// next step, and abort the simulation once they exceed the limit.
+    fn count_delta_cycle(
+        &mut self,
+        next: &TimeValue,
+        changed_signals: &HashSet<SignalRef>,
+        ready_insts: &[usize],
+    ) -> Result<(), SimulationError> {
+        let deltas = &mut self.state.deltas;
+        if next.time() != self.state.time.time() {
+            deltas.count = 0;
+            deltas.recent.clear();
+            return Ok(());
+        }
+        let limit = match deltas.limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        deltas.count += 1;
+
+        // Keep track of what happened in the last cycles before the limit, to
+        // report the signals and instances that do not settle.
+        if deltas.count + DeltaCycles::HISTORY > limit {
+            if deltas.recent.len() == DeltaCycles::HISTORY {
+                deltas.recent.pop_front();
+            }
+            deltas.recent.push_back((
+                changed_signals.iter().cloned().collect(),
+                ready_insts.iter().map(|&i| InstanceRef::new(i)).collect(),
+            ));
+        }
+        if deltas.count <= limit {
+            return Ok(());
+        }
+
+        let recent = std::mem::take(&mut deltas.recent);
+        let mut signals: Vec<_> = recent
+            .iter()
+            .flat_map(|(signals, _)| signals)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|&sig| {
+                self.state
+                    .signal_name(sig)
+                    .unwrap_or_else(|| format!("{:?}", sig))
+            })
+            .collect();
+        signals.sort();
+        let mut instances: Vec<_> = recent
+            .iter()
+            .flat_map(|(_, insts)| insts)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|&inst| self.state[inst].lock().unwrap().path.clone())
+            .collect();
+        instances.sort();
+        instances.dedup();
+        Err(SimulationError::DeltaLimit {
+            limit,
+            signals,
+            instances,
+        })
+    }
+
+    /// Continue execution of one single process or entity instance, until it is
+    /// suspended by an instruction.
+    fn step_instance(
+        &self,
+        instance: &mut Instance,
+        changed_signals: &HashSet<SignalRef>,
+        first: bool,
+    ) -> Result<Vec<Event>, SimulationError> {
+        if let Some(programs) = self.programs {
+            return programs.step_instance(instance, self.state, changed_signals, first);
+        }
+        match instance.kind {
+            InstanceKind::Process {
+                prok,
+                next_block,
+                prev_block,
+            } => self.step_process(instance, prok, next_block, prev_block),
+            InstanceKind::Entity { entity } => {
+                self.step_entity(instance, entity, changed_signals, first)
+            }
+        }
+    }
+
+    /// Continue execution of one single process until it is suspended by an
+    /// instruction.
+    fn step_process(
+        &self,
+        instance: &mut Instance,
+        unit: crate::ir::Unit,
+        block: Option<crate::ir::Block>,
+        pred: Option<crate::ir::Block>,
+    ) -> Result<Vec<Event>, SimulationError> {
+        debug!("Step process {}", unit.name());
+        let started = self.profile().map(|_| Instant::now());
+        let mut executed = 0;
+        let mut events = Vec::new();
+        let mut next_block = block;
+        let mut pred = pred;
+        let mut entered = vec![];
+        while let Some(block) = next_block {
+            next_block = None;
+            if self.block_hits().is_some() {
+                entered.push(block);
+            }
+            // The `phi` instructions at the beginning of a block all observe
+            // the values of the predecessor, so their results are only stored
+            // once the first other instruction is reached.
+            let mut phis = vec![];
+            for inst in unit.insts(block) {
+                if unit[inst].opcode() != Opcode::Phi {
+                    for (v, vs) in phis.drain(..) {
+                        trace!("{} = {}", v, vs);
+                        instance.set_value(v, vs);
+                    }
+                }
+                executed += 1;
+                let action = self
+                    .execute_instruction(inst, unit, &instance.values, &self.state.signals, pred)
+                    .map_err(|e| e.in_instance(instance))?;
+                match action {
+                    Action::Value(vs) if unit[inst].opcode() == Opcode::Phi => {
+                        phis.push((unit.inst_result(inst), vs))
+                    }
+                    Action::None => (),
+                    Action::Value(vs) => {
+                        let v = unit.inst_result(inst);
+                        trace!("{} = {}", v, vs);
+                        instance.set_value(v, vs)
+                    }
+                    Action::Store(ptr, value) => store_pointer(&mut instance.values, &ptr, &value),
+                    Action::Event(e) => events.push(e),
+                    Action::Call(vs, evs) => {
+                        if let Some(vs) = vs {
+                            let v = unit.inst_result(inst);
+                            trace!("{} = {}", v, vs);
+                            instance.set_value(v, vs)
+                        }
+                        events.extend(evs);
+                    }
+                    Action::Jump(blk) => {
+                        next_block = Some(blk);
+                        pred = Some(block);
+                        break;
+                    }
+                    Action::Suspend(blk, st) => {
+                        instance.state = st;
+                        match instance.kind {
+                            InstanceKind::Process {
+                                ref mut next_block,
+                                ref mut prev_block,
+                                ..
+                            } => {
+                                *next_block = blk;
+                                *prev_block = Some(block);
+                            }
+                            _ => unreachable!(),
+                        }
+                        if let Some(hits) = self.block_hits() {
+                            hits.record_process(&instance.path, &entered);
+                        }
+                        if let (Some(profile), Some(started)) = (self.profile(), started) {
+                            profile.record_instance(&instance.path, executed, started.elapsed());
+                        }
+                        return Ok(events);
+                    }
+                }
+            }
+        }
+
+        // We should never arrive here, since every block ends with a
+        // terminator instruction that redirects control flow.
+        panic!("process starved of instructions");
+    }
+
+    /// Continue execution of one single entity.
+    fn step_entity(
+        &self,
+        instance: &mut Instance,
+        unit: crate::ir::Unit,
+        changed_signals: &HashSet<SignalRef>,
+        first: bool,
+    ) -> Result<Vec<Event>, SimulationError> {
+        debug!("Step entity {}", unit.name());
+        let started = self.profile().map(|_| Instant::now());
+        let mut executed = 0;
+        let mut events = Vec::new();
+
+        // First collect the probe instructions that react to the changed
+        // signals. This will be the root of the data flow update.
+        trace!("Collecting triggered instructions");
+        let mut dirty = VecDeque::new();
+        let mut dirty_set = HashSet::new();
+        if first {
+            for inst in unit.all_insts() {
+                dirty.push_back(inst);
+                dirty_set.insert(inst);
+            }
+        } else {
+            for sig in instance
+                .signals
+                .iter()
+                .filter(|sig| changed_signals.contains(sig))
+            {
+                for &value in &instance.signal_values[sig] {
+                    trace!("  Triggering {} ({})", self.state.probes[sig][0], value);
+                    for &inst in unit.uses(value) {
+                        match unit[inst].opcode() {
+                            Opcode::Drv | Opcode::Inst | Opcode::Sig | Opcode::Con => continue,
+                            Opcode::Del if unit[inst].args()[0] == value => continue,
+                            _ => (),
+                        }
+                        trace!("    -> {}", inst.dump(&unit));
+                        if !dirty_set.contains(&inst) {
+                            dirty.push_back(inst);
+                            dirty_set.insert(inst);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Work the set of dirty instructions. Grab an instruction from the set
+        // and execute it. If the action causes a value change, add dependent
+        // instructions to the set.
+        while let Some(inst) = dirty.pop_front() {
+            dirty_set.remove(&inst);
+            executed += 1;
+            let mut action = if unit[inst].opcode() == Opcode::Reg {
+                let (state, action) = self.execute_register(inst, unit, instance);
+                instance.registers.insert(inst, state);
+                action
+            } else {
+                self.execute_instruction(inst, unit, &instance.values, &self.state.signals, None)
+                    .map_err(|e| e.in_instance(instance))?
+            };
+            if let Action::Call(vs, evs) = action {
+                events.extend(evs);
+                action = vs.map(Action::Value).unwrap_or(Action::None);
+            }
+            match action {
+                Action::None => (),
+                Action::Value(new) => {
+                    let v = unit.inst_result(inst);
+                    trace!("%{} = {}", v, new);
+                    let changed = instance
+                        .values
+                        .get(&v)
+                        .map(|old| match old {
+                            // Check actual values for a change.
+                            ValueSlot::Const(_) => old != &new,
+                            // Everything else is always considered changed,
+                            // especially pointers.
+                            _ => true,
+                        })
+                        .unwrap_or(true);
+                    instance.set_value(v, new);
+                    if changed {
+                        for &inst in unit.uses(v) {
+                            if !dirty_set.contains(&inst) {
+                                dirty.push_back(inst);
+                                dirty_set.insert(inst);
+                            }
+                        }
+                    }
+                }
+                Action::Store(..) => panic!("cannot store in entity"),
+                Action::Event(e) => events.push(e),
+                Action::Jump(..) => panic!("cannot jump in entity"),
+                Action::Suspend(..) => panic!("cannot suspend entity"),
+                Action::Call(..) => unreachable!(),
+            }
+        }
+
+        // Suspend entity execution until any of the input and output signals
+        // change.
+        instance.state = InstanceState::Wait(None, instance.signals.clone());
+
+        if let (Some(profile), Some(started)) = (self.profile(), started) {
+            profile.record_instance(&instance.path, executed, started.elapsed());
+        }
+        Ok(events)
+    }
+
+    /// Execute a single instruction. Returns an action to be taken in response
+    /// to the instruction.
+    fn execute_instruction(
+        &self,
+        inst: crate::ir::Inst,
+        unit: crate::ir::Unit,
+        values: &HashMap<crate::ir::Value, ValueSlot>,
+        signals: &[Signal],
+        pred: Option<crate::ir::Block>,
+    ) -> Result<Action, ExecError> {
+        InstContext {
+            module: self.state.module,
+            unit,
+            values,
+            signals,
+            time: &self.state.time,
+            pred,
+            block_hits: self.block_hits(),
+            externs: &self.state.externs,
+            xprop: self.state.xprop.as_ref(),
+        }
+        .exec(inst)
+    }
+
+    /// Execute a `reg` instruction. Returns the updated trigger state of the
+    /// register and an action to be taken in response to the instruction.
+    fn execute_register(
+        &self,
+        inst: crate::ir::Inst,
+        unit: crate::ir::Unit,
+        instance: &Instance,
+    ) -> (RegisterState, Action) {
+        InstContext {
+            module: self.state.module,
+            unit,
+            values: &instance.values,
+            signals: &self.state.signals,
+            time: &self.state.time,
+            pred: None,
+            block_hits: self.block_hits(),
+            externs: &self.state.externs,
+            xprop: self.state.xprop.as_ref(),
+        }
+        .exec_reg(inst, instance.registers.get(&inst))
+    }
+
+    /// The block counters to record into, if coverage is collected.
+    fn block_hits(&self) -> Option<&BlockHits> {
+        self.state.coverage.as_ref().map(|c| &c.blocks)
+    }
+
+    /// The profile to record into, if profiling is enabled.
+    fn profile(&self) -> Option<&ProfileState> {
+        self.state.profile.as_ref()
+    }
+}
+
+/// The environment in which instructions are executed.
+pub(crate) struct InstContext<'a> {
+    pub module: &'a crate::ir::Module,
+    pub unit: crate::ir::Unit<'a>,
+    pub values: &'a dyn ValueTable,
+    pub signals: &'a [Signal],
+    pub time: &'a TimeValue,
+    /// The block from which control was transferred to the current block.
+    pub pred: Option<crate::ir::Block>,
+    /// The counters that record the blocks entered by called functions.
+    pub block_hits: Option<&'a BlockHits>,
+    /// The functions that are called but not defined in the module.
+    pub externs: &'a Externs<'a>,
+    /// The X propagation state, if enabled.
+    pub xprop: Option<&'a XpropState>,
+}
+
+impl<'a> InstContext<'a> {
+    /// Execute a single instruction. Returns an action to be taken in response
+    /// to the instruction.
+    pub fn exec(&self, inst: crate::ir::Inst) -> Result<Action, ExecError> {
+        use crate::ir::Opcode;
+        let data = &self.unit[inst];
+        let ty = self.unit.inst_type(inst);
+        match data.opcode() {
+            Opcode::Inst | Opcode::Sig | Opcode::Con => (),
+            _ => trace!("{}", inst.dump(&self.unit)),
+        }
+
+        let action = match data.opcode() {
+            // Constants
+            Opcode::ConstInt => {
+                let v = IntValue::from_signed(
+                    ty.unwrap_int(),
+                    data.get_const_int()
+                        .unwrap()
+                        .value
+                        .to_bigint()
+                        .unwrap()
+                        .clone(),
+                );
+                Action::Value(ValueSlot::Const(v.into()))
+            }
+            Opcode::ConstTime => {
+                let v = data.get_const_time().unwrap().clone();
+                Action::Value(ValueSlot::Const(v.into()))
+            }
+            Opcode::ConstLogic => {
+                let v = data.get_const_logic().unwrap().clone();
+                Action::Value(ValueSlot::Const(v.into()))
+            }
+
+            // Aggregates
+            Opcode::ArrayUniform => {
+                let v = ArrayValue::new_uniform(data.imms()[0], self.resolve_value(data.args()[0]));
+                Action::Value(ValueSlot::Const(v.into()))
+            }
+            Opcode::Array => {
+                let vs = data
+                    .args()
+                    .iter()
+                    .map(|&arg| self.resolve_value(arg))
+                    .collect();
+                let v = ArrayValue::new(vs);
+                Action::Value(ValueSlot::Const(v.into()))
+            }
+            Opcode::Struct => {
+                let vs = data
+                    .args()
+                    .iter()
+                    .map(|&arg| self.resolve_value(arg))
+                    .collect();
+                let v = StructValue::new(vs);
+                Action::Value(ValueSlot::Const(v.into()))
+            }
+
+            // Alias
+            Opcode::Alias => Action::Value(ValueSlot::Const(self.resolve_value(data.args()[0]))),
+
+            // Phi nodes select the value incoming from the predecessor block.
+            Opcode::Phi => {
+                let arg = data
+                    .blocks()
+                    .iter()
+                    .zip(data.args())
+                    .find(|&(&bb, _)| Some(bb) == self.pred)
+                    .map(|(_, &arg)| arg);
+                match arg.and_then(|arg| self.values.get(arg)) {
+                    Some(slot) => Action::Value(slot.clone()),
+                    None => return Err(self.unsupported(inst)),
+                }
+            }
+
+            // Branches
+            Opcode::Br => Action::Jump(data.blocks()[0]),
+            Opcode::BrCond => {
+                let cond = self.resolve_value(data.args()[0]);
+                if self.is_false(inst, &cond) {
+                    Action::Jump(data.blocks()[0])
+                } else {
+                    Action::Jump(data.blocks()[1])
+                }
+            }
+
+            // Waits
+            Opcode::Wait => {
+                let sigs = data
+                    .args()
+                    .iter()
+                    .map(|&s| self.resolve_signal(s))
+                    .collect();
+                Action::Suspend(Some(data.blocks()[0]), InstanceState::Wait(None, sigs))
+            }
+            Opcode::WaitTime => {
+                let delay = self.time_after_delay(&self.resolve_delay(data.args()[0]));
+                let sigs = data.args()[1..]
+                    .iter()
+                    .map(|&s| self.resolve_signal(s))
+                    .collect();
+                Action::Suspend(
+                    Some(data.blocks()[0]),
+                    InstanceState::Wait(Some(delay), sigs),
+                )
+            }
+
+            // Memory
+            Opcode::Var => Action::Value(ValueSlot::Variable(self.resolve_value(data.args()[0]))),
+            Opcode::Ld => {
+                let ptr = self.resolve_variable_pointer(data.args()[0]);
+                Action::Value(ValueSlot::Const(self.read_pointer(&ty, &ptr)))
+            }
+            Opcode::St => {
+                let ptr = self.resolve_variable_pointer(data.args()[0]);
+                let value = self.resolve_value(data.args()[1]);
+                Action::Store(ptr, value)
+            }
+
+            // Signals are simply ignored, as they are handled by the builder.
+            Opcode::Sig => Action::None,
+            Opcode::Prb => {
+                let sig = self.resolve_signal_pointer(data.args()[0]);
+                Action::Value(ValueSlot::Const(self.read_pointer(&ty, &sig)))
+            }
+            Opcode::Drv => {
+                let delay = self.resolve_delay(data.args()[2]);
+                let ev = Event {
+                    time: self.time_after_delay(&delay),
+                    signal: self.resolve_signal_pointer(data.args()[0]),
+                    value: self.resolve_value(data.args()[1]),
+                    mode: data.delay_mode().unwrap(),
+                    drive: self.drive_of(inst),
+                };
+                Action::Event(ev)
+            }
+            Opcode::DrvCond => {
+                if self.is_false(inst, &self.resolve_value(data.args()[3])) {
+                    Action::None
+                } else {
+                    let delay = self.resolve_delay(data.args()[2]);
+                    let ev = Event {
+                        time: self.time_after_delay(&delay),
+                        signal: self.resolve_signal_pointer(data.args()[0]),
+                        value: self.resolve_value(data.args()[1]),
+                        mode: data.delay_mode().unwrap(),
+                        drive: self.drive_of(inst),
+                    };
+                    Action::Event(ev)
+                }
+            }
+            Opcode::Del => {
+                let delay = self.resolve_delay(data.args()[2]);
+                let ty = self.unit.value_type(data.args()[1]).unwrap_signal().clone();
+                let source = self.resolve_signal_pointer(data.args()[1]);
+                let ev = Event {
+                    time: self.time_after_delay(&delay),
+                    signal: self.resolve_signal_pointer(data.args()[0]),
+                    value: self.read_pointer(&ty, &source),
+                    mode: DelayMode::Transport,
+                    drive: self.drive_of(inst),
+                };
+                Action::Event(ev)
+            }
+
+            // Connections are handled by the builder.
+            Opcode::Con => Action::None,
+
+            // Unary operators
+            Opcode::Not | Opcode::Neg => {
+                if ty.is_int() {
+                    let arg = self.resolve_value(data.args()[0]);
+                    let v = Value::int_unary_op(data.opcode(), &arg);
+                    Action::Value(ValueSlot::Const(v))
+                } else if ty.is_logic() {
+                    let arg = self.resolve_value(data.args()[0]);
+                    match LogicValue::try_unary_op(data.opcode(), arg.unwrap_logic()) {
+                        Some(v) => Action::Value(ValueSlot::Const(v.into())),
+                        None => return Err(self.unsupported(inst)),
+                    }
+                } else {
+                    return Err(self.unsupported(inst));
+                }
+            }
+
+            // Binary operators
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::Smul
+            | Opcode::Sdiv
+            | Opcode::Smod
+            | Opcode::Srem
+            | Opcode::Umul
+            | Opcode::Udiv
+            | Opcode::Umod
+            | Opcode::Urem => {
+                if ty.is_int() {
+                    let lhs = self.resolve_value(data.args()[0]);
+                    let rhs = self.resolve_value(data.args()[1]);
+                    match Value::int_binary_op(data.opcode(), &lhs, &rhs) {
+                        Some(v) => Action::Value(ValueSlot::Const(v)),
+                        None => return self.division_by_zero(inst, &ty),
+                    }
+                } else if ty.is_logic() {
+                    let lhs = self.resolve_value(data.args()[0]);
+                    let rhs = self.resolve_value(data.args()[1]);
+                    let (lhs, rhs) = (lhs.unwrap_logic(), rhs.unwrap_logic());
+                    match LogicValue::try_binary_op(data.opcode(), lhs, rhs) {
+                        Some(v) => Action::Value(ValueSlot::Const(v.into())),
+                        None => return Err(self.unsupported(inst)),
+                    }
+                } else {
+                    return Err(self.unsupported(inst));
+                }
+            }
+
+            // Comparisons
+            Opcode::Eq
+            | Opcode::Neq
+            | Opcode::Slt
+            | Opcode::Sgt
+            | Opcode::Sle
+            | Opcode::Sge
+            | Opcode::Ult
+            | Opcode::Ugt
+            | Opcode::Ule
+            | Opcode::Uge => {
+                let lhs = self.resolve_value(data.args()[0]);
+                let rhs = self.resolve_value(data.args()[1]);
+                if self.unit.value_type(data.args()[0]).is_int() {
+                    let v = Value::int_compare_op(data.opcode(), &lhs, &rhs);
+                    return Ok(Action::Value(ValueSlot::Const(v)));
+                }
+                match Value::compare_op(data.opcode(), &lhs, &rhs) {
+                    Some(v) => Action::Value(ValueSlot::Const(v.into())),
+                    None => return Err(self.unsupported(inst)),
+                }
+            }
+
+            // Shifts
+            Opcode::Shl | Opcode::Shr => {
+                let (base, hidden) = if ty.is_pointer() {
+                    (
+                        self.resolve_variable_pointer(data.args()[0]),
+                        self.resolve_variable_pointer(data.args()[1]),
+                    )
+                } else if ty.is_signal() {
+                    (
+                        self.resolve_signal_pointer(data.args()[0]),
+                        self.resolve_signal_pointer(data.args()[1]),
+                    )
+                } else {
+                    (
+                        self.resolve_value_pointer(data.args()[0]),
+                        self.resolve_value_pointer(data.args()[1]),
+                    )
+                };
+                let amount = self.resolve_value(data.args()[2]);
+                if amount.is_unknown() {
+                    if !ty.is_pointer() && !ty.is_signal() {
+                        return Ok(Action::Value(ValueSlot::Const(Value::unknown(&ty))));
+                    }
+                    if let Some(xprop) = self.xprop {
+                        xprop.warn(self.unit, inst, "unknown shift amount", self.time);
+                    }
+                }
+                let ptr = self.exec_shift(data.opcode(), &base, &hidden, &amount);
+                if ty.is_pointer() {
+                    Action::Value(ValueSlot::VariablePointer(ptr))
+                } else if ty.is_signal() {
+                    Action::Value(ValueSlot::SignalPointer(ptr))
+                } else {
+                    Action::Value(ValueSlot::Const(self.read_pointer(&ty, &ptr)))
+                }
+            }
+
+            // Insert and extract fields and slices
+            Opcode::InsField | Opcode::InsSlice => {
+                let target = self.resolve_value_pointer(data.args()[0]);
+                let target_ty = self.unit.value_type(data.args()[0]);
+                let value = self.resolve_value(data.args()[1]);
+                let ptr = self.exec_insext(data.opcode(), &target_ty, &target, data.imms());
+                let mut results = [self.resolve_value(data.args()[0])];
+                write_pointer(&ptr, &mut results, &value);
+                let [result] = results;
+                Action::Value(ValueSlot::Const(result))
+            }
+            Opcode::ExtField | Opcode::ExtSlice => {
+                let target = if ty.is_pointer() {
+                    self.resolve_variable_pointer(data.args()[0])
+                } else if ty.is_signal() {
+                    self.resolve_signal_pointer(data.args()[0])
+                } else {
+                    self.resolve_value_pointer(data.args()[0])
+                };
+                let target_ty = self.unit.value_type(data.args()[0]);
+                let ptr = self.exec_insext(data.opcode(), &target_ty, &target, data.imms());
+                if ty.is_pointer() {
+                    Action::Value(ValueSlot::VariablePointer(ptr))
+                } else if ty.is_signal() {
+                    Action::Value(ValueSlot::SignalPointer(ptr))
+                } else {
+                    Action::Value(ValueSlot::Const(self.read_pointer(&ty, &ptr)))
+                }
+            }
+
+            // Multiplexing
+            Opcode::Mux => {
+                let ways = self.resolve_value(data.args()[0]);
+                let index = self.resolve_value(data.args()[1]);
+                match (ways, index.to_known_int()) {
+                    (Value::Array(v), Some(index)) => {
+                        let index = std::cmp::min(v.len() - 1, index.to_usize());
+                        Action::Value(ValueSlot::Const(v.extract_field(index)))
+                    }
+                    // An unknown index selects an unknown value, unless all
+                    // ways agree.
+                    (Value::Array(v), None) => {
+                        let first = v.extract_field(0);
+                        let v = match v.iter().all(|way| way == first) {
+                            true => first,
+                            false => Value::unknown(&ty),
+                        };
+                        Action::Value(ValueSlot::Const(v))
+                    }
+                    _ => return Err(self.unsupported(inst)),
+                }
+            }
+
+            // Instantiations are handled by the builder.
+            Opcode::Inst => Action::None,
+
+            // Function calls
+            Opcode::Call => self.exec_call(inst)?,
+
+            // Halt trivially suspends the process indefinitely.
+            Opcode::Halt if self.unit.is_entity() => Action::None,
+            Opcode::Halt => Action::Suspend(None, InstanceState::Done),
+
+            // Registers are handled by `exec_reg`, and returns by `exec_call`;
+            // reaching them here means they appear in a unit that cannot
+            // execute them.
+            Opcode::Reg | Opcode::Ret | Opcode::RetValue => return Err(self.unsupported(inst)),
+        };
+        Ok(action)
+    }
+
+    /// Determine the drive instruction that distinguishes the driver of an
+    /// event from the other drivers of its instance.
+    ///
+    /// Every drive of an entity is a separate driver, whereas a process drives
+    /// each signal with a single driver.
+    pub fn drive_of(&self, inst: crate::ir::Inst) -> Option<crate::ir::Inst> {
+        self.unit.is_entity().then_some(inst)
+    }
+
+    /// Check whether the condition of a branch or drive is false.
+    ///
+    /// An unknown condition counts as false, and is recorded as a warning if X
+    /// propagation is enabled.
+    pub fn is_false(&self, inst: crate::ir::Inst, cond: &Value) -> bool {
+        if !cond.is_unknown() {
+            return cond.is_zero();
+        }
+        if let Some(xprop) = self.xprop {
+            xprop.warn(self.unit, inst, "unknown condition", self.time);
+        }
+        true
+    }
+
+    /// Handle a division or remainder by zero.
+    ///
+    /// The result is unknown and recorded as a warning if X propagation is
+    /// enabled. Otherwise the division is an error.
+    pub fn division_by_zero(
+        &self,
+        inst: crate::ir::Inst,
+        ty: &crate::Type,
+    ) -> Result<Action, ExecError> {
+        match self.xprop {
+            Some(xprop) => {
+                xprop.warn(self.unit, inst, "division by zero", self.time);
+                Ok(Action::Value(ValueSlot::Const(Value::unknown(ty))))
+            }
+            None => Err(ExecError::DivisionByZero {
+                unit: self.unit.name().to_string(),
+                inst: inst.dump(&self.unit).to_string(),
+            }),
+        }
+    }
+
+    /// Report an instruction as not supported by the simulator.
+    pub fn unsupported(&self, inst: crate::ir::Inst) -> ExecError {
+        ExecError::Unsupported {
+            unit: self.unit.name().to_string(),
+            inst: inst.dump(&self.unit).to_string(),
+        }
+    }
+
+    /// Execute a function call.
+    ///
+    /// Runs the called function and any functions it calls in turn to
+    /// completion. Each call pushes a new frame with its own value table onto
+    /// a call stack, such that recursive functions do not consume native
+    /// stack space.
+    pub fn exec_call(&self, inst: crate::ir::Inst) -> Result<Action, ExecError> {
+        if let Some(action) = self.exec_extern(self.unit, inst, self.values)? {
+            return Ok(action);
+        }
+        let mut events = vec![];
+        let mut stack = vec![self.call_frame(self.unit, inst, self.values, None)];
+        let record = self.block_hits.is_some();
+        let mut entered = vec![];
+        if record {
+            entered.push(stack[0].location());
+        }
+        loop {
+            let frame = stack.last_mut().unwrap();
+            let inst = match frame.insts.get(frame.next) {
+                Some(&inst) => inst,
+                None => panic!("function {} starved of instructions", frame.unit.name()),
+            };
+            frame.next += 1;
+            let unit = frame.unit;
+            let data = &unit[inst];
+            if data.opcode() != Opcode::Phi {
+                for (v, vs) in frame.phis.drain(..) {
+                    frame.values.insert(v, vs);
+                }
+            }
+            match data.opcode() {
+                Opcode::Call => {
+                    let result = if unit.has_result(inst) {
+                        Some(unit.inst_result(inst))
+                    } else {
+                        None
+                    };
+                    if let Some(action) = self.exec_extern(unit, inst, &frame.values)? {
+                        if let (Some(result), Action::Call(Some(value), _)) = (result, action) {
+                            frame.values.insert(result, value);
+                        }
+                        continue;
+                    }
+                    let callee = self.call_frame(unit, inst, &frame.values, result);
+                    if record {
+                        entered.push(callee.location());
+                    }
+                    stack.push(callee);
+                }
+                Opcode::Ret | Opcode::RetValue => {
+                    let value = if data.opcode() == Opcode::RetValue {
+                        Some(ValueSlot::Const(
+                            InstContext {
+                                module: self.module,
+                                unit,
+                                values: &frame.values,
+                                signals: self.signals,
+                                time: self.time,
+                                pred: frame.pred,
+                                block_hits: self.block_hits,
+                                externs: self.externs,
+                                xprop: self.xprop,
+                            }
+                            .resolve_value(data.args()[0]),
+                        ))
+                    } else {
+                        None
+                    };
+                    let frame = stack.pop().unwrap();
+                    trace!("Return from {}", frame.unit.name());
+                    match stack.last_mut() {
+                        Some(caller) => {
+                            if let (Some(result), Some(value)) = (frame.result, value) {
+                                caller.values.insert(result, value);
+                            }
+                        }
+                        None => {
+                            if let Some(hits) = self.block_hits {
+                                hits.record_functions(&entered);
+                            }
+                            return Ok(Action::Call(value, events));
+                        }
+                    }
+                }
+                _ => {
+                    let action = InstContext {
+                        module: self.module,
+                        unit,
+                        values: &frame.values,
+                        signals: self.signals,
+                        time: self.time,
+                        pred: frame.pred,
+                        block_hits: self.block_hits,
+                        externs: self.externs,
+                        xprop: self.xprop,
+                    }
+                    .exec(inst)?;
+                    match action {
+                        Action::None => (),
+                        Action::Value(vs) if data.opcode() == Opcode::Phi => {
+                            frame.phis.push((unit.inst_result(inst), vs));
+                        }
+                        Action::Value(vs) => {
+                            frame.values.insert(unit.inst_result(inst), vs);
+                        }
+                        Action::Store(ptr, value) => store_pointer(&mut frame.values, &ptr, &value),
+                        Action::Event(e) => events.push(e),
+                        Action::Jump(block) => {
+                            frame.jump(block);
+                            if record {
+                                entered.push(frame.location());
+                            }
+                        }
+                        Action::Suspend(..) => panic!("cannot suspend function"),
+                        Action::Call(..) => unreachable!(),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Execute a call to a function that is not defined in the module, but
+    /// registered with the simulation.
+    ///
+    /// Returns `None` if the called function is defined in the module. The
+    /// arguments are resolved in the value table of the caller, with signals
+    /// being passed by their current value.
+    fn exec_extern(
+        &self,
+        unit: crate::ir::Unit,
+        inst: crate::ir::Inst,
+        values: &dyn ValueTable,
+    ) -> Result<Option<Action>, ExecError> {
+        let ext_unit = unit[inst].get_ext_unit().unwrap();
+        if let Some(crate::ir::LinkedUnit::Def(_)) =
+            self.module.lookup_ext_unit(ext_unit, unit.id())
+        {
+            return Ok(None);
+        }
+        let function = unit[ext_unit].name.to_string();
+        let args = unit[inst]
+            .input_args()
+            .iter()
+            .map(|&arg| match values.get(arg) {
+                Some(ValueSlot::Const(v)) => Ok(v.clone()),
+                Some(&ValueSlot::Signal(sig)) => Ok(self.signals[sig.as_usize()].value().clone()),
+                _ => Err(ExecError::Unsupported {
+                    unit: unit.name().to_string(),
+                    inst: inst.dump(&unit).to_string(),
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+        trace!("Call extern {}", function);
+        let value = match self.externs.call(&function, args, self.time) {
+            Ok(value) => value,
+            Err(message) => return Err(ExecError::Extern { function, message }),
+        };
+        let sig = &unit[ext_unit].sig;
+        let value = match (sig.has_return_type() && !sig.return_type().is_void(), value) {
+            (true, None) => {
+                return Err(ExecError::Extern {
+                    function,
+                    message: "returned no value".to_string(),
+                })
+            }
+            (true, value) => value.map(ValueSlot::Const),
+            (false, _) => None,
+        };
+        Ok(Some(Action::Call(value, vec![])))
+    }
+
+    /// Create the call frame for a function called by a `call` instruction.
+    ///
+    /// Binds the call's arguments to the inputs of the callee's signature.
+    /// The arguments are resolved in the value table of the caller.
+    fn call_frame(
+        &self,
+        unit: crate::ir::Unit<'a>,
+        inst: crate::ir::Inst,
+        values: &dyn ValueTable,
+        result: Option<crate::ir::Value>,
+    ) -> Frame<'a> {
+        let ext_unit = unit[inst].get_ext_unit().unwrap();
+        let name = &unit[ext_unit].name;
+        let callee = match self.module.lookup_ext_unit(ext_unit, unit.id()) {
+            Some(crate::ir::LinkedUnit::Def(u)) => self.module.unit(u),
+            _ => panic!("external unit {} not linked", name),
+        };
+        if !callee.is_function() {
+            panic!("cannot call {}, which is not a function", name);
+        }
+        trace!("Call {}", callee.name());
+        let values = callee
+            .sig()
+            .inputs()
+            .zip(unit[inst].input_args())
+            .map(|(arg, &value)| (callee.arg_value(arg), values.get(value).unwrap().clone()))
+            .collect();
+        let mut frame = Frame {
+            unit: callee,
+            values,
+            insts: vec![],
+            next: 0,
+            block: None,
+            pred: None,
+            phis: vec![],
+            result,
+        };
+        frame.jump(
+            callee
+                .first_block()
+                .unwrap_or_else(|| panic!("function {} has no blocks", name)),
+        );
+        frame
+    }
+
+    /// Execute a `reg` instruction, given the trigger state it observed during
+    /// its last execution.
+    ///
+    /// The triggers are checked from left to right, and the first one that
+    /// fires determines the value stored. If no trigger fires, the register
+    /// retains its current value.
+    pub fn exec_reg(
+        &self,
+        inst: crate::ir::Inst,
+        state: Option<&RegisterState>,
+    ) -> (RegisterState, Action) {
+        use crate::ir::RegMode;
+        let data = &self.unit[inst];
+        trace!("{}", inst.dump(&self.unit));
+
+        // Determine the current value of each trigger, and the one observed in
+        // the previous step. Triggers may either be values or signals, in
+        // which case they are implicitly probed.
+        let curr: Vec<_> = data
+            .trigger_args()
+            .map(|arg| self.resolve_value_or_signal(arg))
+            .collect();
+        let prev = match state {
+            Some(state) if state.time == *self.time => state.prev.clone(),
+            Some(state) => state.curr.clone(),
+            None => curr.clone(),
+        };
+
+        // Find the left-most trigger that fires.
+        let fired =
+            data.triggers()
+                .zip(curr.iter().zip(prev.iter()))
+                .find(|(trigger, (curr, prev))| {
+                    let fires = match trigger.mode {
+                        RegMode::Low => curr.is_zero(),
+                        RegMode::High => curr.is_one(),
+                        RegMode::Rise => prev.is_zero() && curr.is_one(),
+                        RegMode::Fall => prev.is_one() && curr.is_zero(),
+                        RegMode::Both => prev != curr,
+                    };
+                    let gated = trigger
+                        .gate
+                        .map(|gate| self.resolve_value(gate).is_zero())
+                        .unwrap_or(false);
+                    fires && !gated
+                });
+
+        // Drive the value associated with the trigger onto the target signal.
+        let action = match fired {
+            Some((trigger, _)) => Action::Event(Event {
+                time: self.time_after_delta(),
+                signal: self.resolve_signal_pointer(data.args()[0]),
+                value: self.resolve_value_or_signal(trigger.data),
+                mode: DelayMode::Transport,
+                drive: self.drive_of(inst),
+            }),
+            None => Action::None,
+        };
+        let state = RegisterState {
+            time: self.time.clone(),
+            prev,
+            curr,
+        };
+        (state, action)
+    }
+
+    /// Resolve a value to a constant, probing it if it is a signal.
+    fn resolve_value_or_signal(&self, id: crate::ir::Value) -> Value {
+        match self.values.get(id) {
+            Some(ValueSlot::Signal(_)) | Some(ValueSlot::SignalPointer(_)) => {
+                let ty = self.unit.value_type(id).unwrap_signal().clone();
+                self.read_pointer(&ty, &self.resolve_signal_pointer(id))
+            }
+            _ => self.resolve_value(id),
+        }
+    }
+
+    /// Resolve a value to a constant.
+    pub fn resolve_value(&self, id: crate::ir::Value) -> Value {
+        match self.values.get(id) {
+            Some(ValueSlot::Const(k)) => k.clone(),
+            x => panic!(
+                "expected value {:?} to resolve to a constant, got {:?}",
+                id, x
+            ),
+        }
+    }
+
+    // Resolve a value ref to a constant time value.
+    pub fn resolve_delay(&self, id: crate::ir::Value) -> TimeValue {
+        let v = self.resolve_value(id);
+        match v.get_time() {
+            Some(x) => x.clone(),
+            None => panic!(
+                "expected value {:?} to resolve to a time constant, got {:?}",
+                id, v
+            ),
+        }
+    }
+
+    // Resolve a value to a signal.
+    pub fn resolve_signal(&self, id: crate::ir::Value) -> SignalRef {
+        match self.values.get(id) {
+            Some(ValueSlot::Signal(r)) => *r,
+            x => panic!(
+                "expected value {:?} to resolve to a signal, got {:?}",
+                id, x
+            ),
+        }
+    }
+
+    // Resolve a value to a variable pointer.
+    fn resolve_variable_pointer(&self, id: crate::ir::Value) -> ValuePointer {
+        match self.values.get(id) {
+            Some(ValueSlot::Variable(_)) => ValuePointer(vec![ValueSlice {
+                target: ValueTarget::Variable(id),
+                select: vec![],
+                width: self.pointer_width(id),
+            }]),
+            Some(ValueSlot::VariablePointer(ref ptr)) => ptr.clone(),
+            x => panic!(
+                "expected value {:?} to resolve to a variable pointer, got {:?}",
+                id, x
+            ),
+        }
+    }
+
+    // Resolve a value to a signal pointer.
+    fn resolve_signal_pointer(&self, id: crate::ir::Value) -> ValuePointer {
+        match self.values.get(id) {
+            Some(ValueSlot::Signal(sig)) => ValuePointer(vec![ValueSlice {
+                target: ValueTarget::Signal(*sig),
+                select: vec![],
+                width: self.pointer_width(id),
+            }]),
+            Some(ValueSlot::SignalPointer(ref ptr)) => ptr.clone(),
+            x => panic!(
+                "expected value {:?} to resolve to a signal pointer, got {:?}",
+                id, x
+            ),
+        }
+    }
+
+    // Resolve a value to a value pointer.
+    fn resolve_value_pointer(&self, id: crate::ir::Value) -> ValuePointer {
+        ValuePointer(vec![ValueSlice {
+            target: ValueTarget::Value(id),
+            select: vec![],
+            width: self.pointer_width(id),
+        }])
+    }
+
+    /// Determine the width of a pointer or value, for the purpose of pointer
+    /// operations.
+    ///
+    /// Returns the length of arrays or integers, or 0 for structs.
+    fn pointer_width(&self, id: crate::ir::Value) -> usize {
+        let ty = self.unit.value_type(id);
+        let ty = match &*ty {
+            crate::PointerType(ty) => ty,
+            crate::SignalType(ty) => ty,
+            _ => &ty,
+        };
+        match **ty {
+            crate::IntType(w) | crate::LogicType(w) => w,
+            crate::ArrayType(w, _) => w,
+            crate::StructType(..) => 0,
+            _ => panic!("{} has no pointer width", ty),
+        }
+    }
+
+    /// Calculate the time at which an event occurs, given an optional delay. If
+    /// the delay is omitted, the next delta cycle is returned.
+    pub fn time_after_delay(&self, delay: &TimeValue) -> TimeValue {
+        use num::Zero;
+        let mut time = self.time.time().clone();
+        let mut delta = self.time.delta();
+        let mut epsilon = self.time.epsilon();
+        if !delay.time().is_zero() {
+            time += delay.time();
+            delta = 0;
+            epsilon = 0;
+        }
+        if delay.delta() != 0 {
+            delta += delay.delta();
+            epsilon = 0;
+        }
+        epsilon += delay.epsilon();
+        TimeValue::new(time, delta, epsilon)
+    }
+
+    /// Calculate the absolute time of the next delta step.
+    fn time_after_delta(&self) -> TimeValue {
+        TimeValue::new(self.time.time().clone(), self.time.delta() + 1, 0)
+    }
+
+    /// Read the target value of a pointer.
+    pub fn read_pointer(&self, ty: &crate::Type, ptr: &ValuePointer) -> Value {
+        // A single slice covers the entire value.
+        if let [slice] = ptr.0.as_slice() {
+            return self.read_pointer_slice(slice);
+        }
+
+        // Map each slice to its corresponding subresult.
+        let mut results = ptr.0.iter().map(|s| (self.read_pointer_slice(s), s.width));
+
+        // Otherwise concatenate the results.
+        match **ty {
+            crate::IntType(w) => {
+                let results: Vec<_> = results.collect();
+                if results.iter().any(|(result, _)| result.get_int().is_none()) {
+                    let mut value = LogicValue::zero(w);
+                    let mut offset = 0;
+                    for (result, width) in results {
+                        value.insert_slice(offset, width, &result.to_logic());
+                        offset += width;
+                    }
+                    assert_eq!(offset, w);
+                    return Value::int_or_unknown(value);
+                }
+                let mut value = IntValue::from_usize(w, 0);
+                let mut offset = 0;
+                for (result, width) in results {
+                    value.insert_slice(offset, width, result.unwrap_int());
+                    offset += width;
+                }
+                assert_eq!(offset, w);
+                value.into()
+            }
+            crate::LogicType(w) => {
+                let mut value = LogicValue::zero(w);
+                let mut offset = 0;
+                for (result, width) in results {
+                    value.insert_slice(offset, width, result.unwrap_logic());
+                    offset += width;
+                }
+                assert_eq!(offset, w);
+                value.into()
+            }
+            crate::ArrayType(w, _) => {
+                let mut values = vec![];
+                for (result, _) in results {
+                    values.extend(result.unwrap_array().iter());
+                }
+                assert_eq!(values.len(), w);
+                ArrayValue::new(values).into()
+            }
+            _ if ptr.0.len() == 1 => results.next().unwrap().0,
+            _ => panic!("multi-slice concat on {}", ty),
+        }
+    }
+
+    /// Read the target value of a pointer slice.
+    pub fn read_pointer_slice(&self, ptr: &ValueSlice) -> Value {
+        let mut value = self.read_pointer_target(ptr.target);
+        for &select in &ptr.select {
+            match select {
+                ValueSelect::Field(idx) => match value {
+                    Value::Array(v) => value = v.extract_field(idx),
+                    Value::Struct(v) => value = v.extract_field(idx),
+                    _ => panic!("access field {} in {} ({:?})", idx, value, ptr.target),
+                },
+                ValueSelect::Slice(off, len) => match value {
+                    Value::Int(v) => value = v.extract_slice(off, len).into(),
+                    Value::Logic(v) => value = v.extract_slice(off, len).into(),
+                    Value::Array(v) => value = v.extract_slice(off, len).into(),
+                    _ => panic!(
+                        "access slice {},{} in {} ({:?})",
+                        off, len, value, ptr.target
+                    ),
+                },
+            }
+        }
+        value
+    }
+
+    /// Read the value of a pointer target.
+    pub fn read_pointer_target(&self, target: ValueTarget) -> Value {
+        match target {
+            ValueTarget::Value(v) => self.resolve_value(v),
+            ValueTarget::Variable(v) => match self.values.get(v) {
+                Some(ValueSlot::Variable(k)) => k.clone(),
+                _ => panic!(
+                    "pointer target {:?} did not resolve to a variable value",
+                    target
+                ),
+            },
+            ValueTarget::Signal(v) => self.signals[v.as_usize()].value().clone(),
+        }
+    }
+
+    /// Execute a shift operation.
+    ///
+    /// This merely modifies pointers. Actual computation of the shift result
+    /// is performed in `read_pointer`.
+    pub fn exec_shift(
+        &self,
+        op: Opcode,
+        base: &ValuePointer,
+        hidden: &ValuePointer,
+        amount: &Value,
+    ) -> ValuePointer {
+        // Map the shift amount to a usize and clamp to the maximum shift.
+        let amount = amount.to_known_int().map_or(0, |amount| amount.to_usize());
+        let amount = std::cmp::min(hidden.width(), amount);
+
+        // Compute the length of the selected slices from the base and hidden
+        // pointers.
+        let base_len = base.width() as isize - amount as isize;
+        let hidden_len = amount as isize;
+
+        // Compute the offsets of the selected slice from the base and hidden
+        // pointers, and determine whether in the output the base or hidden
+        // values come first (i.e. shift the hidden in at the bottom or top).
+        let (base_off, hidden_off, base_first) = match op {
+            Opcode::Shl => (0, hidden.width() as isize - amount as isize, false),
+            Opcode::Shr => (amount as isize, 0, true),
+            _ => panic!("{} is not a shift op", op),
+        };
+        let base_slice = (base_off, base_len);
+        let hidden_slice = (hidden_off, hidden_len);
+
+        // Assemble an iterator over the base and hidden pointers, plus their
+        // slicing information, in the right order.
+        let order = match base_first {
+            true => vec![base, hidden]
+                .into_iter()
+                .zip(vec![base_slice, hidden_slice].into_iter()),
+            false => vec![hidden, base]
+                .into_iter()
+                .zip(vec![hidden_slice, base_slice].into_iter()),
+        };
+
+        // Create an updated set of pointer slices by fusing the base and hidden
+        // pointers according to the slicing and ordering determined above.
+        let slices = order
+            .flat_map(|(ptr, (off, len))| {
+                ptr.offset_slices().flat_map(move |(slice_offset, slice)| {
+                    use std::cmp::{max, min};
+                    let clamp = |x| max(0isize, min(slice.width as isize, x)) as usize;
+
+                    // Translate the offset and length determined for the shift
+                    // to the slice-local equivalent.
+                    let actual_off = clamp(off - slice_offset as isize);
+                    let actual_end = clamp(off + len - slice_offset as isize);
+                    let actual_len = actual_end - actual_off;
+
+                    // Extract exactly the slice determined above.
+                    if actual_off == 0 && actual_len == slice.width {
+                        Some(slice.clone())
+                    } else if actual_len == 0 {
+                        None
+                    } else {
+                        let mut s = slice.clone();
+                        s.width = actual_len;
+                        s.select.push(ValueSelect::Slice(actual_off, actual_len));
+                        Some(s)
+                    }
+                })
+            })
+            .collect();
+
+        ValuePointer(slices)
+    }
+
+    /// Execute an insert or extract operation.
+    ///
+    /// This merely modifies pointers. Actual computation of the insertion or
+    /// extraction result is performed in `read_pointer`.
+    pub fn exec_insext(
+        &self,
+        op: Opcode,
+        target_ty: &crate::Type,
+        target: &ValuePointer,
+        imms: &[usize],
+    ) -> ValuePointer {
+        match op {
+            // Field accesses simply go through the slices until they find the
+            // one which is being targeted, at which point that specific slice
+            // is extracted and a `Field(..)` access is added.
+            Opcode::InsField | Opcode::ExtField => {
+                let field = imms[0];
+                for (offset, slice) in target.offset_slices() {
+                    // Match this slice if it's either a struct slice, or the
+                    // accessed index is in range.
+                    if slice.width == 0 || (field >= offset && field < offset + slice.width) {
+                        let mut s = slice.clone();
+                        let ty = match **target_ty {
+                            crate::PointerType(ref ty) => ty,
+                            crate::SignalType(ref ty) => ty,
+                            _ => target_ty,
+                        };
+                        let field_ty = match **ty {
+                            crate::ArrayType(_, ref ty) => ty,
+                            crate::StructType(ref f) => &f[field],
+                            _ => panic!("cannot field access into {}", ty),
+                        };
+                        s.width = match **field_ty {
+                            crate::IntType(w) | crate::LogicType(w) => w,
+                            crate::ArrayType(w, _) => w,
+                            _ => 0,
+                        };
+                        s.select.push(ValueSelect::Field(field - offset));
+                        return ValuePointer(vec![s]);
+                    }
+                }
+                panic!("field {} out of bounds in {:?}", field, target);
+            }
+
+            // Slice accesses go through the slices and filter out all the ones
+            // that do not fall within the range, and adjust the ranges of the
+            // ones that do.
+            Opcode::InsSlice | Opcode::ExtSlice => {
+                let offset = imms[0];
+                let length = imms[1];
+                let mut slices = vec![];
+                for (slice_offset, slice) in target.offset_slices() {
+                    use std::cmp::{max, min};
+                    let clamp = |x| max(0isize, min(slice.width as isize, x)) as usize;
+
+                    // Shift the global slice bounds into this slice's domain.
+                    let actual_off = clamp(offset as isize - slice_offset as isize);
+                    let actual_end =
+                        clamp(offset as isize + length as isize - slice_offset as isize);
+                    let actual_len = actual_end - actual_off;
+
+                    // Extract exactly the slice determined above.
+                    if actual_off == 0 && actual_len == slice.width {
+                        slices.push(slice.clone());
+                    } else if actual_len == 0 {
+                        continue;
+                    } else {
+                        let mut s = slice.clone();
+                        s.width = actual_len;
+                        s.select.push(ValueSelect::Slice(actual_off, actual_len));
+                        slices.push(s);
+                    }
+                }
+                ValuePointer(slices)
+            }
+
+            _ => panic!("{} is not an insert/extract op", op),
+        }
+    }
+}
+
+/// The delta cycles performed in the current time step.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct DeltaCycles {
+    /// The maximum number of delta cycles per time step.
+    #[serde(skip)]
+    pub limit: Option<usize>,
+    /// The number of delta cycles performed in the current time step.
+    count: usize,
+    /// The signals that changed and the instances that were executed in the
+    /// last delta cycles before the limit.
+    recent: VecDeque<(Vec<SignalRef>, Vec<InstanceRef>)>,
+}
+
+impl DeltaCycles {
+    /// The default limit, which is only reached by designs that do not settle.
+    pub const DEFAULT_LIMIT: usize = 10000;
+    /// The number of delta cycles reported when the limit is reached.
+    const HISTORY: usize = 8;
+
+    /// Start counting delta cycles with the default limit.
+    pub fn new() -> Self {
+        DeltaCycles {
+            limit: Some(Self::DEFAULT_LIMIT),
+            count: 0,
+            recent: VecDeque::new(),
+        }
+    }
+}
+
+/// An error that occurred while executing an instruction.
+#[derive(Debug)]
+pub(crate) enum ExecError {
+    /// An instruction that the simulator does not support.
+    Unsupported {
+        /// The unit containing the instruction.
+        unit: String,
+        /// The instruction.
+        inst: String,
+    },
+    /// A division or remainder by zero.
+    DivisionByZero {
+        /// The unit containing the instruction.
+        unit: String,
+        /// The instruction.
+        inst: String,
+    },
+    /// A call to an extern function failed.
+    Extern {
+        /// The name of the function.
+        function: String,
+        /// A description of the failure.
+        message: String,
+    },
+}
+
+impl ExecError {
+    /// Attribute the error to the instance that executed the instruction.
+    pub fn in_instance(self, instance: &Instance) -> SimulationError {
+        match self {
+            ExecError::Unsupported { unit, inst } => SimulationError::Unsupported {
+                instance: instance.path.clone(),
+                unit,
+                inst,
+            },
+            ExecError::DivisionByZero { unit, inst } => SimulationError::DivisionByZero {
+                instance: instance.path.clone(),
+                unit,
+                inst,
+            },
+            ExecError::Extern { function, message } => SimulationError::Extern {
+                instance: instance.path.clone(),
+                function,
+                message,
+            },
+        }
+    }
+}
+
+/// An action to be taken as the result of an instruction's execution.
+#[derive(Debug)]
+pub(crate) enum Action {
+    /// No action.
+    None,
+    /// Change the instruction's entry in the value table. Used by instructions
+    /// that yield a value to change that value.
+    Value(ValueSlot),
+    /// Change another value's entry in the value table. Used by instructions
+    /// to simulate writing to memory.
+    Store(ValuePointer, Value),
+    /// Add an event to the event queue.
+    Event(Event),
+    /// Transfer control to a different block, executing that block's
+    /// instructions.
+    Jump(crate::ir::Block),
+    /// Suspend execution of the current instance and change the instance's
+    /// state.
+    Suspend(Option<crate::ir::Block>, InstanceState),
+    /// A function call has completed. Change the instruction's entry in the
+    /// value table if the function returned a value, and add the events
+    /// generated by the function to the event queue.
+    Call(Option<ValueSlot>, Vec<Event>),
+}
+
+/// A function activation on the call stack.
+struct Frame<'a> {
+    /// The function being executed.
+    unit: crate::ir::Unit<'a>,
+    /// The function's value table.
+    values: HashMap<crate::ir::Value, ValueSlot>,
+    /// The instructions in the block currently being executed.
+    insts: Vec<crate::ir::Inst>,
+    /// The index of the next instruction to be executed in `insts`.
+    next: usize,
+    /// The block currently being executed.
+    block: Option<crate::ir::Block>,
+    /// The block from which control was transferred to the current block.
+    pred: Option<crate::ir::Block>,
+    /// The results of the `phi` instructions executed in the current block,
+    /// which are only stored once the first other instruction is reached.
+    phis: Vec<(crate::ir::Value, ValueSlot)>,
+    /// The value in the caller's table which receives the return value.
+    result: Option<crate::ir::Value>,
+}
+
+impl<'a> Frame<'a> {
+    /// Transfer control to a different block.
+    fn jump(&mut self, block: crate::ir::Block) {
+        self.insts = self.unit.insts(block).collect();
+        self.next = 0;
+        self.pred = self.block.replace(block);
+    }
+
+    /// The function and block currently being executed.
+    fn location(&self) -> (crate::ir::UnitId, crate::ir::Block) {
+        (self.unit.id(), self.block.unwrap())
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Action::None => write!(f, "<no action>"),
+            Action::Value(ref v) => write!(f, "= {:?}", v),
+            Action::Store(ref ptr, ref v) => write!(f, "*{:?} = {:?}", ptr, v),
+            Action::Event(ref ev) => write!(f, "@{} {:?} <= {:?}", ev.time, ev.signal, ev.value),
+            Action::Jump(..) | Action::Suspend(..) | Action::Call(..) => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// A table holding the current state of each value in a unit.
+pub(crate) trait ValueTable: Sync {
+    /// Get the state of a value.
+    fn get(&self, id: crate::ir::Value) -> Option<&ValueSlot>;
+    /// Change the state of a value.
+    fn set(&mut self, id: crate::ir::Value, slot: ValueSlot);
+}
+
+impl ValueTable for HashMap<crate::ir::Value, ValueSlot> {
+    fn get(&self, id: crate::ir::Value) -> Option<&ValueSlot> {
+        HashMap::get(self, &id)
+    }
+
+    fn set(&mut self, id: crate::ir::Value, slot: ValueSlot) {
+        self.insert(id, slot);
+    }
+}
+
+impl ValueTable for Registers {
+    fn get(&self, id: crate::ir::Value) -> Option<&ValueSlot> {
+        Registers::get(self, id)
+    }
+
+    fn set(&mut self, id: crate::ir::Value, slot: ValueSlot) {
+        Registers::set(self, id, slot)
+    }
+}
+
+/// Write a value to a pointer into the variables of a value table.
+pub(crate) fn store_pointer(values: &mut dyn ValueTable, ptr: &ValuePointer, value: &Value) {
+    // Determine the current state of all targeted variables.
+    let vars = ptr.0.iter().map(|s| s.target.unwrap_variable());
+    let mut modified: Vec<_> = vars
+        .clone()
+        .map(|var| match values.get(var) {
+            Some(ValueSlot::Variable(k)) => k.clone(),
+            x => panic!(
+                "variable targeted by store action has value {:?} instead of Variable(...)",
+                x
+            ),
+        })
+        .collect();
+
+    // Modify the variables.
+    write_pointer(ptr, &mut modified, value);
+
+    // Store the modified state back.
+    for (var, modified) in vars.zip(modified.into_iter()) {
+        values.set(var, ValueSlot::Variable(modified));
+    }
+}
+
+/// Modify a pointer.
+///
+/// This applies a value to a pointer and returns the modified values for
+/// each of the pointer slices.
+pub fn write_pointer(ptr: &ValuePointer, into: &mut [Value], value: &Value) {
+    for (i, (off, s)) in ptr.offset_slices().enumerate() {
+        // Extract the slice of the value that maps to this pointer
+        // slice.
+        let subvalue = if s.width != 0 {
+            match value {
+                Value::Int(v) => v.extract_slice(off, s.width).into(),
+                Value::Logic(v) => v.extract_slice(off, s.width).into(),
+                Value::Array(v) => v.extract_slice(off, s.width).into(),
+                _ => panic!(
+                    "cannot slice {} into {},{} for write to pointer slice {:?}",
+                    value, off, s.width, s
+                ),
+            }
+        } else {
+            value.clone()
+        };
+
+        // Write to this pointer slice.
+        write_pointer_slice(s, &mut into[i], subvalue);
+    }
+}
+
+/// Modify a pointer slice.
+///
+/// This applies a value to a pointer slice and returns the modified value.
+pub fn write_pointer_slice(ptr: &ValueSlice, into: &mut Value, value: Value) {
+    write_pointer_select(&ptr.select, into, value);
+}
+
+/// Modify a pointer selection.
+///
+/// This applies one single select operation to a pointer. Modifies the
+/// value in place.
+pub fn write_pointer_select(select: &[ValueSelect], into: &mut Value, value: Value) {
+    if select.is_empty() {
+        *into = value;
+        return;
+    }
+
+    // Integers and integer arrays store their bits packed, which leaves no
+    // room for the unknown bits of a logic value written into them.
+    let unknown = match value {
+        Value::Logic(_) => true,
+        Value::Array(ref v) => v.packed_bits().is_none(),
+        _ => false,
+    };
+    if unknown {
+        match into {
+            Value::Int(v) => *into = v.to_logic().into(),
+            Value::Array(v) => v.unpack(),
+            _ => (),
+        }
+    }
+
+    match select[0] {
+        ValueSelect::Field(index) => match into {
+            Value::Array(v) => match v.packed_mut() {
+                Some((width, bits)) => {
+                    write_packed_select(&select[1..], bits, index * width, width, None, value)
+                }
+                None => write_pointer_select(&select[1..], v.field_mut(index).unwrap(), value),
+            },
+            Value::Struct(v) => write_pointer_select(&select[1..], &mut v.0[index], value),
+            _ => panic!("access field {} in {}", index, into),
+        },
+        ValueSelect::Slice(offset, length) => match into {
+            Value::Int(v) => write_packed_select(&select[1..], v, offset, length, None, value),
+            Value::Logic(v) => {
+                let mut sub = v.extract_slice(offset, length).into();
+                write_pointer_select(&select[1..], &mut sub, value);
+                v.insert_slice(offset, length, &sub.to_logic());
+            }
+            Value::Array(v) => match v.packed_mut() {
+                Some((width, bits)) => write_packed_select(
+                    &select[1..],
+                    bits,
+                    offset * width,
+                    length * width,
+                    Some(width),
+                    value,
+                ),
+                None => {
+                    let mut sub = v.extract_slice(offset, length).into();
+                    write_pointer_select(&select[1..], &mut sub, value);
+                    v.insert_slice(offset, length, sub.unwrap_array());
+                }
+            },
+            _ => panic!("access slice {},{} in {}", offset, length, into),
+        },
+    }
+}
+
+/// Modify a pointer selection within the bits of an integer or an integer
+/// array.
+///
+/// The selection applies to the `len` bits at `off`, which hold an integer if
+/// `elem` is `None`, or an array of integers of width `elem` otherwise.
+fn write_packed_select(
+    select: &[ValueSelect],
+    bits: &mut IntValue,
+    off: usize,
+    len: usize,
+    elem: Option<usize>,
+    value: Value,
+) {
+    match (select.first(), elem) {
+        (None, _) => match value {
+            Value::Int(v) => bits.insert_slice(off, len, &v),
+            Value::Array(v) => bits.insert_slice(off, len, v.packed_bits().unwrap()),
+            _ => panic!("cannot write {} into integer bits", value),
+        },
+        (Some(&ValueSelect::Field(index)), Some(width)) => {
+            write_packed_select(&select[1..], bits, off + index * width, width, None, value)
+        }
+        (Some(&ValueSelect::Slice(offset, length)), None) => {
+            write_packed_select(&select[1..], bits, off + offset, length, None, value)
+        }
+        (Some(&ValueSelect::Slice(offset, length)), Some(width)) => write_packed_select(
+            &select[1..],
+            bits,
+            off + offset * width,
+            length * width,
+            Some(width),
+            value,
+        ),
+        (Some(&ValueSelect::Field(index)), None) => panic!("access field {} in integer", index),
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Extern functions
+//!
+//! Functions that are declared but not defined in a module are looked up by
+//! name in a registry of functions implemented in Rust. This allows testbenches
+//! to interact with the simulator. The following intrinsics are always
+//! available:
+//!
+//! - `@llhd.print` prints its arguments, followed by a newline. Integers are
+//!   printed in decimal, and arrays of `i8` as text.
+//! - `@llhd.assert` aborts the simulation if its first argument is zero. Any
+//!   further arguments are printed as part of the error message.
+//! - `@llhd.finish` ends the simulation once the current step is complete.
+//!
+//! ```text
+//! declare @llhd.print ([5 x i8], i32) void
+//! declare @llhd.assert (i1) void
+//! declare @llhd.finish () void
+//! ```
+
+use crate::sim::value::{TimeValue, Value};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A function implemented in Rust that can be called from a simulated design.
+///
+/// Returns the value of the call, if any, or a message describing why the
+/// call failed, which aborts the simulation.
+pub type ExternFn<'ll> =
+    Box<dyn Fn(&ExternCall) -> Result<Option<Value>, String> + Send + Sync + 'll>;
+
+/// A call of an extern function.
+pub struct ExternCall<'a> {
+    args: Vec<Value>,
+    time: &'a TimeValue,
+    finish: &'a AtomicBool,
+}
+
+impl ExternCall<'_> {
+    /// The arguments of the call. Signals are passed by their current value.
+    pub fn args(&self) -> &[Value] {
+        &self.args
+    }
+
+    /// The simulation time at which the call occurs.
+    pub fn time(&self) -> &TimeValue {
+        self.time
+    }
+
+    /// End the simulation once the current step is complete.
+    pub fn finish(&self) {
+        self.finish.store(true, Ordering::Relaxed);
+    }
+
+    /// Format the arguments as a message, starting at a given argument.
+    ///
+    /// Integers are formatted in decimal, and arrays of `i8` as text.
+    pub fn message(&self, skip: usize) -> String {
+        let mut msg = String::new();
+        for arg in self.args.iter().skip(skip) {
+            format_value(&mut msg, arg);
+        }
+        msg
+    }
+}
+
+/// The extern functions available to a simulation.
+pub(crate) struct Externs<'ll> {
+    functions: HashMap<String, ExternFn<'ll>>,
+    /// Whether an extern function requested the simulation to end.
+    finish: AtomicBool,
+}
+
+impl<'ll> Externs<'ll> {
+    /// Create a registry with the intrinsics.
+    pub fn new() -> Self {
+        let mut externs = Externs {
+            functions: HashMap::new(),
+            finish: AtomicBool::new(false),
+        };
+        externs.register("llhd.print", Box::new(print));
+        externs.register("llhd.assert", Box::new(assert));
+        externs.register("llhd.finish", Box::new(finish));
+        externs
+    }
+
+    /// Register a function, replacing any function of the same name.
+    ///
+    /// The leading `@` of the name may be omitted.
+    pub fn register(&mut self, name: &str, function: ExternFn<'ll>) {
+        let name = match name.strip_prefix('@') {
+            Some(_) => name.to_string(),
+            None => format!("@{}", name),
+        };
+        self.functions.insert(name, function);
+    }
+
+    /// Call a function.
+    pub fn call(
+        &self,
+        name: &str,
+        args: Vec<Value>,
+        time: &TimeValue,
+    ) -> Result<Option<Value>, String> {
+        let function = self
+            .functions
+            .get(name)
+            .ok_or_else(|| "function is neither defined nor registered".to_string())?;
+        function(&ExternCall {
+            args,
+            time,
+            finish: &self.finish,
+        })
+    }
+
+    /// Check whether a function requested the simulation to end, and reset
+    /// the request.
+    pub fn take_finish(&self) -> bool {
+        self.finish.swap(false, Ordering::Relaxed)
+    }
+
+    /// Check whether a function requested the simulation to end.
+    pub fn finish_requested(&self) -> bool {
+        self.finish.load(Ordering::Relaxed)
+    }
+
+    /// Request the simulation to end, or withdraw the request.
+    pub fn set_finish(&self, finish: bool) {
+        self.finish.store(finish, Ordering::Relaxed);
+    }
+}
+
+/// Append a value to a message.
+fn format_value(msg: &mut String, value: &Value) {
+    match value {
+        Value::Void => (),
+        Value::Time(t) => write!(msg, "{}", t).unwrap(),
+        Value::Int(v) => write!(msg, "{}", v.to_unsigned()).unwrap(),
+        Value::Logic(v) => msg.extend(v.0.iter().rev().map(|b| b.to_char())),
+        Value::Array(v)
+            if v.iter()
+                .all(|e| matches!(e, Value::Int(i) if i.width() == 8)) =>
+        {
+            msg.extend(v.iter().map(|e| e.unwrap_int().to_usize() as u8 as char))
+        }
+        Value::Array(v) => format_list(msg, '[', v.iter(), ']'),
+        Value::Struct(v) => format_list(msg, '{', v.0.iter().cloned(), '}'),
+    }
+}
+
+/// Append a list of values to a message.
+fn format_list(msg: &mut String, open: char, values: impl Iterator<Item = Value>, close: char) {
+    msg.push(open);
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            msg.push_str(", ");
+        }
+        format_value(msg, &value);
+    }
+    msg.push(close);
+}
+
+/// The `@llhd.print` intrinsic.
+fn print(call: &ExternCall) -> Result<Option<Value>, String> {
+    println!("{}", call.message(0));
+    Ok(None)
+}
+
+/// The `@llhd.assert` intrinsic.
+fn assert(call: &ExternCall) -> Result<Option<Value>, String> {
+    match call.args().first() {
+        Some(Value::Int(cond)) if !cond.is_zero() => Ok(None),
+        Some(Value::Int(_)) => {
+            let msg = call.message(1);
+            if msg.is_empty() {
+                Err(format!("assertion failed at {}", call.time()))
+            } else {
+                Err(format!("assertion failed at {}: {}", call.time(), msg))
+            }
+        }
+        _ => Err("expected an integer condition as first argument".to_string()),
+    }
+}
+
+/// The `@llhd.finish` intrinsic.
+fn finish(call: &ExternCall) -> Result<Option<Value>, String> {
+    call.finish();
+    Ok(None)
+}
@@ -0,0 +1,671 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Simulation of LLHD designs.
+//!
+//! This module implements the reference simulator for LLHD. A `Simulation` is
+//! built from a `Module`, after which it can be stepped through time. Signals
+//! can be inspected and changed by their hierarchical name, and tracers can be
+//! attached to record the simulation as it progresses. Signal values are
+//! represented by the types in `sim::value`, which are laid out for simulation
+//! and convert from and to the constants in `llhd::value`.
+//!
+//! ```
+//! use llhd::sim::{value::IntValue, Simulation};
+//!
+//! let module = llhd::assembly::parse_module("
+//!     entity @top (i32$ %a) -> (i32$ %b) {
+//!         %delay = const time 1ns
+//!         %0 = prb i32$ %a
+//!         drv i32$ %b, %0, %delay
+//!     }
+//! ").unwrap();
+//! let mut sim = Simulation::new(&module).unwrap();
+//! sim.poke("@top.a", IntValue::from_usize(32, 42).into()).unwrap();
+//! sim.run().unwrap();
+//! assert_eq!(sim.peek("@top.b"), Some(&IntValue::from_usize(32, 42).into()));
+//! ```
+
+mod builder;
+mod bytecode;
+mod checkpoint;
+pub mod coverage;
+mod engine;
+mod externs;
+pub mod profile;
+mod state;
+pub mod stimulus;
+pub mod tracediff;
+pub mod tracer;
+pub mod value;
+pub mod xprop;
+
+pub use self::externs::{ExternCall, ExternFn};
+pub use self::state::{Scope, Signal, SignalRef, State};
+use self::{
+    bytecode::Programs,
+    checkpoint::Checkpoint,
+    coverage::{Coverage, CoverageState},
+    engine::Engine,
+    profile::{Profile, ProfileState},
+    state::{ValuePointer, ValueSlice, ValueTarget},
+    stimulus::{Change, Stimulus},
+    tracer::Tracer,
+    value::{TimeValue, Value},
+    xprop::{XpropState, XpropWarning},
+};
+use crate::ir::Module;
+
+/// A simulation of an LLHD design.
+pub struct Simulation<'ll> {
+    state: State<'ll>,
+    tracers: Vec<Box<dyn Tracer + 'll>>,
+    parallelize: bool,
+    /// The compiled units, if the bytecode backend is used.
+    programs: Option<Programs<'ll>>,
+    steps: usize,
+    /// Whether the current simulation time has already been simulated.
+    settled: bool,
+}
+
+impl<'ll> Simulation<'ll> {
+    /// Build a simulation for a module.
+    ///
+    /// The last process or entity in the module is used as the root of the
+    /// design hierarchy.
+    pub fn new(module: &'ll Module) -> Result<Self, SimulationError> {
+        Self::build(module, None)
+    }
+
+    /// Build a simulation for a module, using a specific process or entity as
+    /// the root of the design hierarchy.
+    ///
+    /// The leading `@` of the unit's name may be omitted.
+    pub fn with_top(module: &'ll Module, top: &str) -> Result<Self, SimulationError> {
+        Self::build(module, Some(top))
+    }
+
+    fn build(module: &'ll Module, top: Option<&str>) -> Result<Self, SimulationError> {
+        Ok(Self {
+            state: builder::build(module, top)?,
+            tracers: vec![],
+            parallelize: true,
+            programs: None,
+            steps: 0,
+            settled: false,
+        })
+    }
+
+    /// Restore a simulation from a checkpoint.
+    ///
+    /// The module must be the same as the one the checkpoint was taken from.
+    /// The simulation continues at the point in time where the checkpoint was
+    /// saved. Tracers attached afterwards only record the remainder of the
+    /// simulation, whereas the coverage and profile collected up to the
+    /// checkpoint carry over. Settings such as the backend, the delta cycle
+    /// limit, and X propagation are not part of the checkpoint.
+    pub fn restore(
+        module: &'ll Module,
+        reader: impl std::io::Read,
+    ) -> Result<Self, SimulationError> {
+        let cp = Checkpoint::read(reader)?;
+        let mut sim = Self::build(module, Some(&cp.top))?;
+        sim.steps = cp.steps;
+        sim.settled = cp.settled;
+        cp.apply(&mut sim.state)?;
+        Ok(sim)
+    }
+
+    /// Save the current state of the simulation as a checkpoint.
+    ///
+    /// The simulation can later be resumed from this point with `restore`.
+    pub fn save_checkpoint(&self, writer: impl std::io::Write) -> Result<(), SimulationError> {
+        Checkpoint::capture(&self.state, self.steps, self.settled).write(writer)
+    }
+
+    /// Enable or disable the parallel execution of instances.
+    pub fn set_parallelize(&mut self, parallelize: bool) {
+        self.parallelize = parallelize;
+    }
+
+    /// Limit the number of delta cycles in each time step.
+    ///
+    /// A design that does not settle, for example because of a combinational
+    /// loop, would otherwise be simulated forever without advancing time.
+    /// Once the limit is exceeded, the simulation stops with an error listing
+    /// the signals and instances that were active in the last delta cycles.
+    /// The limit is 10000 by default, and `None` disables it.
+    pub fn set_delta_limit(&mut self, limit: Option<usize>) {
+        self.state.deltas.limit = limit;
+    }
+
+    /// Select the backend that executes the processes and entities.
+    ///
+    /// Selecting the bytecode backend compiles all units of the module. The
+    /// backend may be changed at any point during the simulation.
+    pub fn set_backend(&mut self, backend: Backend) {
+        match backend {
+            Backend::Interpreter => {
+                self.programs = None;
+                for inst in &mut self.state.insts {
+                    let inst = inst.get_mut().unwrap();
+                    if !inst.frame.is_empty() {
+                        inst.values = inst.frame.to_map();
+                        inst.frame = Default::default();
+                    }
+                }
+            }
+            Backend::Bytecode => {
+                if self.programs.is_none() {
+                    self.programs = Some(Programs::compile(self.state.module));
+                }
+            }
+        }
+    }
+
+    /// Start collecting toggle and block coverage.
+    ///
+    /// Only the remainder of the simulation is covered, such that this is
+    /// usually called before the first step.
+    pub fn enable_coverage(&mut self) {
+        if self.state.coverage.is_none() {
+            self.state.coverage = Some(CoverageState::new(&self.state.signals));
+        }
+    }
+
+    /// Get the coverage collected so far, if coverage is enabled.
+    pub fn coverage(&self) -> Option<Coverage> {
+        self.state
+            .coverage
+            .as_ref()
+            .map(|coverage| coverage.report(&self.state))
+    }
+
+    /// Start collecting an activity profile of the instances and signals.
+    ///
+    /// Only the remainder of the simulation is profiled, such that this is
+    /// usually called before the first step.
+    pub fn enable_profile(&mut self) {
+        if self.state.profile.is_none() {
+            self.state.profile = Some(ProfileState::new(self.state.signals.len()));
+        }
+    }
+
+    /// Get the profile collected so far, if profiling is enabled.
+    pub fn profile(&self) -> Option<Profile> {
+        self.state
+            .profile
+            .as_ref()
+            .map(|profile| profile.report(&self.state))
+    }
+
+    /// Start X propagation.
+    ///
+    /// The ports of the simulated unit and the signals stored by `reg`
+    /// instructions are set to `X`, unless the simulation has already been
+    /// stepped. Integers then carry unknown bits through arithmetic,
+    /// comparisons, and `mux`. Branches and conditional drives on an unknown
+    /// condition treat it as false and record a warning.
+    pub fn enable_xprop(&mut self) {
+        if self.state.xprop.is_some() {
+            return;
+        }
+        self.state.xprop = Some(XpropState::default());
+        if self.steps > 0 {
+            return;
+        }
+        for &sig in &self.state.uninitialized {
+            let signal = &mut self.state.signals[sig.as_usize()];
+            let value = Value::unknown(signal.ty().unwrap_signal());
+            signal.set_value(value);
+        }
+    }
+
+    /// Get the warnings of instructions that observed an unknown value they
+    /// could not propagate, if X propagation is enabled.
+    pub fn xprop_warnings(&self) -> Option<Vec<XpropWarning>> {
+        self.state.xprop.as_ref().map(|xprop| xprop.warnings())
+    }
+
+    /// Register a function that can be called from the simulated design.
+    ///
+    /// Calls of functions that are declared but not defined in the module are
+    /// dispatched to the registered function of the same name. This replaces
+    /// any function registered under the same name before, including the
+    /// intrinsics `llhd.print`, `llhd.assert`, and `llhd.finish`. The leading
+    /// `@` of the name may be omitted.
+    ///
+    /// Instances may run in parallel, such that the function may be called
+    /// from several threads at once.
+    pub fn register_extern(
+        &mut self,
+        name: &str,
+        function: impl Fn(&ExternCall) -> Result<Option<Value>, String> + Send + Sync + 'll,
+    ) {
+        self.state.externs.register(name, Box::new(function));
+    }
+
+    /// Attach a tracer to the simulation.
+    pub fn add_tracer(&mut self, mut tracer: Box<dyn Tracer + 'll>) {
+        tracer.init(&self.state);
+        self.tracers.push(tracer);
+    }
+
+    /// Access the simulation state.
+    pub fn state(&self) -> &State<'ll> {
+        &self.state
+    }
+
+    /// Get the current simulation time.
+    pub fn time(&self) -> &TimeValue {
+        &self.state.time
+    }
+
+    /// Get the number of steps simulated so far.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Perform one simulation step.
+    ///
+    /// Returns true if there are remaining events in the queue, false
+    /// otherwise. This can be used as an indication as to when the simulation
+    /// is finished.
+    pub fn step(&mut self) -> Result<bool, SimulationError> {
+        let first = self.steps == 0;
+        self.steps += 1;
+        let more = Engine::new(&mut self.state, self.parallelize, self.programs.as_ref())
+            .step(&mut self.tracers, first)?;
+        self.settled = !more;
+        Ok(more)
+    }
+
+    /// Run the simulation until no more events are scheduled.
+    pub fn run(&mut self) -> Result<(), SimulationError> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    /// Run the simulation until the given time has passed.
+    ///
+    /// All steps up to and including the given time are performed, including
+    /// all of their delta cycles. Returns true if there are remaining events in
+    /// the queue, false otherwise.
+    pub fn run_until(&mut self, time: &TimeValue) -> Result<bool, SimulationError> {
+        while !self.settled && self.state.time.time() <= time.time() {
+            self.step()?;
+        }
+        Ok(!self.settled)
+    }
+
+    /// Notify the attached tracers that the simulation has ended.
+    pub fn finish(&mut self) {
+        for tracer in &mut self.tracers {
+            tracer.finish(&self.state);
+        }
+    }
+
+    /// Find a signal by its hierarchical name.
+    ///
+    /// The name consists of the names of the nested scopes and the name of the
+    /// signal within its scope, separated by periods, for example `@top.@ff.q`.
+    /// The name of the root scope may be omitted, such that the ports of the
+    /// top-level unit can be referred to by their name alone. If several
+    /// signals share the same name, the first one is returned.
+    pub fn signal(&self, name: &str) -> Option<SignalRef> {
+        fn find(scope: &Scope, prefix: &str, name: &str) -> Option<SignalRef> {
+            let prefix = format!("{}{}.", prefix, scope.name);
+            let rest = name.strip_prefix(&prefix)?;
+            let mut probes: Vec<_> = scope.probes.iter().collect();
+            probes.sort();
+            probes
+                .into_iter()
+                .find(|(_, names)| names.iter().any(|n| n == rest))
+                .map(|(&sig, _)| sig)
+                .or_else(|| scope.subscopes.iter().find_map(|s| find(s, &prefix, name)))
+        }
+        let root = &self.state.scope;
+        find(root, "", name).or_else(|| find(root, "", &format!("{}.{}", root.name, name)))
+    }
+
+    /// Find the hierarchical name of a signal.
+    ///
+    /// This is the inverse of `signal`. If the signal is visible under several
+    /// names, the one closest to the root of the hierarchy is returned.
+    pub fn signal_name(&self, sig: SignalRef) -> Option<String> {
+        self.state.signal_name(sig)
+    }
+
+    /// Get the status of all process and entity instances in the design.
+    pub fn instances(&self) -> Vec<InstanceStatus> {
+        use self::state::{InstanceKind, InstanceState};
+        self.state
+            .insts
+            .iter()
+            .map(|inst| {
+                let inst = inst.lock().unwrap();
+                let name = |sig: &SignalRef| {
+                    self.signal_name(*sig)
+                        .unwrap_or_else(|| format!("{:?}", sig))
+                };
+                let state = match &inst.state {
+                    InstanceState::Ready => "ready".to_string(),
+                    InstanceState::Done => "done".to_string(),
+                    InstanceState::Wait(time, sigs) => {
+                        let mut s = "waiting".to_string();
+                        if let Some(time) = time {
+                            s.push_str(&format!(" until {}", time));
+                        }
+                        if !sigs.is_empty() {
+                            let sigs: Vec<_> = sigs.iter().map(name).collect();
+                            s.push_str(&format!(" for {}", sigs.join(", ")));
+                        }
+                        s
+                    }
+                };
+                let (unit, location) = match inst.kind {
+                    InstanceKind::Process {
+                        prok,
+                        next_block,
+                        prev_block,
+                    } => {
+                        // A suspended process is stopped at the terminator of
+                        // the previous block. One that has not run yet is
+                        // stopped at the first instruction.
+                        let location = match (prev_block, next_block) {
+                            (Some(bb), _) => Some((bb, prok.terminator(bb))),
+                            (None, Some(bb)) => prok.first_inst(bb).map(|inst| (bb, inst)),
+                            (None, None) => None,
+                        };
+                        let location = location.map(|(bb, inst)| {
+                            (bb.dump(&prok).to_string(), inst.dump(&prok).to_string())
+                        });
+                        (prok, location)
+                    }
+                    InstanceKind::Entity { entity } => (entity, None),
+                };
+                let (block, inst_text) = location.unzip();
+                InstanceStatus {
+                    path: inst.path.clone(),
+                    unit: unit.name().to_string(),
+                    state,
+                    block,
+                    inst: inst_text,
+                }
+            })
+            .collect()
+    }
+
+    /// Get the current value of a signal, given its hierarchical name.
+    pub fn peek(&self, name: &str) -> Option<&Value> {
+        self.signal(name).map(|sig| self.state[sig].value())
+    }
+
+    /// Change the value of a signal, given its hierarchical name.
+    ///
+    /// The new value is applied at the beginning of the next simulation step,
+    /// as if it were driven by an instance in the design.
+    pub fn poke(&mut self, name: &str, value: Value) -> Result<(), SimulationError> {
+        let time = self.state.time.clone();
+        self.schedule(name, time, value)
+    }
+
+    /// Change the value of a signal at a given time, given its hierarchical
+    /// name.
+    ///
+    /// Times that have already been simulated are treated like `poke`.
+    pub fn schedule(
+        &mut self,
+        name: &str,
+        time: TimeValue,
+        value: Value,
+    ) -> Result<(), SimulationError> {
+        let sig = self.lookup(name)?;
+        if self.settled {
+            let time = &self.state.time;
+            self.state.time = TimeValue::new(time.time().clone(), time.delta() + 1, 0);
+            self.settled = false;
+        }
+        let time = std::cmp::max(time, self.state.time.clone());
+        let ty = self.value_type(sig);
+        let ptr = ValuePointer(vec![ValueSlice {
+            target: ValueTarget::Signal(sig),
+            select: vec![],
+            width: if ty.is_struct() { 0 } else { ty.len() },
+        }]);
+        self.state
+            .events
+            .entry(time)
+            .or_default()
+            .insert((ptr, None), value);
+        Ok(())
+    }
+
+    /// Set the initial value of a signal, given its hierarchical name.
+    ///
+    /// Unlike `poke`, this changes the value immediately without generating an
+    /// event. This is intended to be used before the simulation starts.
+    pub fn initialize(&mut self, name: &str, value: Value) -> Result<(), SimulationError> {
+        let sig = self.lookup(name)?;
+        self.state[sig].set_value(value);
+        Ok(())
+    }
+
+    /// Schedule all changes of a stimulus.
+    ///
+    /// The values are parsed according to the type of the signal they are
+    /// applied to.
+    pub fn apply_stimulus(&mut self, stimulus: &Stimulus) -> Result<(), SimulationError> {
+        for change in stimulus.changes() {
+            let value = self.parse_change(change)?;
+            self.schedule(&change.signal, change.time.clone(), value)?;
+        }
+        Ok(())
+    }
+
+    /// Parse the value of a stimulus change.
+    ///
+    /// Unknown and high-impedance bits can only be applied to integer signals
+    /// if X propagation is enabled.
+    fn parse_change(&self, change: &Change) -> Result<Value, SimulationError> {
+        let ty = self.value_type(self.lookup(&change.signal)?);
+        if let crate::ty::TypeKind::IntType(w) = *ty {
+            let logic = Value::parse(&crate::ty::logic_ty(w), &change.value);
+            if let (None, Some(value)) = (Value::parse(&ty, &change.value), logic) {
+                if self.state.xprop.is_none() {
+                    return Err(SimulationError::UnknownBits {
+                        signal: change.signal.clone(),
+                        time: change.time.clone(),
+                    });
+                }
+                return Ok(value);
+            }
+        }
+        self.parse_value(&change.signal, &change.value)
+    }
+
+    /// Parse a value for a signal, given its hierarchical name.
+    pub fn parse_value(&self, name: &str, text: &str) -> Result<Value, SimulationError> {
+        let ty = self.value_type(self.lookup(name)?);
+        Value::parse(&ty, text).ok_or_else(|| SimulationError::InvalidValue {
+            signal: name.to_string(),
+            value: text.to_string(),
+        })
+    }
+
+    /// Find a signal by its hierarchical name, or fail.
+    fn lookup(&self, name: &str) -> Result<SignalRef, SimulationError> {
+        self.signal(name)
+            .ok_or_else(|| SimulationError::UnknownSignal(name.to_string()))
+    }
+
+    /// Get the type of the values carried by a signal.
+    fn value_type(&self, sig: SignalRef) -> crate::Type {
+        let ty = self.state[sig].ty();
+        if ty.is_signal() {
+            ty.unwrap_signal().clone()
+        } else {
+            ty.clone()
+        }
+    }
+}
+
+/// The status of a process or entity instance in a simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceStatus {
+    /// The hierarchical name of the instance.
+    pub path: String,
+    /// The name of the instantiated process or entity.
+    pub unit: String,
+    /// A description of what the instance is waiting for.
+    pub state: String,
+    /// The block a process is currently stopped in.
+    pub block: Option<String>,
+    /// The instruction a process is currently stopped at. This is the
+    /// instruction that suspended the process, or the first instruction if the
+    /// process has not run yet.
+    pub inst: Option<String>,
+}
+
+/// The backend that executes the processes and entities of a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Interpret the instructions of each unit directly. This is the reference
+    /// implementation of the simulation semantics.
+    #[default]
+    Interpreter,
+    /// Compile each unit to a flat list of operations on pre-resolved
+    /// registers before the simulation starts, and execute these.
+    Bytecode,
+}
+
+/// An error that aborts the simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimulationError {
+    /// The module contains no process or entity that could be simulated.
+    NoRoot,
+    /// The requested root unit is not a process or entity in the module.
+    UnknownUnit(String),
+    /// A signal name could not be found in the design hierarchy.
+    UnknownSignal(String),
+    /// A value is not valid for the signal it is applied to.
+    InvalidValue {
+        /// The name of the signal.
+        signal: String,
+        /// The textual representation of the value.
+        value: String,
+    },
+    /// A stimulus applied unknown or high-impedance bits to an integer signal
+    /// while X propagation was disabled.
+    UnknownBits {
+        /// The name of the signal.
+        signal: String,
+        /// The time at which the bits were applied.
+        time: TimeValue,
+    },
+    /// An instance executed an instruction the simulator does not support.
+    Unsupported {
+        /// The hierarchical name of the instance.
+        instance: String,
+        /// The unit containing the instruction. This differs from the
+        /// instance's unit if the instruction is part of a called function.
+        unit: String,
+        /// The instruction that is not supported.
+        inst: String,
+    },
+    /// An instance divided by zero, or computed a remainder of a division by
+    /// zero, while X propagation was disabled.
+    DivisionByZero {
+        /// The hierarchical name of the instance.
+        instance: String,
+        /// The unit containing the instruction.
+        unit: String,
+        /// The instruction that divided by zero.
+        inst: String,
+    },
+    /// A checkpoint could not be saved or restored.
+    Checkpoint(String),
+    /// A call of an extern function failed, for example because an assertion
+    /// did not hold.
+    Extern {
+        /// The hierarchical name of the calling instance.
+        instance: String,
+        /// The name of the function.
+        function: String,
+        /// A description of the failure.
+        message: String,
+    },
+    /// The simulation did not settle within the limit of delta cycles in a
+    /// time step, for example because of a combinational loop. The simulation
+    /// time remains in the time step in which the limit was reached.
+    DeltaLimit {
+        /// The number of delta cycles performed in the time step.
+        limit: usize,
+        /// The hierarchical names of the signals that changed in the last
+        /// delta cycles.
+        signals: Vec<String>,
+        /// The hierarchical names of the instances executed in the last delta
+        /// cycles.
+        instances: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SimulationError::NoRoot => {
+                write!(f, "no process or entity found that can be simulated")
+            }
+            SimulationError::UnknownUnit(name) => {
+                write!(f, "no process or entity named `{}` found", name)
+            }
+            SimulationError::UnknownSignal(name) => write!(f, "unknown signal `{}`", name),
+            SimulationError::InvalidValue { signal, value } => {
+                write!(f, "invalid value `{}` for signal `{}`", value, signal)
+            }
+            SimulationError::UnknownBits { signal, time } => write!(
+                f,
+                "`x` or `z` bits applied to integer signal `{}` at {}",
+                signal, time
+            ),
+            SimulationError::Unsupported {
+                instance,
+                unit,
+                inst,
+            } => write!(
+                f,
+                "unsupported instruction `{}` in {} (instance {})",
+                inst, unit, instance
+            ),
+            SimulationError::DivisionByZero {
+                instance,
+                unit,
+                inst,
+            } => write!(
+                f,
+                "division by zero in `{}` in {} (instance {})",
+                inst, unit, instance
+            ),
+            SimulationError::Checkpoint(msg) => write!(f, "checkpoint: {}", msg),
+            SimulationError::Extern {
+                instance,
+                function,
+                message,
+            } => write!(f, "{}: {} (instance {})", function, message, instance),
+            SimulationError::DeltaLimit {
+                limit,
+                signals,
+                instances,
+            } => write!(
+                f,
+                "simulation did not settle after {} delta cycles\n  \
+                 oscillating signals: {}\n  \
+                 active instances: {}",
+                limit,
+                signals.join(", "),
+                instances.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
@@ -0,0 +1,259 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Activity profile of a simulation
+//!
+//! The profile records for every instance how often it was woken up, how many
+//! instructions it executed, and how much wall time was spent executing it. It
+//! also records for every signal how many events were applied to it, and how
+//! many instances were woken up by its changes. This shows which parts of a
+//! design make a simulation slow, and whether a transformation of the design
+//! made it faster.
+//!
+//! Instructions executed in functions are attributed to the calling
+//! instruction, such that a call counts as a single instruction. When
+//! instances are executed in parallel, the sum of their times exceeds the wall
+//! time of the simulation.
+
+use crate::sim::state::{InstanceKind, SignalRef, State};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Mutex,
+    time::Duration,
+};
+
+/// The number of instances and signals listed in the summary of a profile.
+const SUMMARY_LENGTH: usize = 20;
+
+/// The activity profile of a simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// The activity of each instance, most time-consuming first.
+    pub instances: Vec<InstanceProfile>,
+    /// The events of each signal, most eventful first.
+    pub signals: Vec<SignalProfile>,
+}
+
+/// The activity of an instance or a unit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Activity {
+    /// The number of times execution was resumed.
+    pub wakeups: u64,
+    /// The number of instructions executed.
+    pub instructions: u64,
+    /// The wall time spent executing.
+    pub time: Duration,
+}
+
+/// The activity of an instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceProfile {
+    /// The hierarchical path of the instance.
+    pub path: String,
+    /// The name of the instantiated unit.
+    pub unit: String,
+    /// The activity of the instance.
+    pub activity: Activity,
+}
+
+/// The activity of all instances of a unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitProfile {
+    /// The name of the unit.
+    pub unit: String,
+    /// The number of instances of the unit.
+    pub instances: usize,
+    /// The combined activity of the instances.
+    pub activity: Activity,
+}
+
+/// The events of a signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalProfile {
+    /// The hierarchical name of the signal.
+    pub name: String,
+    /// The number of events applied to the signal.
+    pub events: u64,
+    /// The number of instances woken up by changes of the signal.
+    pub wakeups: u64,
+}
+
+impl Profile {
+    /// The activity of each unit, most time-consuming first.
+    pub fn units(&self) -> Vec<UnitProfile> {
+        let mut units = BTreeMap::<&str, UnitProfile>::new();
+        for inst in &self.instances {
+            let unit = units.entry(&inst.unit).or_insert_with(|| UnitProfile {
+                unit: inst.unit.clone(),
+                instances: 0,
+                activity: Default::default(),
+            });
+            unit.instances += 1;
+            unit.activity.add(&inst.activity);
+        }
+        let mut units: Vec<_> = units.into_values().collect();
+        units.sort_by_key(|unit| std::cmp::Reverse(unit.activity.time));
+        units
+    }
+}
+
+impl Activity {
+    /// Accumulate the activity of another instance.
+    fn add(&mut self, other: &Activity) {
+        self.wakeups += other.wakeups;
+        self.instructions += other.instructions;
+        self.time += other.time;
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = |f: &mut fmt::Formatter, what: &str| {
+            writeln!(
+                f,
+                "{:>9} {:>13} {:>12}  {}",
+                "wakeups", "instructions", "time", what
+            )
+        };
+        let row = |f: &mut fmt::Formatter, activity: &Activity, name: &str| {
+            writeln!(
+                f,
+                "{:>9} {:>13} {:>10.3}ms  {}",
+                activity.wakeups,
+                activity.instructions,
+                activity.time.as_secs_f64() * 1e3,
+                name
+            )
+        };
+        let more = |f: &mut fmt::Formatter, total: usize, what: &str| {
+            if total > SUMMARY_LENGTH {
+                writeln!(f, "  ... and {} more {}", total - SUMMARY_LENGTH, what)?;
+            }
+            Ok(())
+        };
+
+        writeln!(f, "Units:")?;
+        header(f, "unit")?;
+        for unit in self.units() {
+            let plural = if unit.instances == 1 { "" } else { "s" };
+            let name = format!("{} ({} instance{})", unit.unit, unit.instances, plural);
+            row(f, &unit.activity, &name)?;
+        }
+
+        writeln!(f, "Instances:")?;
+        header(f, "instance")?;
+        for inst in self.instances.iter().take(SUMMARY_LENGTH) {
+            row(f, &inst.activity, &inst.path)?;
+        }
+        more(f, self.instances.len(), "instances")?;
+
+        writeln!(f, "Signals:")?;
+        writeln!(f, "{:>9} {:>13}  signal", "events", "wakeups")?;
+        for signal in self.signals.iter().take(SUMMARY_LENGTH) {
+            writeln!(
+                f,
+                "{:>9} {:>13}  {}",
+                signal.events, signal.wakeups, signal.name
+            )?;
+        }
+        more(f, self.signals.len(), "signals")
+    }
+}
+
+/// The profile of a running simulation.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ProfileState {
+    /// The activity of each instance, by hierarchical path.
+    instances: Mutex<HashMap<String, Activity>>,
+    /// The number of events and wakeups of each signal.
+    signals: Vec<(u64, u64)>,
+}
+
+impl Clone for ProfileState {
+    fn clone(&self) -> Self {
+        ProfileState {
+            instances: Mutex::new(self.instances.lock().unwrap().clone()),
+            signals: self.signals.clone(),
+        }
+    }
+}
+
+impl ProfileState {
+    /// Start profiling a simulation with a number of signals.
+    pub fn new(num_signals: usize) -> Self {
+        ProfileState {
+            instances: Default::default(),
+            signals: vec![(0, 0); num_signals],
+        }
+    }
+
+    /// Record one execution of an instance.
+    pub fn record_instance(&self, path: &str, instructions: u64, time: Duration) {
+        let mut instances = self.instances.lock().unwrap();
+        instances
+            .entry(path.to_string())
+            .or_default()
+            .add(&Activity {
+                wakeups: 1,
+                instructions,
+                time,
+            });
+    }
+
+    /// Record an event applied to a signal.
+    pub fn record_event(&mut self, signal: SignalRef) {
+        self.signals[signal.as_usize()].0 += 1;
+    }
+
+    /// Record an instance woken up by a change of a signal.
+    pub fn record_wakeup(&mut self, signal: SignalRef) {
+        self.signals[signal.as_usize()].1 += 1;
+    }
+
+    /// Assemble a profile of the instances and signals.
+    pub fn report(&self, state: &State) -> Profile {
+        let activities = self.instances.lock().unwrap();
+        let mut instances: Vec<_> = state
+            .insts
+            .iter()
+            .map(|inst| {
+                let inst = inst.lock().unwrap();
+                let unit = match inst.kind {
+                    InstanceKind::Process { prok, .. } => prok,
+                    InstanceKind::Entity { entity } => entity,
+                };
+                InstanceProfile {
+                    path: inst.path.clone(),
+                    unit: unit.name().to_string(),
+                    activity: activities.get(&inst.path).copied().unwrap_or_default(),
+                }
+            })
+            .collect();
+        instances.sort_by(|a, b| {
+            b.activity
+                .time
+                .cmp(&a.activity.time)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        let mut signals: Vec<_> = self
+            .signals
+            .iter()
+            .enumerate()
+            .filter(|(_, &(events, wakeups))| events > 0 || wakeups > 0)
+            .filter_map(|(i, &(events, wakeups))| {
+                Some(SignalProfile {
+                    name: state.signal_name(SignalRef::new(i))?,
+                    events,
+                    wakeups,
+                })
+            })
+            .collect();
+        signals.sort_by(|a, b| {
+            (b.events, b.wakeups)
+                .cmp(&(a.events, a.wakeups))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Profile { instances, signals }
+    }
+}
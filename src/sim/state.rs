@@ -0,0 +1,1046 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! The simulation state.
+
+use crate::ir::{DelayMode, Resolution};
+use crate::sim::value::{TimeValue, Value};
+use crate::table::TableKey;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    ops::{Index, IndexMut},
+    sync::Mutex,
+};
+
+/// A simulation state.
+pub struct State<'ll> {
+    /// The LLHD module being simulated.
+    pub module: &'ll crate::ir::Module,
+    /// The signals present in the simulation.
+    pub signals: Vec<Signal>,
+    /// The probed signals.
+    pub probes: HashMap<SignalRef, Vec<String>>,
+    /// The root scope of the simulation.
+    pub scope: Scope,
+    /// The process and entity instances in the simulation.
+    pub(crate) insts: Vec<Mutex<Instance<'ll>>>,
+    /// The current simulation time.
+    pub time: TimeValue,
+
+    /// The current state of the event queue, by target and driver.
+    pub(crate) events: BTreeMap<TimeValue, HashMap<(ValuePointer, Option<Driver>), Value>>,
+    /// The slices with transactions in the event queue, by target signal and
+    /// driver, and by time.
+    pub(crate) pending: HashMap<(SignalRef, Driver), BTreeMap<TimeValue, Vec<ValueSlice>>>,
+    /// The current wakeup queue for instances.
+    pub(crate) timed: BTreeMap<TimeValue, HashSet<InstanceRef>>,
+    /// The coverage collected so far, if enabled.
+    pub(crate) coverage: Option<crate::sim::coverage::CoverageState>,
+    /// The activity profile collected so far, if enabled.
+    pub(crate) profile: Option<crate::sim::profile::ProfileState>,
+    /// The delta cycles performed in the current time step.
+    pub(crate) deltas: crate::sim::engine::DeltaCycles,
+    /// The functions that are called but not defined in the module.
+    pub(crate) externs: crate::sim::externs::Externs<'ll>,
+    /// The signals whose initial value is not given by the design: the ports
+    /// of the root unit, and the signals stored by `reg` instructions.
+    pub(crate) uninitialized: Vec<SignalRef>,
+    /// The X propagation state, if enabled.
+    pub(crate) xprop: Option<crate::sim::xprop::XpropState>,
+}
+
+impl<'ll> State<'ll> {
+    /// Add a set of events driven by instances to the schedule.
+    ///
+    /// Each event is split into one transaction per pointer slice, such that
+    /// later drives can cancel individual slices.
+    pub(crate) fn schedule_events<I>(&mut self, iter: I)
+    where
+        I: Iterator<Item = (InstanceRef, Event)>,
+    {
+        let time = self.time.clone();
+        for (inst, i) in iter {
+            assert!(i.time >= time);
+            debug!(
+                "Schedule {} <- {}  [@ {}]",
+                i.signal
+                    .0
+                    .iter()
+                    .map(|s| {
+                        let sig = s.target.unwrap_signal();
+                        self.probes
+                            .get(&sig)
+                            .map(|n| n[0].clone())
+                            .unwrap_or_else(|| format!("{:?}", sig))
+                    })
+                    .collect::<String>(),
+                i.value,
+                i.time,
+            );
+            let driver = Driver {
+                inst,
+                drive: i.drive,
+            };
+            let transactions: Vec<_> = i
+                .signal
+                .offset_slices()
+                .map(|(off, s)| (s.clone(), extract_slice(&i.value, off, s.width)))
+                .collect();
+            for (slice, value) in &transactions {
+                self.cancel_transactions(driver, slice, &i.time, value, i.mode);
+            }
+            for (slice, value) in transactions {
+                self.add_transaction(i.time.clone(), driver, slice, value);
+            }
+        }
+    }
+
+    /// Add a transaction of a driver to the event queue.
+    fn add_transaction(
+        &mut self,
+        time: TimeValue,
+        driver: Driver,
+        slice: ValueSlice,
+        value: Value,
+    ) {
+        self.pending
+            .entry((slice.target.unwrap_signal(), driver))
+            .or_default()
+            .entry(time.clone())
+            .or_default()
+            .push(slice.clone());
+        self.events
+            .entry(time)
+            .or_default()
+            .insert((ValuePointer(vec![slice]), Some(driver)), value);
+    }
+
+    /// Remove the pending transactions of a driver that are superseded by a
+    /// new transaction on a slice.
+    ///
+    /// The transactions at or after the time of the new one are always
+    /// removed. With inertial delay, the earlier ones are removed as well,
+    /// except for the run of transactions immediately before the new one that
+    /// drive the same value. This swallows pulses shorter than the delay.
+    /// Transactions that only partially overlap the slice are cut down to the
+    /// elements or bits outside of it.
+    fn cancel_transactions(
+        &mut self,
+        driver: Driver,
+        slice: &ValueSlice,
+        time: &TimeValue,
+        value: &Value,
+        mode: DelayMode,
+    ) {
+        let signal = slice.target.unwrap_signal();
+        let pending = match self.pending.get_mut(&(signal, driver)) {
+            Some(pending) => pending,
+            None => return,
+        };
+        let cut = slice.path();
+        let mut preceding = true;
+        let mut emptied = vec![];
+        for (t, slices) in pending.iter_mut().rev() {
+            if t < time && mode == DelayMode::Transport {
+                break;
+            }
+            let (overlapping, disjoint): (Vec<_>, Vec<_>) =
+                slices.drain(..).partition(|s| s.path().overlaps(&cut));
+            *slices = disjoint;
+            if overlapping.is_empty() {
+                continue;
+            }
+            let events = self.events.get_mut(t).unwrap();
+            if t < time {
+                let key = (ValuePointer(vec![slice.clone()]), Some(driver));
+                if preceding && key.0 .0 == overlapping && events.get(&key) == Some(value) {
+                    slices.extend(overlapping);
+                    continue;
+                }
+                preceding = false;
+            }
+            for s in overlapping {
+                let value = events
+                    .remove(&(ValuePointer(vec![s.clone()]), Some(driver)))
+                    .unwrap();
+                for (s, value) in subtract_slice(s, value, &cut) {
+                    slices.push(s.clone());
+                    events.insert((ValuePointer(vec![s]), Some(driver)), value);
+                }
+            }
+            if events.is_empty() {
+                self.events.remove(t);
+            }
+            if slices.is_empty() {
+                emptied.push(t.clone());
+            }
+        }
+        for t in emptied {
+            pending.remove(&t);
+        }
+        if pending.is_empty() {
+            self.pending.remove(&(signal, driver));
+        }
+    }
+
+    /// Replace the event queue, for example when restoring a checkpoint.
+    pub(crate) fn set_events(
+        &mut self,
+        events: BTreeMap<TimeValue, HashMap<(ValuePointer, Option<Driver>), Value>>,
+    ) {
+        self.events = Default::default();
+        self.pending.clear();
+        for (time, events) in events {
+            for ((ptr, driver), value) in events {
+                match driver {
+                    Some(driver) => {
+                        for (off, s) in ptr.offset_slices() {
+                            let value = extract_slice(&value, off, s.width);
+                            self.add_transaction(time.clone(), driver, s.clone(), value);
+                        }
+                    }
+                    None => {
+                        self.events
+                            .entry(time.clone())
+                            .or_default()
+                            .insert((ptr, None), value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add a set of timed instances to the schedule.
+    pub(crate) fn schedule_timed<I>(&mut self, iter: I)
+    where
+        I: Iterator<Item = TimedInstance>,
+    {
+        let time = self.time.clone();
+        for i in iter {
+            assert!(i.time >= time);
+            debug!("Schedule {:?}  [@ {}]", i.inst, i.time);
+            self.timed
+                .entry(i.time)
+                .or_insert_with(Default::default)
+                .insert(i.inst);
+        }
+    }
+
+    /// Dequeue all events due at the current time, together with their driver.
+    ///
+    /// Events of multiple drivers on the same unresolved target are applied in
+    /// the order of the drivers, such that the last driver wins.
+    pub(crate) fn take_next_events(
+        &mut self,
+    ) -> impl Iterator<Item = (Option<Driver>, ValuePointer, Value)> {
+        let mut events: Vec<_> = self
+            .events
+            .remove(&self.time)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        for ((ptr, driver), _) in &events {
+            if let Some(driver) = *driver {
+                for s in &ptr.0 {
+                    let key = (s.target.unwrap_signal(), driver);
+                    if let Some(pending) = self.pending.get_mut(&key) {
+                        pending.remove(&self.time);
+                        if pending.is_empty() {
+                            self.pending.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+        events.sort_by_key(|((_, driver), _)| *driver);
+        events
+            .into_iter()
+            .map(|((signal, driver), value)| (driver, signal, value))
+    }
+
+    /// Dequeue all timed instances due at the current time.
+    pub(crate) fn take_next_timed(&mut self) -> impl Iterator<Item = InstanceRef> {
+        if let Some(x) = self.timed.remove(&self.time) {
+            x.into_iter()
+        } else {
+            HashSet::new().into_iter()
+        }
+    }
+
+    /// Determine the time of the next simulation step. This is the lowest time
+    /// value of any event or wake up request in the schedule. If both the event
+    /// and timed instances queue are empty, None is returned.
+    pub(crate) fn next_time(&self) -> Option<TimeValue> {
+        use std::cmp::min;
+        match (self.events.keys().next(), self.timed.keys().next()) {
+            (Some(e), Some(t)) => Some(min(e, t).clone()),
+            (Some(e), None) => Some(e.clone()),
+            (None, Some(t)) => Some(t.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Find the hierarchical name of a signal, preferring the name closest to
+    /// the root of the hierarchy.
+    pub(crate) fn signal_name(&self, sig: SignalRef) -> Option<String> {
+        let mut scopes = vec![(&self.scope, self.scope.name.clone())];
+        while !scopes.is_empty() {
+            for (scope, path) in &scopes {
+                if let Some(names) = scope.probes.get(&sig) {
+                    return Some(format!("{}.{}", path, names[0]));
+                }
+            }
+            scopes = scopes
+                .into_iter()
+                .flat_map(|(scope, path)| {
+                    scope
+                        .subscopes
+                        .iter()
+                        .map(move |s| (s, format!("{}.{}", path, s.name)))
+                })
+                .collect();
+        }
+        None
+    }
+}
+
+impl Index<SignalRef> for State<'_> {
+    type Output = Signal;
+
+    fn index(&self, idx: SignalRef) -> &Self::Output {
+        &self.signals[idx.0]
+    }
+}
+
+impl IndexMut<SignalRef> for State<'_> {
+    fn index_mut(&mut self, idx: SignalRef) -> &mut Self::Output {
+        &mut self.signals[idx.0]
+    }
+}
+
+impl<'ll> Index<InstanceRef> for State<'ll> {
+    type Output = Mutex<Instance<'ll>>;
+
+    fn index(&self, idx: InstanceRef) -> &Self::Output {
+        &self.insts[idx.0]
+    }
+}
+
+impl IndexMut<InstanceRef> for State<'_> {
+    fn index_mut(&mut self, idx: InstanceRef) -> &mut Self::Output {
+        &mut self.insts[idx.0]
+    }
+}
+
+/// A unique handle to a signal in a simulation state.
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignalRef(usize);
+
+impl SignalRef {
+    /// Create a new signal reference.
+    pub fn new(id: usize) -> SignalRef {
+        SignalRef(id)
+    }
+
+    /// Return the underlying index of this reference.
+    pub fn as_usize(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Debug for SignalRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "s{}", self.0)
+    }
+}
+
+/// A signal in a simulation state.
+pub struct Signal {
+    ty: crate::Type,
+    value: Value,
+    resolution: Option<Resolution>,
+    /// The value driven by each driver of a resolved signal.
+    pub(crate) drivers: BTreeMap<Option<Driver>, Value>,
+}
+
+impl Signal {
+    /// Create a new signal.
+    pub fn new(ty: crate::Type, value: Value) -> Signal {
+        Self::new_resolved(ty, value, None)
+    }
+
+    /// Create a new signal whose drivers are combined with a resolution
+    /// function.
+    ///
+    /// The signal holds its initial value until it is first driven.
+    pub fn new_resolved(ty: crate::Type, value: Value, resolution: Option<Resolution>) -> Signal {
+        Signal {
+            ty: ty,
+            value: value,
+            resolution,
+            drivers: Default::default(),
+        }
+    }
+
+    /// Get the signal's resolution function.
+    pub fn resolution(&self) -> Option<Resolution> {
+        self.resolution
+    }
+
+    /// Change the signal's resolution function.
+    pub(crate) fn set_resolution(&mut self, resolution: Option<Resolution>) {
+        self.resolution = resolution;
+    }
+
+    /// Get the value a driver drives onto the signal.
+    ///
+    /// This is the current value of the signal, unless the signal is resolved.
+    pub fn driven_value(&self, driver: Option<Driver>) -> Value {
+        match self.resolution {
+            Some(resolution) => self
+                .drivers
+                .get(&driver)
+                .cloned()
+                .unwrap_or_else(|| Value::undriven(self.ty.unwrap_signal(), resolution)),
+            None => self.value.clone(),
+        }
+    }
+
+    /// Change the value a driver drives onto the signal. Returns whether the
+    /// signal's value changed.
+    ///
+    /// The value of a resolved signal is the resolution of the values of all
+    /// its drivers, otherwise the driven value replaces the signal's value.
+    pub fn drive(&mut self, driver: Option<Driver>, value: Value) -> bool {
+        let resolution = match self.resolution {
+            Some(resolution) => resolution,
+            None => return self.set_value(value),
+        };
+        self.drivers.insert(driver, value);
+        let mut values = self.drivers.values();
+        let first = values.next().unwrap().clone();
+        let resolved = values.fold(first, |acc, v| Value::resolve(resolution, &acc, v));
+        self.set_value(resolved)
+    }
+
+    /// Get the signal's type.
+    pub fn ty(&self) -> &crate::Type {
+        &self.ty
+    }
+
+    /// Get the signal's current value.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Change the signal's current value. Returns whether the values were
+    /// identical.
+    pub fn set_value(&mut self, value: Value) -> bool {
+        if self.value != value {
+            self.value = value;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// An instance of a process or entity.
+pub struct Instance<'ll> {
+    pub path: String,
+    pub values: HashMap<crate::ir::Value, ValueSlot>,
+    pub kind: InstanceKind<'ll>,
+    pub state: InstanceState,
+    pub signals: Vec<SignalRef>,
+    pub signal_values: HashMap<SignalRef, Vec<crate::ir::Value>>,
+    pub registers: HashMap<crate::ir::Inst, RegisterState>,
+    /// The register file used by the bytecode backend. Empty until the
+    /// instance is first executed by that backend, after which it holds the
+    /// current state of the values instead of `values`.
+    pub frame: Registers,
+}
+
+impl<'ll> Instance<'ll> {
+    /// Access an entry in this instance's value table.
+    pub fn value(&self, id: crate::ir::Value) -> &ValueSlot {
+        self.values.get(&id).unwrap()
+    }
+
+    /// Change an entry in this instance's value table.
+    pub fn set_value(&mut self, id: crate::ir::Value, value: ValueSlot) {
+        self.values.insert(id, value);
+    }
+
+    /// The current state of the instance's values, regardless of the backend
+    /// that executes the instance.
+    pub fn current_values(&self) -> HashMap<crate::ir::Value, ValueSlot> {
+        if self.frame.is_empty() {
+            self.values.clone()
+        } else {
+            self.frame.to_map()
+        }
+    }
+
+    /// Get the name of the entity or process.
+    pub fn name(&self) -> String {
+        match self.kind {
+            InstanceKind::Process { prok, .. } => prok.name().to_string(),
+            InstanceKind::Entity { entity, .. } => entity.name().to_string(),
+        }
+    }
+}
+
+/// The state of the values of a unit, indexed by their number.
+#[derive(Debug, Clone, Default)]
+pub struct Registers(pub Vec<Option<ValueSlot>>);
+
+impl Registers {
+    /// Create a register file from a value table.
+    pub fn from_map(size: usize, values: &HashMap<crate::ir::Value, ValueSlot>) -> Self {
+        let mut regs = Registers(vec![None; size]);
+        for (&id, slot) in values {
+            regs.set(id, slot.clone());
+        }
+        regs
+    }
+
+    /// Convert the register file into a value table.
+    pub fn to_map(&self) -> HashMap<crate::ir::Value, ValueSlot> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((crate::ir::Value::new(i), slot.clone()?)))
+            .collect()
+    }
+
+    /// Check whether the register file has not been allocated.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the state of a value.
+    pub fn get(&self, id: crate::ir::Value) -> Option<&ValueSlot> {
+        self.0.get(id.index()).and_then(Option::as_ref)
+    }
+
+    /// Change the state of a value.
+    pub fn set(&mut self, id: crate::ir::Value, slot: ValueSlot) {
+        if id.index() >= self.0.len() {
+            self.0.resize(id.index() + 1, None);
+        }
+        self.0[id.index()] = Some(slot);
+    }
+}
+
+/// A slot that carries a single value.
+///
+/// Slots are assigned to each entity in the LLHD graph that may carry a value.
+/// Execution of instructions change the value slots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueSlot {
+    /// A signal.
+    Signal(SignalRef),
+    /// A variable with its current value.
+    Variable(Value),
+    /// A constant value.
+    Const(Value),
+    /// A pointer to a variable.
+    VariablePointer(ValuePointer),
+    /// A pointer to a signal.
+    SignalPointer(ValuePointer),
+}
+
+impl fmt::Display for ValueSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueSlot::Signal(v) => fmt::Debug::fmt(v, f),
+            ValueSlot::Variable(v) => fmt::Display::fmt(v, f),
+            ValueSlot::Const(v) => fmt::Display::fmt(v, f),
+            ValueSlot::VariablePointer(v) => fmt::Display::fmt(v, f),
+            ValueSlot::SignalPointer(v) => fmt::Display::fmt(v, f),
+        }
+    }
+}
+
+/// A pointer to a value.
+///
+/// A `ValuePointer` represents a variable or signal that is either referenced
+/// in its entirety, or by selecting a subset of its elements, bits, or fields.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ValuePointer(pub Vec<ValueSlice>);
+
+impl fmt::Display for ValuePointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let single = self.0.len() == 1;
+        if !single {
+            write!(f, "[")?;
+        }
+        let mut first = true;
+        for s in &self.0 {
+            if f.alternate() && !single {
+                write!(f, "\n    ")?;
+            } else if !first {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", s)?;
+            first = false;
+        }
+        if !single {
+            if f.alternate() {
+                write!(f, "\n]")
+            } else {
+                write!(f, "]")
+            }
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl ValuePointer {
+    /// Compute the width of the pointed at value.
+    ///
+    /// Returns 0 if it is a struct.
+    pub fn width(&self) -> usize {
+        self.0.iter().map(|s| s.width).sum()
+    }
+
+    /// Get an iterator over the slices which tracks slice offsets.
+    pub fn offset_slices(&self) -> impl Iterator<Item = (usize, &ValueSlice)> {
+        let mut i = 0;
+        self.0.iter().map(move |s| {
+            let v = i;
+            i += s.width;
+            (v, s)
+        })
+    }
+}
+
+/// A slice of a pointer.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ValueSlice {
+    /// The targeted value, variable, or signal.
+    pub target: ValueTarget,
+    /// The selection into the target.
+    pub select: Vec<ValueSelect>,
+    /// The width of this slice, or 0 if it is a struct.
+    pub width: usize,
+}
+
+impl fmt::Display for ValueSlice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.target)?;
+        for s in &self.select {
+            write!(f, "{}", s)?;
+        }
+        Ok(())
+    }
+}
+
+impl ValueSlice {
+    /// Determine the location of the selected part within the target.
+    fn path(&self) -> SlicePath {
+        let mut fields = vec![];
+        let mut range: Option<(usize, usize)> = None;
+        for &select in &self.select {
+            let base = range.map_or(0, |(off, _)| off);
+            match select {
+                ValueSelect::Field(i) => {
+                    fields.push(base + i);
+                    range = None;
+                }
+                ValueSelect::Slice(off, len) => range = Some((base + off, len)),
+            }
+        }
+        if range.is_none() && self.width != 0 {
+            range = Some((0, self.width));
+        }
+        SlicePath { fields, range }
+    }
+
+    /// Select a field of the slice, which holds `value`.
+    fn select_field(&self, index: usize, value: &Value) -> ValueSlice {
+        let mut s = self.clone();
+        s.select.push(ValueSelect::Field(index));
+        s.width = match value {
+            Value::Int(v) => v.width(),
+            Value::Logic(v) => v.width(),
+            Value::Array(v) => v.len(),
+            _ => 0,
+        };
+        s
+    }
+
+    /// Select a range of elements or bits of the slice.
+    fn select_slice(&self, off: usize, len: usize) -> ValueSlice {
+        let mut s = self.clone();
+        s.select.push(ValueSelect::Slice(off, len));
+        s.width = len;
+        s
+    }
+}
+
+/// The location of a pointer slice within its target.
+///
+/// This is the chain of fields and array elements selected by the slice,
+/// followed by the `(offset, length)` range of elements or bits selected
+/// within the innermost value. The range is `None` if the innermost value is
+/// a struct, which is selected in its entirety.
+struct SlicePath {
+    fields: Vec<usize>,
+    range: Option<(usize, usize)>,
+}
+
+impl SlicePath {
+    /// Check whether two slices of the same target select a common part.
+    fn overlaps(&self, other: &SlicePath) -> bool {
+        let depth = std::cmp::min(self.fields.len(), other.fields.len());
+        if self.fields[..depth] != other.fields[..depth] {
+            return false;
+        }
+        match (self.range_at(depth), other.range_at(depth)) {
+            (Some((a, m)), Some((b, n))) => a < b + n && b < a + m,
+            _ => true,
+        }
+    }
+
+    /// Determine the range selected at a depth of the field chain.
+    fn range_at(&self, depth: usize) -> Option<(usize, usize)> {
+        match self.fields.get(depth) {
+            Some(&field) => Some((field, 1)),
+            None => self.range,
+        }
+    }
+}
+
+/// Extract the value for a slice at an offset within a pointer's value.
+fn extract_slice(value: &Value, off: usize, width: usize) -> Value {
+    if width == 0 {
+        return value.clone();
+    }
+    match value {
+        Value::Int(v) => v.extract_slice(off, width).into(),
+        Value::Logic(v) => v.extract_slice(off, width).into(),
+        Value::Array(v) => v.extract_slice(off, width).into(),
+        _ => panic!("cannot slice {} into {},{}", value, off, width),
+    }
+}
+
+/// Remove the part selected by `cut` from a transaction on an overlapping
+/// slice, and return the transactions for the parts that remain.
+fn subtract_slice(slice: ValueSlice, value: Value, cut: &SlicePath) -> Vec<(ValueSlice, Value)> {
+    let path = slice.path();
+    let depth = path.fields.len();
+    let inner = cut.fields.get(depth).copied();
+    let (cut_off, cut_len) = match cut.range_at(depth) {
+        Some(range) if cut.fields.len() >= depth => range,
+        _ => return vec![],
+    };
+    let mut parts = vec![];
+    match path.range {
+        Some((off, len)) => {
+            let start = std::cmp::max(off, cut_off);
+            let end = std::cmp::min(off + len, cut_off + cut_len);
+            if start > off {
+                let s = slice.select_slice(0, start - off);
+                parts.push((s, extract_slice(&value, 0, start - off)));
+            }
+            if let Some(index) = inner {
+                let field = match value {
+                    Value::Array(ref v) => v.extract_field(index - off),
+                    _ => panic!("access field {} in {}", index - off, value),
+                };
+                let s = slice.select_field(index - off, &field);
+                parts.extend(subtract_slice(s, field, cut));
+            }
+            if end < off + len {
+                let s = slice.select_slice(end - off, off + len - end);
+                parts.push((s, extract_slice(&value, end - off, off + len - end)));
+            }
+        }
+        None => {
+            for (index, field) in value.unwrap_struct().0.iter().enumerate() {
+                let s = slice.select_field(index, field);
+                if index != cut_off {
+                    parts.push((s, field.clone()));
+                } else {
+                    parts.extend(subtract_slice(s, field.clone(), cut));
+                }
+            }
+        }
+    }
+    parts
+}
+
+/// A pointer target.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ValueTarget {
+    Value(crate::ir::Value),
+    Variable(crate::ir::Value),
+    Signal(SignalRef),
+}
+
+impl fmt::Display for ValueTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueTarget::Value(v) => write!(f, "{}", v),
+            ValueTarget::Variable(v) => write!(f, "*{}", v),
+            ValueTarget::Signal(v) => write!(f, "${:?}", v),
+        }
+    }
+}
+
+impl ValueTarget {
+    /// Unwrap the underlying variable, or panic.
+    pub fn unwrap_variable(&self) -> crate::ir::Value {
+        match *self {
+            ValueTarget::Variable(v) => v,
+            _ => panic!("value target is not a variable"),
+        }
+    }
+
+    /// Unwrap the underlying signal, or panic.
+    pub fn unwrap_signal(&self) -> SignalRef {
+        match *self {
+            ValueTarget::Signal(v) => v,
+            _ => panic!("value target is not a signal"),
+        }
+    }
+}
+
+/// A selection of a part of a value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ValueSelect {
+    /// An individual array element or struct field.
+    Field(usize),
+    /// A slice of array elements or integer bits, given by `(offset, length)`.
+    Slice(usize, usize),
+}
+
+impl fmt::Display for ValueSelect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueSelect::Field(i) => write!(f, ".{}", i),
+            ValueSelect::Slice(o, l) => write!(f, "[{}+:{}]", o, l),
+        }
+    }
+}
+
+/// An instantiation.
+pub enum InstanceKind<'ll> {
+    Process {
+        prok: crate::ir::Unit<'ll>,
+        next_block: Option<crate::ir::Block>,
+        prev_block: Option<crate::ir::Block>,
+    },
+    Entity {
+        entity: crate::ir::Unit<'ll>,
+    },
+}
+
+/// The trigger values observed by a `reg` instruction.
+///
+/// Edge-sensitive triggers compare the current trigger values against the ones
+/// observed in the previous simulation step. Since an entity may re-evaluate a
+/// `reg` several times within the same step as its operands settle, the values
+/// of the step before are kept around until time advances.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterState {
+    /// The time at which the current trigger values were observed.
+    pub time: TimeValue,
+    /// The trigger values observed before `time`.
+    pub prev: Vec<Value>,
+    /// The trigger values observed at `time`.
+    pub curr: Vec<Value>,
+}
+
+/// The state an instance can be in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstanceState {
+    Ready,
+    Wait(Option<TimeValue>, Vec<SignalRef>),
+    Done,
+}
+
+/// A unique reference to an instance in the simulation.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InstanceRef(usize);
+
+impl InstanceRef {
+    /// Create a new instance reference.
+    pub fn new(id: usize) -> InstanceRef {
+        InstanceRef(id)
+    }
+}
+
+/// A driver of a signal.
+///
+/// A process drives each signal with a single driver, whereas each drive
+/// instruction of an entity is a separate driver, as for concurrent statements.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Driver {
+    /// The driving instance.
+    pub inst: InstanceRef,
+    /// The drive instruction, if the instance is an entity.
+    pub drive: Option<crate::ir::Inst>,
+}
+
+/// An event that can be scheduled in a binary heap, forming an event queue. The
+/// largest element, i.e. the one at the top of the heap, is the one with the
+/// lowest time value.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Event {
+    pub time: TimeValue,
+    pub signal: ValuePointer,
+    pub value: Value,
+    pub mode: DelayMode,
+    /// The drive instruction, if each drive of the instance is a separate
+    /// driver.
+    pub drive: Option<crate::ir::Inst>,
+}
+
+impl Ord for Event {
+    fn cmp(&self, rhs: &Event) -> Ordering {
+        match self.time.cmp(&rhs.time) {
+            Ordering::Equal => self.signal.cmp(&rhs.signal),
+            Ordering::Greater => Ordering::Less,
+            Ordering::Less => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, rhs: &Event) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+/// A notice that an instance is in a wait state and wants to be resumed once a
+/// certain simulation time has been reached. TimedInstance objects can be
+/// scheduled in a binary heap, which forms a wake up queue. The largest
+/// element, i.e. the one at the top of the heap, is the one with the lowest
+/// time value.
+#[derive(Debug, Eq, PartialEq)]
+pub struct TimedInstance {
+    pub time: TimeValue,
+    pub inst: InstanceRef,
+}
+
+impl Ord for TimedInstance {
+    fn cmp(&self, rhs: &TimedInstance) -> Ordering {
+        match self.time.cmp(&rhs.time) {
+            Ordering::Equal => self.inst.cmp(&rhs.inst),
+            Ordering::Greater => Ordering::Less,
+            Ordering::Less => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for TimedInstance {
+    fn partial_cmp(&self, rhs: &TimedInstance) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+/// A level of hierarchy.
+///
+/// The scope represents the hierarchy of a design. Each instantiation or
+/// process creates a new subscope with its own set of probes.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Scope {
+    /// The name of the scope.
+    pub name: String,
+    /// The probes in this scope.
+    pub probes: HashMap<SignalRef, Vec<String>>,
+    /// The subscopes.
+    pub subscopes: Vec<Scope>,
+}
+
+impl Scope {
+    /// Create a new empty scope.
+    pub fn new(name: impl Into<String>) -> Scope {
+        Scope {
+            name: name.into(),
+            probes: Default::default(),
+            subscopes: vec![],
+        }
+    }
+
+    /// Add a subscope.
+    pub fn add_subscope(&mut self, scope: Scope) {
+        self.subscopes.push(scope);
+    }
+
+    /// Add a probe.
+    pub fn add_probe(&mut self, signal: SignalRef, name: String) {
+        self.probes.entry(signal).or_insert(Vec::new()).push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::value::{ArrayValue, IntValue, StructValue};
+
+    fn int(width: usize, value: usize) -> Value {
+        IntValue::from_usize(width, value).into()
+    }
+
+    fn slice(select: Vec<ValueSelect>, width: usize) -> ValueSlice {
+        ValueSlice {
+            target: ValueTarget::Signal(SignalRef::new(0)),
+            select,
+            width,
+        }
+    }
+
+    #[test]
+    fn subtract_bits() {
+        let cut = slice(vec![ValueSelect::Slice(2, 4)], 4).path();
+        let parts = subtract_slice(slice(vec![], 8), int(8, 0b10110111), &cut);
+        assert_eq!(
+            parts,
+            vec![
+                (slice(vec![ValueSelect::Slice(0, 2)], 2), int(2, 0b11)),
+                (slice(vec![ValueSelect::Slice(6, 2)], 2), int(2, 0b10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn subtract_fields() {
+        // A struct of an integer and an array of two integers, of which the
+        // lower half of the second array element is cut.
+        let array = ArrayValue::new(vec![int(4, 3), int(4, 12)]);
+        let value = StructValue::new(vec![int(1, 1), array.into()]);
+        let cut = slice(
+            vec![
+                ValueSelect::Field(1),
+                ValueSelect::Slice(1, 1),
+                ValueSelect::Field(0),
+                ValueSelect::Slice(0, 2),
+            ],
+            2,
+        )
+        .path();
+        assert!(cut.overlaps(&slice(vec![], 0).path()));
+        assert!(!cut.overlaps(&slice(vec![ValueSelect::Field(0)], 1).path()));
+        let parts = subtract_slice(slice(vec![], 0), value.into(), &cut);
+        let field = ValueSelect::Field;
+        assert_eq!(
+            parts,
+            vec![
+                (slice(vec![field(0)], 1), int(1, 1)),
+                (
+                    slice(vec![field(1), ValueSelect::Slice(0, 1)], 1),
+                    ArrayValue::new(vec![int(4, 3)]).into()
+                ),
+                (
+                    slice(vec![field(1), field(1), ValueSelect::Slice(2, 2)], 2),
+                    int(2, 3)
+                ),
+            ]
+        );
+    }
+}
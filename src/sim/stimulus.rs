@@ -0,0 +1,293 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Stimuli for the inputs of a design
+//!
+//! A stimulus is a list of values that are applied to named signals at given
+//! points in time. It is usually read from a file and applied to the ports of
+//! the top-level unit of a simulation, which allows a design to be exercised
+//! without writing a dedicated testbench.
+//!
+//! Two formats are supported:
+//!
+//! - A CSV table whose header row names the signals, and whose first column
+//!   contains the time at which the values in the remaining columns are
+//!   applied. Empty cells leave the signal unchanged.
+//!
+//!   ```text
+//!   time, a, b
+//!   0ns,  0, 0x10
+//!   5ns,  1,
+//!   10ns, 0, 0x20
+//!   ```
+//!
+//! - A VCD file, as produced by the simulator itself or other tools. Variables
+//!   are named by their enclosing scopes and their name, separated by periods.
+//!   Values are applied as binary literals. `x` and `z` bits are applied as
+//!   `X` and `Z` to logic signals, and to integer signals if X propagation is
+//!   enabled; applying them to other integer signals is an error.
+
+use crate::sim::value::TimeValue;
+use num::{BigInt, BigRational, One, Zero};
+use std::collections::HashMap;
+
+/// A list of values to be applied to signals over time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stimulus {
+    changes: Vec<Change>,
+}
+
+/// A single value applied to a signal in a stimulus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// The time at which the value is applied.
+    pub time: TimeValue,
+    /// The name of the signal, either hierarchical or relative to the root.
+    pub signal: String,
+    /// The value to be applied, as accepted by `Value::parse`.
+    pub value: String,
+}
+
+impl Stimulus {
+    /// Create an empty stimulus.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Apply a value to a signal at a given time.
+    pub fn add(&mut self, time: TimeValue, signal: impl Into<String>, value: impl Into<String>) {
+        self.changes.push(Change {
+            time,
+            signal: signal.into(),
+            value: value.into(),
+        });
+    }
+
+    /// The changes in this stimulus, in the order they were added.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Parse a stimulus from a CSV time-value table.
+    pub fn parse_csv(input: &str) -> Result<Self, String> {
+        let mut stimulus = Self::new();
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        // Parse the header, which names the signals.
+        let signals: Vec<_> = match lines.next() {
+            Some((_, line)) => line.split(',').skip(1).map(str::trim).collect(),
+            None => return Ok(stimulus),
+        };
+        if let Some(i) = signals.iter().position(|s| s.is_empty()) {
+            return Err(format!("column {} has no signal name", i + 2));
+        }
+
+        // Parse the values.
+        for (line_number, line) in lines {
+            let mut cells = line.split(',').map(str::trim);
+            let time = cells.next().unwrap_or("");
+            let time = crate::assembly::parse_time(time)
+                .map_err(|_| format!("line {}: invalid time `{}`", line_number, time))?;
+            let mut count = 0;
+            for (signal, cell) in signals.iter().zip(&mut cells) {
+                count += 1;
+                if !cell.is_empty() {
+                    stimulus.add(time.clone(), *signal, cell);
+                }
+            }
+            if count != signals.len() || cells.next().is_some() {
+                return Err(format!(
+                    "line {}: expected {} values",
+                    line_number,
+                    signals.len()
+                ));
+            }
+        }
+        Ok(stimulus)
+    }
+
+    /// Parse a stimulus from a VCD file.
+    pub fn parse_vcd(input: &str) -> Result<Self, String> {
+        let mut stimulus = Self::new();
+        read_vcd(input, |time, names, bits| {
+            let value: String = bits
+                .chars()
+                .map(|c| match c {
+                    '0' | '1' => Ok(c),
+                    'x' | 'X' => Ok('X'),
+                    'z' | 'Z' => Ok('Z'),
+                    _ => Err(format!("invalid bits `{}`", bits)),
+                })
+                .collect::<Result<_, _>>()?;
+            for name in names {
+                stimulus.add(time.clone(), name.clone(), format!("0b{}", value));
+            }
+            Ok(())
+        })?;
+        Ok(stimulus)
+    }
+}
+
+/// Read the value changes of a VCD file.
+///
+/// Calls `change` with the time, the hierarchical names of the variable, and
+/// its new bits for every value change in the file. Names are formed of the
+/// enclosing scopes and the variable name, separated by periods.
+pub(crate) fn read_vcd(
+    input: &str,
+    mut change: impl FnMut(&TimeValue, &[String], &str) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut tokens = input.split_whitespace();
+    let mut scale = BigRational::one();
+    let mut scopes: Vec<&str> = vec![];
+    let mut vars: HashMap<&str, Vec<String>> = HashMap::new();
+    let mut time = TimeValue::zero();
+
+    // Consume the tokens up to the next `$end`.
+    fn until_end<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vec<&'a str>, String> {
+        let mut body = vec![];
+        loop {
+            match tokens.next() {
+                Some("$end") => return Ok(body),
+                Some(token) => body.push(token),
+                None => return Err("missing `$end`".to_string()),
+            }
+        }
+    }
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "$timescale" => scale = parse_timescale(&until_end(&mut tokens)?.concat())?,
+            "$scope" => match until_end(&mut tokens)?.as_slice() {
+                [_, name] => scopes.push(name),
+                _ => return Err("invalid `$scope`".to_string()),
+            },
+            "$upscope" => {
+                until_end(&mut tokens)?;
+                scopes.pop();
+            }
+            "$var" => match until_end(&mut tokens)?.as_slice() {
+                [_, _, id, name, ..] => {
+                    let mut path = scopes.join(".");
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(name);
+                    vars.entry(id).or_default().push(path);
+                }
+                _ => return Err("invalid `$var`".to_string()),
+            },
+            "$dumpvars" | "$dumpall" | "$dumpon" | "$dumpoff" | "$end" => (),
+            _ if token.starts_with('$') => {
+                until_end(&mut tokens)?;
+            }
+            _ if token.starts_with('#') => {
+                let ticks = BigInt::parse_bytes(&token.as_bytes()[1..], 10)
+                    .ok_or_else(|| format!("invalid time `{}`", token))?;
+                time = TimeValue::new(BigRational::from(ticks) * &scale, 0, 0);
+            }
+            _ => {
+                let (bits, id) = match token.as_bytes()[0] {
+                    b'b' | b'B' => {
+                        let id = tokens
+                            .next()
+                            .ok_or_else(|| format!("missing identifier after `{}`", token))?;
+                        (&token[1..], id)
+                    }
+                    b'0' | b'1' | b'x' | b'X' | b'z' | b'Z' => token.split_at(1),
+                    _ => return Err(format!("unsupported value change `{}`", token)),
+                };
+                let names = vars
+                    .get(id)
+                    .ok_or_else(|| format!("unknown identifier `{}`", id))?;
+                change(&time, names, bits)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a VCD timescale such as `1ps` or `10 ns` into seconds.
+fn parse_timescale(input: &str) -> Result<BigRational, String> {
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: BigInt = number
+        .parse()
+        .map_err(|_| format!("invalid timescale `{}`", input))?;
+    let exp = match unit {
+        "s" => 0,
+        "ms" => 3,
+        "us" => 6,
+        "ns" => 9,
+        "ps" => 12,
+        "fs" => 15,
+        _ => return Err(format!("invalid timescale `{}`", input)),
+    };
+    if number.is_zero() {
+        return Err(format!("invalid timescale `{}`", input));
+    }
+    Ok(BigRational::new(number, BigInt::from(10).pow(exp)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> TimeValue {
+        crate::assembly::parse_time(s).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        let stimulus = Stimulus::parse_csv(
+            "
+            time, a, @top.b
+            # comment
+            0ns, 1, 0x10
+            2ns, , 5
+            ",
+        )
+        .unwrap();
+        let mut expected = Stimulus::new();
+        expected.add(time("0ns"), "a", "1");
+        expected.add(time("0ns"), "@top.b", "0x10");
+        expected.add(time("2ns"), "@top.b", "5");
+        assert_eq!(stimulus, expected);
+        assert!(Stimulus::parse_csv("time, a\n0ns, 1, 2\n").is_err());
+        assert!(Stimulus::parse_csv("time, a\nfoo, 1\n").is_err());
+    }
+
+    #[test]
+    fn vcd() {
+        let stimulus = Stimulus::parse_vcd(
+            "
+            $timescale 10 ns $end
+            $scope module @top $end
+            $var wire 1 ! clk $end
+            $var wire 4 \" data [3:0] $end
+            $upscope $end
+            $enddefinitions $end
+            #0
+            $dumpvars
+            0!
+            bxx01 \"
+            $end
+            #3
+            1!
+            bz \"
+            ",
+        )
+        .unwrap();
+        let mut expected = Stimulus::new();
+        expected.add(time("0ns"), "@top.clk", "0b0");
+        expected.add(time("0ns"), "@top.data", "0bXX01");
+        expected.add(time("30ns"), "@top.clk", "0b1");
+        expected.add(time("30ns"), "@top.data", "0bZ");
+        assert_eq!(stimulus, expected);
+    }
+}
@@ -0,0 +1,434 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Comparison of simulation traces
+//!
+//! A trace records the value changes of the signals of a simulation, as written
+//! by `DumpTracer` or `VcdTracer`. Comparing two traces reports the first point
+//! in time at which each signal differs, which is useful to check that a
+//! transformation of a design, such as an optimization, does not change its
+//! behaviour.
+//!
+//! Signals are named by their enclosing scopes and their name, separated by
+//! periods and without the leading `@` of unit names, for example
+//! `top.counter.count`. Arrays and structs are split into their elements, as
+//! in VCD files, such that `mem[3]` or `bus.1` refer to a single integer.
+//! Integer values are compared numerically and formatted in hexadecimal.
+//! Logic values are reduced to the four states of VCD, `0`, `1`, `x`, and `z`,
+//! and are formatted like integers if all their bits are `0` or `1`.
+//!
+//! ```
+//! use llhd::sim::tracediff::{diff, DiffOptions, Trace};
+//!
+//! let before = Trace::parse("0ps 0d 0e\n5000ps 0d 0e\n  top/q = 0x1\n").unwrap();
+//! let after = Trace::parse("0ps 0d 0e\n5000ps 1d 0e\n  top/q = 0x2\n").unwrap();
+//! let divergences = diff(&before, &after, &DiffOptions::new());
+//! assert_eq!(divergences[0].to_string(), "top.q differs at 5ns: 0x1 != none");
+//! ```
+//!
+//! Dump traces only record a signal once it changes, whereas VCD traces also
+//! record the initial value of every signal. Traces are therefore best compared
+//! against traces in the same format.
+
+use crate::{
+    sim::{stimulus::read_vcd, tracer::glob_regex, value::TimeValue},
+    value::LogicBit,
+};
+use num::{BigInt, BigRational, BigUint};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// The value changes of the signals of a simulation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    signals: BTreeMap<String, Vec<(TimeValue, String)>>,
+}
+
+impl Trace {
+    /// Create an empty trace.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Parse a trace in either the dump or the VCD format.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        if input.trim_start().starts_with('$') {
+            Self::parse_vcd(input)
+        } else {
+            Self::parse_dump(input)
+        }
+    }
+
+    /// Parse a trace written by `DumpTracer`.
+    pub fn parse_dump(input: &str) -> Result<Self, String> {
+        let mut trace = Self::new();
+        let mut time = None;
+        for (i, line) in input.lines().enumerate() {
+            let error = |msg: String| format!("line {}: {}", i + 1, msg);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Lines that are not indented start a new time step.
+            if !line.starts_with(' ') {
+                time = Some(parse_dump_time(line).map_err(error)?);
+                continue;
+            }
+            let time = time
+                .as_ref()
+                .ok_or_else(|| error("value change before the first time step".to_string()))?;
+            let (name, value) = line
+                .split_once(" = ")
+                .ok_or_else(|| error(format!("invalid value change `{}`", line.trim())))?;
+            let name = name.trim().replace('/', ".");
+            let mut leaves = vec![];
+            let rest = parse_dump_value(value, name, &mut leaves).map_err(error)?;
+            if !rest.trim().is_empty() {
+                return Err(error(format!("trailing characters `{}`", rest.trim())));
+            }
+            for (name, value) in leaves {
+                trace.add(time.clone(), name, value);
+            }
+        }
+        Ok(trace)
+    }
+
+    /// Parse a trace from a VCD file.
+    pub fn parse_vcd(input: &str) -> Result<Self, String> {
+        let mut trace = Self::new();
+        read_vcd(input, |time, names, bits| {
+            let value = format_bits(&bits.to_lowercase());
+            for name in names {
+                let name: Vec<_> = name.split('.').map(|s| s.trim_start_matches('@')).collect();
+                trace.add(time.clone(), name.join("."), value.clone());
+            }
+            Ok(())
+        })?;
+        Ok(trace)
+    }
+
+    /// Record a change of the value of a signal.
+    ///
+    /// Changes of a signal must be added in the order of their time.
+    pub fn add(&mut self, time: TimeValue, signal: impl Into<String>, value: impl Into<String>) {
+        self.signals
+            .entry(signal.into())
+            .or_default()
+            .push((time, value.into()));
+    }
+
+    /// The names of the signals in the trace.
+    pub fn signals(&self) -> impl Iterator<Item = &str> {
+        self.signals.keys().map(String::as_str)
+    }
+
+    /// The changes of a signal, in the order of their time.
+    pub fn changes(&self, signal: &str) -> &[(TimeValue, String)] {
+        self.signals.get(signal).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// Parse the time step of a dump trace, such as `5000ps 1d 0e`.
+fn parse_dump_time(line: &str) -> Result<TimeValue, String> {
+    let invalid = || format!("invalid time `{}`", line);
+    let mut parts = line.split_whitespace();
+    let mut part = |suffix: &str| {
+        parts
+            .next()
+            .and_then(|p| p.strip_suffix(suffix))
+            .ok_or_else(invalid)
+    };
+    let time: BigInt = part("ps")?.parse().map_err(|_| invalid())?;
+    let delta = part("d")?.parse().map_err(|_| invalid())?;
+    let epsilon = part("e")?.parse().map_err(|_| invalid())?;
+    let time = BigRational::new(time, BigInt::from(10).pow(12));
+    Ok(TimeValue::new(time, delta, epsilon))
+}
+
+/// Parse a value of a dump trace, and split it into its integer elements.
+///
+/// Returns the remainder of the input.
+fn parse_dump_value<'a>(
+    input: &'a str,
+    name: String,
+    leaves: &mut Vec<(String, String)>,
+) -> Result<&'a str, String> {
+    let input = input.trim_start();
+    if let Some(rest) = input.strip_prefix("0x") {
+        let end = rest
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(rest.len());
+        let value = BigUint::parse_bytes(&rest.as_bytes()[..end], 16)
+            .ok_or_else(|| format!("invalid value `{}`", input))?;
+        leaves.push((name, format!("0x{:x}", value)));
+        return Ok(&rest[end..]);
+    }
+    if let Some(rest) = input.strip_prefix("0b") {
+        let end = rest
+            .find(|c: char| LogicBit::from_char(c).is_none())
+            .unwrap_or(rest.len());
+        let bits: String = rest[..end]
+            .chars()
+            .map(|c| LogicBit::from_char(c).unwrap().to_vcd_char())
+            .collect();
+        leaves.push((name, format_bits(&bits)));
+        return Ok(&rest[end..]);
+    }
+    let (close, is_array) = match input.chars().next() {
+        Some('[') => (']', true),
+        Some('{') => ('}', false),
+        _ => return Err(format!("invalid value `{}`", input)),
+    };
+    let mut rest = &input[1..];
+    for index in 0.. {
+        rest = rest.trim_start();
+        if let Some(rest) = rest.strip_prefix(close) {
+            return Ok(rest);
+        }
+        if index > 0 {
+            rest = rest
+                .strip_prefix(',')
+                .ok_or_else(|| format!("expected `,` or `{}`", close))?;
+        }
+        let name = if is_array {
+            format!("{}[{}]", name, index)
+        } else {
+            format!("{}.{}", name, index)
+        };
+        rest = parse_dump_value(rest, name, leaves)?;
+    }
+    unreachable!()
+}
+
+/// Format the bits of a value, given as `0`, `1`, `x`, and `z` with the most
+/// significant bit first.
+fn format_bits(bits: &str) -> String {
+    if bits.chars().all(|c| c == '0' || c == '1') {
+        format!(
+            "0x{:x}",
+            BigUint::parse_bytes(bits.as_bytes(), 2).unwrap_or_default()
+        )
+    } else {
+        format!("0b{}", bits)
+    }
+}
+
+/// Options for the comparison of two traces.
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    ignore_deltas: bool,
+    include: Vec<regex::Regex>,
+    exclude: Vec<regex::Regex>,
+}
+
+impl DiffOptions {
+    /// Create the options to compare all signals, including their delta cycles.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Only compare the value of signals at the end of each physical time step.
+    ///
+    /// Changes that occur in different delta or epsilon steps of the same time
+    /// step are not reported, and neither are glitches within a time step.
+    pub fn set_ignore_deltas(&mut self, ignore: bool) {
+        self.ignore_deltas = ignore;
+    }
+
+    /// Compare only the signals whose name matches a glob pattern.
+    ///
+    /// Patterns are matched as in `ScopeFilter`. Without any such patterns all
+    /// signals are compared.
+    pub fn add_include(&mut self, pattern: &str) {
+        self.include.push(glob_regex(pattern));
+    }
+
+    /// Do not compare the signals whose name matches a glob pattern.
+    pub fn add_exclude(&mut self, pattern: &str) {
+        self.exclude.push(glob_regex(pattern));
+    }
+
+    /// Check whether a signal is compared.
+    fn is_selected(&self, signal: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.is_match(signal)))
+            && !self.exclude.iter().any(|p| p.is_match(signal))
+    }
+}
+
+/// The first difference of a signal between two traces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The name of the signal.
+    pub signal: String,
+    /// The time at which the signal first differs.
+    pub time: TimeValue,
+    /// The value of the signal in the left trace, if it has been recorded.
+    pub left: Option<String>,
+    /// The value of the signal in the right trace, if it has been recorded.
+    pub right: Option<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} differs at {}: {} != {}",
+            self.signal,
+            self.time,
+            self.left.as_deref().unwrap_or("none"),
+            self.right.as_deref().unwrap_or("none")
+        )
+    }
+}
+
+/// Compare two traces.
+///
+/// Returns the first divergence of each signal that differs, ordered by time.
+/// A signal that only occurs in one trace has no value in the other.
+pub fn diff(left: &Trace, right: &Trace, options: &DiffOptions) -> Vec<Divergence> {
+    let signals: BTreeSet<_> = left.signals().chain(right.signals()).collect();
+    let mut divergences: Vec<_> = signals
+        .into_iter()
+        .filter(|signal| options.is_selected(signal))
+        .flat_map(|signal| {
+            diff_signal(left.changes(signal), right.changes(signal), options).map(
+                |(time, left, right)| Divergence {
+                    signal: signal.to_string(),
+                    time,
+                    left: left.map(String::from),
+                    right: right.map(String::from),
+                },
+            )
+        })
+        .collect();
+    divergences.sort_by(|a, b| a.time.cmp(&b.time));
+    divergences
+}
+
+/// Find the first difference between the changes of a signal.
+fn diff_signal<'a>(
+    left: &'a [(TimeValue, String)],
+    right: &'a [(TimeValue, String)],
+    options: &DiffOptions,
+) -> Option<(TimeValue, Option<&'a str>, Option<&'a str>)> {
+    let step = |time: &TimeValue| {
+        if options.ignore_deltas {
+            TimeValue::new(time.time().clone(), 0, 0)
+        } else {
+            time.clone()
+        }
+    };
+    let (mut left, mut right) = (left.iter().peekable(), right.iter().peekable());
+    let (mut left_value, mut right_value) = (None, None);
+    loop {
+        // Advance both traces to the end of the next time step.
+        let time = match (left.peek(), right.peek()) {
+            (None, None) => return None,
+            (Some((l, _)), None) => step(l),
+            (None, Some((r, _))) => step(r),
+            (Some((l, _)), Some((r, _))) => std::cmp::min(step(l), step(r)),
+        };
+        while let Some((_, value)) = left.next_if(|(t, _)| step(t) == time) {
+            left_value = Some(value.as_str());
+        }
+        while let Some((_, value)) = right.next_if(|(t, _)| step(t) == time) {
+            right_value = Some(value.as_str());
+        }
+        if left_value != right_value {
+            return Some((time, left_value, right_value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> TimeValue {
+        crate::assembly::parse_time(s).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let dump = Trace::parse(
+            "0ps 0d 0e\n\
+             2000ps 1d 0e\n  \
+               top/sub/x = [0x01, 0x2]\n  \
+               top/y = {0x0, [0xa]}\n  \
+               top/z = [0bHL01, 0bUZ-1]\n",
+        )
+        .unwrap();
+        let mut expected = Trace::new();
+        expected.add(time("2ns 1d"), "top.sub.x[0]", "0x1");
+        expected.add(time("2ns 1d"), "top.sub.x[1]", "0x2");
+        expected.add(time("2ns 1d"), "top.y.0", "0x0");
+        expected.add(time("2ns 1d"), "top.y.1[0]", "0xa");
+        expected.add(time("2ns 1d"), "top.z[0]", "0x9");
+        expected.add(time("2ns 1d"), "top.z[1]", "0bxzx1");
+        assert_eq!(dump, expected);
+        assert!(Trace::parse("  top/y = 0x1\n").is_err());
+        assert!(Trace::parse("0ps 0d 0e\n  top/y = [0x1\n").is_err());
+
+        let vcd = Trace::parse(
+            "
+            $timescale 1ns $end
+            $scope module @top $end
+            $var wire 4 ! data $end
+            $upscope $end
+            $enddefinitions $end
+            $dumpvars
+            b1010 !
+            $end
+            #2
+            bx1 !
+            ",
+        )
+        .unwrap();
+        let mut expected = Trace::new();
+        expected.add(time("0s"), "top.data", "0xa");
+        expected.add(time("2ns"), "top.data", "0bx1");
+        assert_eq!(vcd, expected);
+    }
+
+    #[test]
+    fn divergences() {
+        let mut left = Trace::new();
+        left.add(time("1ns"), "a", "0x1");
+        left.add(time("1ns 1d"), "a", "0x2");
+        left.add(time("1ns"), "b", "0x1");
+        left.add(time("3ns"), "b", "0x0");
+        left.add(time("1ns"), "c", "0x1");
+        let mut right = Trace::new();
+        right.add(time("1ns 2d"), "a", "0x2");
+        right.add(time("1ns"), "b", "0x1");
+        right.add(time("2ns"), "b", "0x0");
+
+        let mut options = DiffOptions::new();
+        let found: Vec<_> = diff(&left, &right, &options)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                "a differs at 1ns: 0x1 != none",
+                "c differs at 1ns: 0x1 != none",
+                "b differs at 2ns: 0x1 != 0x0",
+            ]
+        );
+
+        options.set_ignore_deltas(true);
+        options.add_exclude("c");
+        let found: Vec<_> = diff(&left, &right, &options)
+            .into_iter()
+            .map(|d| d.signal)
+            .collect();
+        assert_eq!(found, vec!["b"]);
+
+        let mut options = DiffOptions::new();
+        options.add_include("?");
+        options.add_exclude("a");
+        assert_eq!(diff(&left, &right, &options).len(), 2);
+    }
+}
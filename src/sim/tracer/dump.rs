@@ -2,7 +2,7 @@
 
 //! A simple tracer for debugging and regression testing.
 
-use crate::{
+use crate::sim::{
     state::{Scope, SignalRef, State},
     tracer::Tracer,
     value::Value,
@@ -38,14 +38,20 @@ where
         match value {
             Value::Void => (),
             Value::Int(v) => {
-                write!(self.writer, "0x{0:01$x}", v.value, (v.width + 3) / 4).unwrap();
+                write!(self.writer, "0x{0:01$x}", v, v.width().div_ceil(4)).unwrap();
+            }
+            Value::Logic(v) => {
+                write!(self.writer, "0b").unwrap();
+                for bit in v.0.iter().rev() {
+                    write!(self.writer, "{}", bit).unwrap();
+                }
             }
             Value::Time(_) => (),
             Value::Array(v) => {
                 write!(self.writer, "[").unwrap();
-                for (elem, sep) in v.0.iter().zip(once("").chain(repeat(", "))) {
+                for (elem, sep) in v.iter().zip(once("").chain(repeat(", "))) {
                     write!(self.writer, "{}", sep).unwrap();
-                    self.write_value(elem);
+                    self.write_value(&elem);
                 }
                 write!(self.writer, "]").unwrap();
             }
@@ -0,0 +1,586 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! A Fast Signal Trace tracer.
+//!
+//! FST is the compressed waveform format of GTKWave. A file consists of a
+//! header, a sequence of independently compressed value change blocks, and a
+//! trailing description of the signal geometry and hierarchy. Since each block
+//! covers a contiguous range of time, readers can seek to a point in time
+//! without decompressing the entire trace.
+
+use crate::sim::{
+    state::{Scope, SignalRef, State},
+    tracer::{ScopeFilter, Timescale, Tracer},
+    value::Value,
+};
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use num::{BigRational, ToPrimitive};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Seek, SeekFrom, Write},
+};
+
+/// The number of bytes of value changes after which a block is written.
+const BLOCK_SIZE: usize = 1 << 20;
+
+// Block types.
+const BL_HDR: u8 = 0;
+const BL_VCDATA: u8 = 1;
+const BL_GEOM: u8 = 3;
+const BL_HIER: u8 = 4;
+
+// Hierarchy entries.
+const ST_VCD_MODULE: u8 = 0;
+const ST_VCD_SCOPE: u8 = 254;
+const ST_VCD_UPSCOPE: u8 = 255;
+const VT_VCD_WIRE: u8 = 16;
+const VD_IMPLICIT: u8 = 0;
+
+/// A tracer that emits the simulation trace as FST.
+pub struct FstTracer<T> {
+    writer: T,
+    /// The position of the header in the output.
+    header_pos: u64,
+    /// The variables of each signal, as handle and offset into the signal.
+    vars: HashMap<SignalRef, Vec<(usize, usize)>>,
+    /// The width of each variable.
+    widths: Vec<usize>,
+    /// The current value of each variable, as a string of `0` and `1`, or of
+    /// the lowercase characters of nine-valued logic.
+    values: Vec<Vec<u8>>,
+    hierarchy: Vec<u8>,
+    num_scopes: u64,
+    num_vars: u64,
+    time: BigRational,
+    pending: HashMap<SignalRef, Value>,
+    timescale: Timescale,
+    filter: ScopeFilter,
+    block: Block,
+    num_blocks: u64,
+    start_time: u64,
+    end_time: u64,
+}
+
+/// The value changes of a range of time that have not yet been written.
+#[derive(Default)]
+struct Block {
+    /// The time at which the block begins.
+    begin: Option<u64>,
+    /// The value of each variable at the beginning of the block.
+    frame: Vec<u8>,
+    /// The points in time at which values changed.
+    times: Vec<u64>,
+    /// The encoded value changes of each variable.
+    waves: Vec<Vec<u8>>,
+    /// The index into `times` of the last change of each variable.
+    last: Vec<usize>,
+    /// The total size of the encoded value changes.
+    size: usize,
+}
+
+impl<T> FstTracer<T>
+where
+    T: Write + Seek,
+{
+    /// Create a new FST tracer which will write its FST to `writer`.
+    pub fn new(writer: T) -> Self {
+        FstTracer {
+            writer,
+            header_pos: 0,
+            vars: HashMap::new(),
+            widths: vec![],
+            values: vec![],
+            hierarchy: vec![],
+            num_scopes: 0,
+            num_vars: 0,
+            time: num::zero(),
+            pending: HashMap::new(),
+            timescale: Default::default(),
+            filter: Default::default(),
+            block: Block::default(),
+            num_blocks: 0,
+            start_time: 0,
+            end_time: 0,
+        }
+    }
+
+    /// Change the unit in which time is recorded. Defaults to picoseconds.
+    pub fn set_timescale(&mut self, timescale: Timescale) {
+        self.timescale = timescale;
+    }
+
+    /// Only trace the scopes selected by a filter. Defaults to all scopes.
+    pub fn set_filter(&mut self, filter: ScopeFilter) {
+        self.filter = filter;
+    }
+
+    /// Convert the current time to a number of time units.
+    fn ticks(&self) -> u64 {
+        self.timescale.ticks(&self.time).to_u64().unwrap()
+    }
+
+    /// Start a new block with the current values of all variables.
+    fn start_block(&mut self, begin: Option<u64>) {
+        self.block = Block {
+            begin,
+            frame: self.values.concat(),
+            waves: vec![vec![]; self.widths.len()],
+            last: vec![0; self.widths.len()],
+            ..Default::default()
+        };
+    }
+
+    /// Record the value of all signals that have changed since the last
+    /// commit. Clears the `pending` set.
+    fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let index = self.block.times.len();
+        let mut pending: Vec<_> = std::mem::take(&mut self.pending).into_iter().collect();
+        pending.sort_by_key(|&(signal, _)| signal);
+        let mut changed = false;
+        for (signal, value) in pending {
+            for &(handle, offset) in self.vars.get(&signal).into_iter().flatten() {
+                let mut bits = vec![];
+                leaf_bits(&value, offset, &mut bits);
+                if bits == self.values[handle] {
+                    continue;
+                }
+                let delta = (index - self.block.last[handle]) as u64;
+                let wave = &mut self.block.waves[handle];
+                let before = wave.len();
+                let binary = bits.iter().all(|&bit| bit == b'0' || bit == b'1');
+                if bits.len() == 1 && binary {
+                    write_varint(wave, delta << 2 | ((bits[0] - b'0') as u64) << 1);
+                } else if bits.len() == 1 {
+                    let rcv = b"xzhuwl-".iter().position(|&c| c == bits[0]).unwrap_or(0);
+                    write_varint(wave, delta << 4 | (rcv as u64) << 1 | 1);
+                } else if !binary {
+                    write_varint(wave, delta << 1 | 1);
+                    wave.extend(&bits);
+                } else {
+                    write_varint(wave, delta << 1);
+                    let mut packed = vec![0u8; bits.len().div_ceil(8)];
+                    for (i, &bit) in bits.iter().enumerate() {
+                        packed[i / 8] |= (bit - b'0') << (7 - i % 8);
+                    }
+                    wave.extend(packed);
+                }
+                self.block.size += wave.len() - before;
+                self.block.last[handle] = index;
+                self.values[handle] = bits;
+                changed = true;
+            }
+        }
+        if changed {
+            let time = self.ticks();
+            self.block.times.push(time);
+            self.end_time = time;
+        }
+        if self.block.size >= BLOCK_SIZE {
+            self.write_block();
+            self.start_block(None);
+        }
+    }
+
+    /// Write the current block to the output.
+    fn write_block(&mut self) {
+        let block = &self.block;
+        let begin = match block.begin.or_else(|| block.times.first().cloned()) {
+            Some(t) => t,
+            None => return,
+        };
+        let end = block.times.last().cloned().unwrap_or(begin);
+        let mut data = vec![];
+        write_u64(&mut data, begin);
+        write_u64(&mut data, end);
+        write_u64(&mut data, block.waves.iter().map(|w| w.len() as u64).sum());
+
+        // Emit the values at the beginning of the block.
+        let frame = compress(&block.frame);
+        write_varint(&mut data, block.frame.len() as u64);
+        write_varint(&mut data, frame.len() as u64);
+        write_varint(&mut data, self.widths.len() as u64);
+        data.extend(frame);
+
+        // Emit the value changes of each variable, and a table with their
+        // positions relative to the pack type.
+        write_varint(&mut data, self.widths.len() as u64);
+        let start = data.len();
+        data.push(b'Z');
+        let mut positions = vec![];
+        let mut last_pos = 0;
+        let mut skipped = 0;
+        for wave in &block.waves {
+            if wave.is_empty() {
+                skipped += 1;
+                continue;
+            }
+            if skipped > 0 {
+                write_varint(&mut positions, skipped << 1);
+                skipped = 0;
+            }
+            let pos = (data.len() - start) as u64;
+            write_varint(&mut positions, (pos - last_pos) << 1 | 1);
+            last_pos = pos;
+            let compressed = zlib(wave);
+            if compressed.len() < wave.len() {
+                write_varint(&mut data, wave.len() as u64);
+                data.extend(compressed);
+            } else {
+                write_varint(&mut data, 0);
+                data.extend(wave);
+            }
+        }
+        if skipped > 0 {
+            write_varint(&mut positions, skipped << 1);
+        }
+        let positions_len = positions.len() as u64;
+        data.extend(positions);
+        write_u64(&mut data, positions_len);
+
+        // Emit the time table.
+        let mut times = vec![];
+        let mut prev = 0;
+        for &time in &block.times {
+            write_varint(&mut times, time - prev);
+            prev = time;
+        }
+        let compressed = compress(&times);
+        let compressed_len = compressed.len() as u64;
+        data.extend(compressed);
+        write_u64(&mut data, times.len() as u64);
+        write_u64(&mut data, compressed_len);
+        write_u64(&mut data, block.times.len() as u64);
+
+        if self.num_blocks == 0 {
+            self.start_time = begin;
+        }
+        self.num_blocks += 1;
+        self.write_section(BL_VCDATA, &data);
+    }
+
+    /// Write a section with its type and length to the output.
+    fn write_section(&mut self, ty: u8, data: &[u8]) {
+        let mut head = vec![ty];
+        write_u64(&mut head, data.len() as u64 + 8);
+        self.writer.write_all(&head).unwrap();
+        self.writer.write_all(data).unwrap();
+    }
+
+    /// Write the header section to the output.
+    fn write_header(&mut self) {
+        let mut data = vec![];
+        write_u64(&mut data, self.start_time);
+        write_u64(&mut data, self.end_time);
+        data.extend(&std::f64::consts::E.to_ne_bytes());
+        write_u64(&mut data, BLOCK_SIZE as u64);
+        write_u64(&mut data, self.num_scopes);
+        write_u64(&mut data, self.num_vars);
+        write_u64(&mut data, self.widths.len() as u64);
+        write_u64(&mut data, self.num_blocks);
+        data.push(self.timescale.exponent() as i8 as u8);
+        let mut version = format!("llhd-sim {}", clap::crate_version!()).into_bytes();
+        version.resize(128, 0);
+        data.extend(version);
+        data.extend(&[0; 119]); // date
+        data.push(0); // Verilog file type
+        write_u64(&mut data, 0); // time zero
+        self.write_section(BL_HDR, &data);
+    }
+
+    /// Emit the hierarchy of a scope and allocate handles for its signals.
+    fn prepare_scope(&mut self, state: &State, scope: &Scope) {
+        self.hierarchy.push(ST_VCD_SCOPE);
+        self.hierarchy.push(ST_VCD_MODULE);
+        write_string(&mut self.hierarchy, &scope.name);
+        write_string(&mut self.hierarchy, "");
+        self.num_scopes += 1;
+        let mut probed_signals: Vec<_> = scope.probes.keys().cloned().collect();
+        probed_signals.sort();
+        for sigref in probed_signals {
+            for name in &scope.probes[&sigref] {
+                self.prepare_signal(
+                    state,
+                    sigref,
+                    state[sigref].ty().unwrap_signal(),
+                    name,
+                    0,
+                    1,
+                );
+            }
+        }
+        for subscope in scope.subscopes.iter() {
+            self.prepare_scope(state, subscope);
+        }
+        self.hierarchy.push(ST_VCD_UPSCOPE);
+    }
+
+    /// Expand signals and allocate handles. Signals that are visible under
+    /// multiple names share the same handle.
+    fn prepare_signal(
+        &mut self,
+        state: &State,
+        sigref: SignalRef,
+        ty: &crate::Type,
+        name: &str,
+        offset: usize,
+        stride: usize,
+    ) {
+        match **ty {
+            crate::IntType(width) | crate::LogicType(width) => {
+                let vars = self.vars.entry(sigref).or_default();
+                let alias = match vars.iter().find(|&&(_, o)| o == offset) {
+                    Some(&(handle, _)) => handle + 1,
+                    None => {
+                        let handle = self.widths.len();
+                        vars.push((handle, offset));
+                        self.widths.push(width);
+                        let mut bits = vec![];
+                        leaf_bits(state[sigref].value(), offset, &mut bits);
+                        bits.resize(width, b'0');
+                        self.values.push(bits);
+                        0
+                    }
+                };
+                self.hierarchy.push(VT_VCD_WIRE);
+                self.hierarchy.push(VD_IMPLICIT);
+                write_string(&mut self.hierarchy, name);
+                write_varint(&mut self.hierarchy, width as u64);
+                write_varint(&mut self.hierarchy, alias as u64);
+                self.num_vars += 1;
+            }
+            crate::ArrayType(width, ref subty) => {
+                for i in 0..width {
+                    self.prepare_signal(
+                        state,
+                        sigref,
+                        subty,
+                        &format!("{}[{}]", name, i),
+                        offset + i * stride,
+                        stride * width,
+                    );
+                }
+            }
+            crate::StructType(ref fields) => {
+                for (i, subty) in fields.iter().enumerate() {
+                    self.prepare_signal(
+                        state,
+                        sigref,
+                        subty,
+                        &format!("{}.{}", name, i),
+                        offset + i * stride,
+                        stride * fields.len(),
+                    );
+                }
+            }
+            // Other types have no waveform representation.
+            _ => (),
+        }
+    }
+}
+
+impl<T> Tracer for FstTracer<T>
+where
+    T: Write + Seek,
+{
+    fn init(&mut self, state: &State) {
+        // Reserve space for the header, which is written once all blocks are
+        // known.
+        self.header_pos = self.writer.stream_position().unwrap();
+        self.write_header();
+        let scope = self.filter.apply(&state.scope);
+        self.prepare_scope(state, &scope);
+        self.time = state.time.time().clone();
+        let time = self.ticks();
+        self.start_block(Some(time));
+    }
+
+    fn step(&mut self, state: &State, changed: &HashSet<SignalRef>) {
+        // If the physical time in seconds of the simulation changed, commit the
+        // aggregated pending changes and update the time.
+        if self.time != *state.time.time() {
+            self.commit();
+            self.time = state.time.time().clone();
+        }
+
+        // Mark the changed signals for consideration during the next commit.
+        self.pending.extend(
+            changed
+                .iter()
+                .map(|&signal| (signal, state[signal].value().clone())),
+        );
+    }
+
+    fn finish(&mut self, _: &State) {
+        self.commit();
+        self.write_block();
+
+        // Emit the width of each variable.
+        let mut geometry = vec![];
+        for &width in &self.widths {
+            write_varint(&mut geometry, width as u64);
+        }
+        let compressed = compress(&geometry);
+        let mut data = vec![];
+        write_u64(&mut data, geometry.len() as u64);
+        write_u64(&mut data, self.widths.len() as u64);
+        data.extend(compressed);
+        self.write_section(BL_GEOM, &data);
+
+        // Emit the hierarchy.
+        let mut data = vec![];
+        write_u64(&mut data, self.hierarchy.len() as u64);
+        data.extend(gzip(&self.hierarchy));
+        self.write_section(BL_HIER, &data);
+
+        // Update the header.
+        self.writer.seek(SeekFrom::Start(self.header_pos)).unwrap();
+        self.write_header();
+        self.writer.seek(SeekFrom::End(0)).unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+/// Extract the bits of the integer or logic value at `offset` within a value,
+/// following the same offset scheme as the VCD tracer.
+fn leaf_bits(value: &Value, offset: usize, bits: &mut Vec<u8>) {
+    match value {
+        Value::Int(v) => {
+            for i in (0..v.width()).rev() {
+                bits.push(if v.bit(i) { b'1' } else { b'0' });
+            }
+        }
+        Value::Logic(v) => {
+            for bit in v.0.iter().rev() {
+                bits.push(bit.to_char().to_ascii_lowercase() as u8);
+            }
+        }
+        Value::Array(v) => leaf_bits(&v.extract_field(offset % v.len()), offset / v.len(), bits),
+        Value::Struct(v) => {
+            let fields = &v.0;
+            leaf_bits(&fields[offset % fields.len()], offset / fields.len(), bits)
+        }
+        Value::Void | Value::Time(_) => (),
+    }
+}
+
+/// Compress a section, or keep it uncompressed if that is smaller. Readers
+/// distinguish the two by comparing the compressed and uncompressed length.
+fn compress(data: &[u8]) -> Vec<u8> {
+    let compressed = zlib(data);
+    if compressed.len() < data.len() {
+        compressed
+    } else {
+        data.to_vec()
+    }
+}
+
+/// Compress data into a zlib stream.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Compress data into a gzip stream.
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend(&value.to_be_bytes());
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    out.extend(value.as_bytes());
+    out.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{value::IntValue, Simulation};
+    use flate2::read::GzDecoder;
+    use std::{convert::TryInto, io::Cursor, io::Read};
+
+    #[test]
+    fn sections() {
+        let module = crate::assembly::parse_module(
+            "
+            entity @top (i8$ %a) -> (i8$ %b) {
+                %delay = const time 1ns
+                %0 = prb i8$ %a
+                drv i8$ %b, %0, %delay
+            }
+            ",
+        )
+        .unwrap();
+        let mut output = Cursor::new(vec![]);
+        {
+            let mut sim = Simulation::new(&module).unwrap();
+            sim.add_tracer(Box::new(FstTracer::new(&mut output)));
+            sim.poke("a", IntValue::from_usize(8, 42).into()).unwrap();
+            sim.run().unwrap();
+            sim.finish();
+        }
+        let output = output.into_inner();
+
+        // Walk the sections of the file.
+        let mut types = vec![];
+        let mut sections = vec![];
+        let mut pos = 0;
+        while pos < output.len() {
+            let mut len = [0; 8];
+            len.copy_from_slice(&output[pos + 1..pos + 9]);
+            let len = u64::from_be_bytes(len) as usize;
+            types.push(output[pos]);
+            sections.push(&output[pos + 9..pos + 1 + len]);
+            pos += 1 + len;
+        }
+        assert_eq!(pos, output.len());
+        assert_eq!(types, vec![BL_HDR, BL_VCDATA, BL_GEOM, BL_HIER]);
+
+        // Check the header.
+        let field = |i: usize| {
+            let mut v = [0; 8];
+            v.copy_from_slice(&output[9 + i * 8..17 + i * 8]);
+            u64::from_be_bytes(v)
+        };
+        assert_eq!(field(0), 0); // start time
+        assert_eq!(field(1), 1000); // end time
+        assert_eq!(field(4), 1); // scopes
+        assert_eq!(field(5), 2); // variables
+        assert_eq!(field(6), 2); // handles
+        assert_eq!(field(7), 1); // blocks
+
+        // Decompress the hierarchy.
+        let mut hierarchy = vec![];
+        GzDecoder::new(&sections[3][8..])
+            .read_to_end(&mut hierarchy)
+            .unwrap();
+        assert_eq!(
+            hierarchy.len() as u64,
+            u64::from_be_bytes(sections[3][..8].try_into().unwrap())
+        );
+        let mut expected = vec![ST_VCD_SCOPE, ST_VCD_MODULE];
+        write_string(&mut expected, "@top");
+        write_string(&mut expected, "");
+        assert!(hierarchy.starts_with(&expected));
+        assert_eq!(hierarchy.last(), Some(&ST_VCD_UPSCOPE));
+    }
+}
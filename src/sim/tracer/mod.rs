@@ -0,0 +1,183 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! A simulation tracer that can store the generated waveform to disk.
+
+use crate::sim::state::{Scope, SignalRef, State};
+use std::collections::HashSet;
+
+pub use crate::value::Timescale;
+
+/// A simulation tracer that can operate on the simulation trace as it is being
+/// generated.
+pub trait Tracer {
+    /// Called once at the beginning of the simulation.
+    fn init(&mut self, state: &State);
+
+    /// Called by the simulation engine after each time step.
+    fn step(&mut self, state: &State, changed: &HashSet<SignalRef>);
+
+    /// Called once at the end of the simulation.
+    fn finish(&mut self, state: &State);
+}
+
+/// A null tracer that does nothing.
+pub struct NullTracer;
+
+impl Tracer for NullTracer {
+    fn init(&mut self, _: &State) {}
+    fn step(&mut self, _: &State, _: &HashSet<SignalRef>) {}
+    fn finish(&mut self, _: &State) {}
+}
+
+/// A selection of the scopes in the design hierarchy that are traced.
+///
+/// Scopes are selected by glob patterns on their hierarchical path, for
+/// example `@top.@cpu`. In a pattern, `*` matches any sequence of characters
+/// except the `.` that separates scopes, `**` matches any sequence of
+/// characters, and `?` matches a single character. A selected scope is traced
+/// together with its subscopes, up to an optional depth limit. Without any
+/// patterns the root scope is selected.
+#[derive(Debug, Clone, Default)]
+pub struct ScopeFilter {
+    patterns: Vec<regex::Regex>,
+    depth: Option<usize>,
+}
+
+impl ScopeFilter {
+    /// Create a filter that selects the entire hierarchy.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Select the scopes whose hierarchical path matches a glob pattern.
+    pub fn add_pattern(&mut self, pattern: &str) {
+        self.patterns.push(glob_regex(pattern));
+    }
+
+    /// Limit the number of levels of hierarchy traced for each selected scope.
+    ///
+    /// As with Verilog's `$dumpvars`, a depth of 1 only traces the signals of
+    /// the selected scope itself, and no limit traces all of its subscopes.
+    pub fn set_depth(&mut self, depth: Option<usize>) {
+        self.depth = depth;
+    }
+
+    /// Apply the filter to a scope.
+    ///
+    /// Returns a copy of the scope where the unselected scopes have no probes,
+    /// and subscopes that contain no selected scopes are removed.
+    pub fn apply(&self, scope: &Scope) -> Scope {
+        self.filter(scope, None, None)
+            .unwrap_or_else(|| Scope::new(scope.name.clone()))
+    }
+
+    /// Filter a scope. `budget` is the number of levels that remain to be
+    /// traced if a parent scope has been selected.
+    fn filter(&self, scope: &Scope, parent: Option<&str>, budget: Option<usize>) -> Option<Scope> {
+        let path = match parent {
+            Some(parent) => format!("{}.{}", parent, scope.name),
+            None => scope.name.clone(),
+        };
+        let selected = if self.patterns.is_empty() {
+            parent.is_none()
+        } else {
+            self.patterns.iter().any(|p| p.is_match(&path))
+        };
+        let budget = budget.or_else(|| {
+            if selected {
+                Some(self.depth.unwrap_or(usize::MAX))
+            } else {
+                None
+            }
+        });
+        let mut result = Scope::new(scope.name.clone());
+        if let Some(budget) = budget {
+            if budget == 0 {
+                return None;
+            }
+            result.probes = scope.probes.clone();
+        }
+        for subscope in &scope.subscopes {
+            if let Some(s) = self.filter(subscope, Some(&path), budget.map(|b| b - 1)) {
+                result.add_subscope(s);
+            }
+        }
+        if budget.is_none() && result.subscopes.is_empty() {
+            return None;
+        }
+        Some(result)
+    }
+}
+
+/// Convert a glob pattern on a hierarchical path into a regular expression.
+///
+/// `*` matches any sequence of characters except `.`, `**` matches any
+/// sequence of characters, and `?` matches a single character.
+pub(crate) fn glob_regex(pattern: &str) -> regex::Regex {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^.]*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex::Regex::new(&regex).unwrap()
+}
+
+// Import the actual tracers.
+mod dump;
+mod fst;
+mod saif;
+mod vcd;
+pub use dump::*;
+pub use fst::*;
+pub use saif::*;
+pub use vcd::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_filter() {
+        let mut top = Scope::new("@top");
+        let mut cpu = Scope::new("@cpu");
+        cpu.add_probe(SignalRef::new(1), "pc".to_string());
+        let mut alu = Scope::new("@alu");
+        alu.add_probe(SignalRef::new(2), "op".to_string());
+        cpu.add_subscope(alu);
+        top.add_probe(SignalRef::new(0), "clk".to_string());
+        top.add_subscope(cpu);
+        top.add_subscope(Scope::new("@mem"));
+
+        // Without patterns, everything is traced.
+        assert_eq!(ScopeFilter::new().apply(&top), top);
+
+        // Select a subsystem.
+        let mut filter = ScopeFilter::new();
+        filter.add_pattern("@top.@c*");
+        let scope = filter.apply(&top);
+        assert!(scope.probes.is_empty());
+        assert_eq!(scope.subscopes, vec![top.subscopes[0].clone()]);
+
+        // Limit the depth.
+        filter.set_depth(Some(1));
+        let scope = filter.apply(&top);
+        assert_eq!(scope.subscopes[0].probes.len(), 1);
+        assert!(scope.subscopes[0].subscopes.is_empty());
+
+        // Match across levels of hierarchy.
+        let mut filter = ScopeFilter::new();
+        filter.add_pattern("**.@alu");
+        let scope = filter.apply(&top);
+        assert!(scope.subscopes[0].probes.is_empty());
+        assert_eq!(scope.subscopes[0].subscopes[0].probes.len(), 1);
+    }
+}
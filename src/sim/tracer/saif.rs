@@ -0,0 +1,336 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! A Switching Activity Interchange Format tracer.
+//!
+//! SAIF records for every bit of a net how long it was at 0, 1, X, and Z, and
+//! how often it toggled between 0 and 1, such that power estimation tools can
+//! derive the switching activity of a design. Unlike a waveform, the file is
+//! only written once the simulation finishes. Changes within the delta cycles
+//! of a time step are not counted, only the value a net settles to.
+
+use crate::{
+    sim::{
+        state::{Scope, SignalRef, State},
+        tracer::{ScopeFilter, Timescale, Tracer},
+        value::Value,
+    },
+    value::LogicBit,
+};
+use num::{zero, BigRational};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+/// A tracer that accumulates the switching activity of each signal and emits
+/// it as SAIF.
+pub struct SaifTracer<T> {
+    writer: T,
+    /// The activity of each bit of each traced signal.
+    activity: HashMap<SignalRef, Vec<BitActivity>>,
+    /// The traced scopes.
+    scope: Scope,
+    start: BigRational,
+    time: BigRational,
+    pending: HashMap<SignalRef, Value>,
+    timescale: Timescale,
+    filter: ScopeFilter,
+}
+
+/// The state of a single bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bit {
+    Zero,
+    One,
+    X,
+    Z,
+}
+
+impl From<LogicBit> for Bit {
+    fn from(bit: LogicBit) -> Bit {
+        match bit.to_vcd_char() {
+            '0' => Bit::Zero,
+            '1' => Bit::One,
+            'z' => Bit::Z,
+            _ => Bit::X,
+        }
+    }
+}
+
+/// The activity of a single bit.
+struct BitActivity {
+    /// The current state of the bit.
+    state: Bit,
+    /// The time at which the bit changed to its current state.
+    since: BigRational,
+    /// The time spent at 0, 1, X, and Z, in this order.
+    durations: [BigRational; 4],
+    /// The number of transitions between 0 and 1.
+    toggles: u64,
+}
+
+impl<T> SaifTracer<T>
+where
+    T: Write,
+{
+    /// Create a new SAIF tracer which will write its SAIF to `writer`.
+    pub fn new(writer: T) -> Self {
+        SaifTracer {
+            writer,
+            activity: HashMap::new(),
+            scope: Scope::new(""),
+            start: zero(),
+            time: zero(),
+            pending: HashMap::new(),
+            timescale: Default::default(),
+            filter: Default::default(),
+        }
+    }
+
+    /// Change the unit in which time is recorded. Defaults to picoseconds.
+    pub fn set_timescale(&mut self, timescale: Timescale) {
+        self.timescale = timescale;
+    }
+
+    /// Only trace the scopes selected by a filter. Defaults to all scopes.
+    pub fn set_filter(&mut self, filter: ScopeFilter) {
+        self.filter = filter;
+    }
+
+    /// Record the values that the signals changed since the last flush settled
+    /// to. Clears the `pending` set.
+    fn flush(&mut self) {
+        let mut bits = vec![];
+        for (signal, value) in std::mem::take(&mut self.pending) {
+            let activity = match self.activity.get_mut(&signal) {
+                Some(activity) => activity,
+                None => continue,
+            };
+            bits.clear();
+            value.flatten(&mut bits);
+            for (bit, &state) in activity.iter_mut().zip(&bits) {
+                bit.change(state.into(), &self.time);
+            }
+        }
+    }
+
+    /// Write the activity of the nets in a scope and its subscopes.
+    fn write_scope(&mut self, scope: &Scope, indent: usize) -> std::io::Result<()> {
+        let pad = "  ".repeat(indent);
+        let timescale = self.timescale;
+        writeln!(self.writer, "{}(INSTANCE {}", pad, identifier(&scope.name))?;
+        let mut probed_signals: Vec<_> = scope.probes.keys().cloned().collect();
+        probed_signals.sort();
+        let mut nets = vec![];
+        for sigref in probed_signals {
+            let activity = match self.activity.get(&sigref) {
+                Some(activity) if !activity.is_empty() => activity,
+                _ => continue,
+            };
+            for name in &scope.probes[&sigref] {
+                if activity.len() == 1 {
+                    nets.push((identifier(name), &activity[0]));
+                } else {
+                    for (i, bit) in activity.iter().enumerate() {
+                        nets.push((format!("{}\\[{}\\]", identifier(name), i), bit));
+                    }
+                }
+            }
+        }
+        if !nets.is_empty() {
+            writeln!(self.writer, "{}  (NET", pad)?;
+            for (name, bit) in nets {
+                let [t0, t1, tx, tz] = &bit.durations;
+                let ticks = |t| timescale.ticks(t);
+                writeln!(self.writer, "{}    ({}", pad, name)?;
+                writeln!(
+                    self.writer,
+                    "{}      (T0 {}) (T1 {}) (TX {}) (TZ {})",
+                    pad,
+                    ticks(t0),
+                    ticks(t1),
+                    ticks(tx),
+                    ticks(tz)
+                )?;
+                writeln!(self.writer, "{}      (TC {}) (IG 0)", pad, bit.toggles)?;
+                writeln!(self.writer, "{}    )", pad)?;
+            }
+            writeln!(self.writer, "{}  )", pad)?;
+        }
+        for subscope in &scope.subscopes {
+            self.write_scope(subscope, indent + 1)?;
+        }
+        writeln!(self.writer, "{})", pad)
+    }
+
+    /// Write the SAIF file.
+    fn write(&mut self) -> std::io::Result<()> {
+        let timescale = self.timescale.to_string();
+        let split = timescale.find(|c: char| !c.is_ascii_digit()).unwrap();
+        let duration = self.timescale.ticks(&(&self.time - &self.start));
+        let scope = std::mem::replace(&mut self.scope, Scope::new(""));
+        writeln!(self.writer, "(SAIFILE")?;
+        writeln!(self.writer, "(SAIFVERSION \"2.0\")")?;
+        writeln!(self.writer, "(DIRECTION \"backward\")")?;
+        writeln!(self.writer, "(DESIGN \"{}\")", identifier(&scope.name))?;
+        writeln!(self.writer, "(VENDOR \"LLHD\")")?;
+        writeln!(self.writer, "(PROGRAM_NAME \"llhd-sim\")")?;
+        writeln!(self.writer, "(VERSION \"{}\")", clap::crate_version!())?;
+        writeln!(self.writer, "(DIVIDER / )")?;
+        writeln!(
+            self.writer,
+            "(TIMESCALE {} {})",
+            &timescale[..split],
+            &timescale[split..]
+        )?;
+        writeln!(self.writer, "(DURATION {})", duration)?;
+        self.write_scope(&scope, 0)?;
+        writeln!(self.writer, ")")?;
+        self.writer.flush()
+    }
+}
+
+impl<T> Tracer for SaifTracer<T>
+where
+    T: Write,
+{
+    fn init(&mut self, state: &State) {
+        // A simulation restored from a checkpoint does not start at time zero,
+        // so only the remainder of the simulation is recorded.
+        self.start = state.time.time().clone();
+        self.time = self.start.clone();
+        self.scope = self.filter.apply(&state.scope);
+        let mut signals = HashSet::new();
+        collect_signals(&self.scope, &mut signals);
+        for signal in signals {
+            let mut bits = vec![];
+            state[signal].value().flatten(&mut bits);
+            let activity = bits
+                .into_iter()
+                .map(|state| BitActivity {
+                    state: state.into(),
+                    since: self.start.clone(),
+                    durations: [zero(), zero(), zero(), zero()],
+                    toggles: 0,
+                })
+                .collect();
+            self.activity.insert(signal, activity);
+        }
+    }
+
+    fn step(&mut self, state: &State, changed: &HashSet<SignalRef>) {
+        // If the physical time in seconds of the simulation changed, record
+        // the values the pending changes settled to and update the time.
+        if self.time != *state.time.time() {
+            self.flush();
+            self.time = state.time.time().clone();
+        }
+
+        // Mark the changed signals for consideration during the next flush.
+        let activity = &self.activity;
+        self.pending.extend(
+            changed
+                .iter()
+                .filter(|signal| activity.contains_key(signal))
+                .map(|&signal| (signal, state[signal].value().clone())),
+        );
+    }
+
+    fn finish(&mut self, _: &State) {
+        self.flush();
+        for bit in self.activity.values_mut().flatten() {
+            bit.change(bit.state, &self.time);
+        }
+        self.write().unwrap();
+    }
+}
+
+impl BitActivity {
+    /// Change the state of the bit at a point in time, accounting the time
+    /// spent in the previous state.
+    fn change(&mut self, state: Bit, time: &BigRational) {
+        let index = match self.state {
+            Bit::Zero => 0,
+            Bit::One => 1,
+            Bit::X => 2,
+            Bit::Z => 3,
+        };
+        self.durations[index] += time - &self.since;
+        self.since = time.clone();
+        if let (Bit::Zero, Bit::One) | (Bit::One, Bit::Zero) = (self.state, state) {
+            self.toggles += 1;
+        }
+        self.state = state;
+    }
+}
+
+/// Collect the signals probed in a scope and its subscopes.
+fn collect_signals(scope: &Scope, signals: &mut HashSet<SignalRef>) {
+    signals.extend(scope.probes.keys());
+    for subscope in &scope.subscopes {
+        collect_signals(subscope, signals);
+    }
+}
+
+/// Turn the name of a scope or signal into a SAIF identifier, dropping the
+/// sigil of unit names and escaping all other special characters.
+fn identifier(name: &str) -> String {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    let mut ident = String::with_capacity(name.len());
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() && c != '_' {
+            ident.push('\\');
+        }
+        ident.push(c);
+    }
+    ident
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{value::IntValue, Simulation};
+
+    #[test]
+    fn toggles_and_durations() {
+        let module = crate::assembly::parse_module(
+            "
+            entity @top (i2$ %a) -> (i2$ %b) {
+                %delay = const time 1ns
+                %0 = prb i2$ %a
+                drv i2$ %b, %0, %delay
+            }
+            ",
+        )
+        .unwrap();
+        let mut output = vec![];
+        {
+            let mut sim = Simulation::new(&module).unwrap();
+            let mut tracer = SaifTracer::new(&mut output);
+            tracer.set_timescale(Timescale::from_exponent(-9).unwrap());
+            sim.add_tracer(Box::new(tracer));
+            sim.poke("a", IntValue::from_usize(2, 1).into()).unwrap();
+            sim.run().unwrap();
+            sim.poke("a", IntValue::from_usize(2, 2).into()).unwrap();
+            sim.run().unwrap();
+            sim.finish();
+        }
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert!(lines.contains(&"(TIMESCALE 1 ns)"));
+        assert!(lines.contains(&"(DURATION 2)"));
+        assert!(lines.contains(&"(INSTANCE top"));
+
+        // Bit 0 of `a` rises at 0ns and falls at 1ns, bit 1 rises at 1ns. The
+        // output follows 1ns later.
+        let net = |name: &str| {
+            let i = lines.iter().position(|l| l.trim() == format!("({}", name));
+            let i = i.unwrap_or_else(|| panic!("no net {} in {}", name, output));
+            format!("{} {}", lines[i + 1].trim(), lines[i + 2].trim())
+        };
+        assert_eq!(net("a\\[0\\]"), "(T0 1) (T1 1) (TX 0) (TZ 0) (TC 2) (IG 0)");
+        assert_eq!(net("a\\[1\\]"), "(T0 1) (T1 1) (TX 0) (TZ 0) (TC 1) (IG 0)");
+        assert_eq!(net("b\\[0\\]"), "(T0 1) (T1 1) (TX 0) (TZ 0) (TC 2) (IG 0)");
+        assert_eq!(net("b\\[1\\]"), "(T0 2) (T1 0) (TX 0) (TZ 0) (TC 1) (IG 0)");
+    }
+}
@@ -2,12 +2,12 @@
 
 //! A Value Change Dump tracer.
 
-use crate::{
+use crate::sim::{
     state::{Scope, SignalRef, State},
-    tracer::Tracer,
+    tracer::{ScopeFilter, Timescale, Tracer},
     value::Value,
 };
-use num::{traits::Pow, BigInt, BigRational, FromPrimitive};
+use num::{BigRational, Zero};
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
@@ -19,7 +19,8 @@ pub struct VcdTracer<T> {
     abbrevs: HashMap<SignalRef, Vec<(String, String, usize)>>,
     time: BigRational,
     pending: HashMap<SignalRef, Value>,
-    precision: BigRational,
+    timescale: Timescale,
+    filter: ScopeFilter,
 }
 
 impl<T> VcdTracer<T>
@@ -33,19 +34,28 @@ where
             abbrevs: HashMap::new(),
             time: num::zero(),
             pending: HashMap::new(),
-            // Hard-code the precision to ps for now. Later on, we might want to
-            // make this configurable or automatically determined by the module.
-            precision: BigInt::from_usize(10).unwrap().pow(12usize).into(), // ps
+            timescale: Default::default(),
+            filter: Default::default(),
         }
     }
 
+    /// Change the unit in which time is recorded. Defaults to picoseconds.
+    pub fn set_timescale(&mut self, timescale: Timescale) {
+        self.timescale = timescale;
+    }
+
+    /// Only trace the scopes selected by a filter. Defaults to all scopes.
+    pub fn set_filter(&mut self, filter: ScopeFilter) {
+        self.filter = filter;
+    }
+
     /// Write the value of all signals that have changed since the last flush.
     /// Clears the `pending` set.
     fn flush(&mut self) {
-        let time = (&self.time * &self.precision).trunc();
+        let time = self.timescale.ticks(&self.time);
         write!(self.writer.borrow_mut(), "#{}\n", time).unwrap();
         for (signal, value) in std::mem::replace(&mut self.pending, HashMap::new()) {
-            for &(ref abbrev, _, offset) in &self.abbrevs[&signal] {
+            for &(ref abbrev, _, offset) in self.abbrevs.get(&signal).into_iter().flatten() {
                 self.flush_signal(signal, offset, &value, abbrev);
             }
         }
@@ -59,15 +69,19 @@ where
             Value::Void => (),
             Value::Int(v) => {
                 assert_eq!(offset, 0);
-                write!(self.writer.borrow_mut(), "b{:b} {}\n", v.value, abbrev).unwrap();
+                writeln!(self.writer.borrow_mut(), "b{:b} {}", v, abbrev).unwrap();
+            }
+            Value::Logic(v) => {
+                assert_eq!(offset, 0);
+                let bits: String = v.0.iter().rev().map(|bit| bit.to_vcd_char()).collect();
+                writeln!(self.writer.borrow_mut(), "b{} {}", bits, abbrev).unwrap();
             }
             Value::Time(_) => (),
             Value::Array(v) => {
-                let elems = &v.0;
                 self.flush_signal(
                     signal,
-                    offset / elems.len(),
-                    &elems[offset % elems.len()],
+                    offset / v.len(),
+                    &v.extract_field(offset % v.len()),
                     abbrev,
                 );
             }
@@ -80,10 +94,6 @@ where
                     abbrev,
                 );
             }
-            // _ => panic!(
-            //     "flush non-const/non-aggregate signal {:?} with value {:?}",
-            //     signal, value
-            // ),
         };
     }
 
@@ -121,14 +131,14 @@ where
         &mut self,
         state: &State,
         sigref: SignalRef,
-        ty: &llhd::Type,
+        ty: &crate::Type,
         name: &str,
         index: &mut usize,
         offset: usize,
         stride: usize,
     ) {
         match **ty {
-            llhd::IntType(width) => {
+            crate::IntType(width) | crate::LogicType(width) => {
                 // Allocate short name for the probed signal.
                 let mut idx = *index;
                 let mut abbrev = String::new();
@@ -153,7 +163,7 @@ where
                 .unwrap();
                 abbrevs_for_signal.push((abbrev, name.to_owned(), offset));
             }
-            llhd::ArrayType(width, ref subty) => {
+            crate::ArrayType(width, ref subty) => {
                 for i in 0..width {
                     self.prepare_signal(
                         state,
@@ -166,7 +176,7 @@ where
                     );
                 }
             }
-            llhd::StructType(ref fields) => {
+            crate::StructType(ref fields) => {
                 for (i, subty) in fields.iter().enumerate() {
                     self.prepare_signal(
                         state,
@@ -197,11 +207,23 @@ where
             clap::crate_version!()
         )
         .unwrap();
-        write!(self.writer.borrow_mut(), "$timescale 1ps $end\n").unwrap();
-        self.prepare_scope(state, &state.scope, &mut 0);
+        writeln!(
+            self.writer.borrow_mut(),
+            "$timescale {} $end",
+            self.timescale
+        )
+        .unwrap();
+        let scope = self.filter.apply(&state.scope);
+        self.prepare_scope(state, &scope, &mut 0);
         write!(self.writer.borrow_mut(), "$enddefinitions $end\n").unwrap();
 
-        // Dump the variables.
+        // Dump the variables. A simulation restored from a checkpoint does not
+        // start at time zero, so mark the time of the dump.
+        self.time = state.time.time().clone();
+        if !self.time.is_zero() {
+            let time = self.timescale.ticks(&self.time);
+            writeln!(self.writer.borrow_mut(), "#{}", time).unwrap();
+        }
         write!(self.writer.borrow_mut(), "$dumpvars\n").unwrap();
         for &signal in state.probes.keys() {
             if let Some(abbrevs) = self.abbrevs.get(&signal) {
@@ -0,0 +1,1549 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Value computation
+//!
+//! This module implements representations for LLHD values as they evolve during
+//! the simulation of a design.
+//!
+//! The types mirror the ones in `llhd::value`, which represent the constants
+//! of a module, but are laid out for the simulator instead: integers of up to
+//! 64 bits are stored in a machine word rather than a `BigUint`, and arrays of
+//! integers store their elements as contiguous bits, such that the simulator
+//! can update them in place. Signals are poked and peeked, and externs called,
+//! with these types, since converting every value at the boundary would defeat
+//! that layout. Values convert from and to their `llhd::value` counterparts
+//! with `From`. Times and logic values are shared between both modules.
+
+use crate::{
+    ir::{Opcode, Resolution},
+    ty::Type,
+    value::LogicBit,
+};
+use num::{bigint::ToBigInt, BigInt, BigUint, One, Signed, ToPrimitive, Zero};
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+};
+
+/// A point in time.
+pub type Time = crate::value::TimeValue;
+
+/// A point in time.
+pub type TimeValue = crate::value::TimeValue;
+
+/// A nine-valued logic value.
+pub type LogicValue = crate::value::LogicValue;
+
+/// A value.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum Value {
+    Void,
+    Time(Time),
+    Int(IntValue),
+    Logic(LogicValue),
+    Array(ArrayValue),
+    Struct(StructValue),
+}
+
+impl Value {
+    /// If this value is a time, access it.
+    pub fn get_time(&self) -> Option<&TimeValue> {
+        match self {
+            Value::Time(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Unwrap this value as a time, or panic.
+    pub fn unwrap_time(&self) -> &TimeValue {
+        self.get_time().expect("value is not a time")
+    }
+
+    /// If this value is an integer, access it.
+    pub fn get_int(&self) -> Option<&IntValue> {
+        match self {
+            Value::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Unwrap this value as an integer, or panic.
+    pub fn unwrap_int(&self) -> &IntValue {
+        self.get_int().expect("value is not an integer")
+    }
+
+    /// If this value is a logic value, access it.
+    pub fn get_logic(&self) -> Option<&LogicValue> {
+        match self {
+            Value::Logic(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Unwrap this value as a logic value, or panic.
+    pub fn unwrap_logic(&self) -> &LogicValue {
+        self.get_logic().expect("value is not a logic value")
+    }
+
+    /// If this value is an array, access it.
+    pub fn get_array(&self) -> Option<&ArrayValue> {
+        match self {
+            Value::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Unwrap this value as an array, or panic.
+    pub fn unwrap_array(&self) -> &ArrayValue {
+        self.get_array().expect("value is not an array")
+    }
+
+    /// If this value is a struct, access it.
+    pub fn get_struct(&self) -> Option<&StructValue> {
+        match self {
+            Value::Struct(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Unwrap this value as a struct, or panic.
+    pub fn unwrap_struct(&self) -> &StructValue {
+        self.get_struct().expect("value is not a struct")
+    }
+
+    /// Create the zero value for a type.
+    ///
+    /// Types that have no simulation representation map to `Value::Void`.
+    pub fn zero(ty: &Type) -> Value {
+        use crate::ty::TypeKind::*;
+        match ty.as_ref() {
+            TimeType => TimeValue::zero().into(),
+            IntType(w) => IntValue::from_usize(*w, 0).into(),
+            LogicType(w) => LogicValue::zero(*w).into(),
+            ArrayType(l, ty) => ArrayValue::new_uniform(*l, Value::zero(ty)).into(),
+            StructType(tys) => StructValue::new(tys.iter().map(Value::zero).collect()).into(),
+            _ => Value::Void,
+        }
+    }
+
+    /// Create the value of a resolved signal that is not driven.
+    ///
+    /// This is the value that does not change the result of the resolution
+    /// function: `Z` for logic values, all ones for wired-and integers, and
+    /// zero for wired-or integers.
+    pub fn undriven(ty: &Type, resolution: Resolution) -> Value {
+        use crate::ty::TypeKind::*;
+        match (ty.as_ref(), resolution) {
+            (LogicType(w), _) => LogicValue::new_uniform(*w, LogicBit::Z).into(),
+            (IntType(w), Resolution::WiredAnd) => IntValue::from_usize(*w, 0).not().into(),
+            _ => Value::zero(ty),
+        }
+    }
+
+    /// Parse a value of a given type from a string.
+    ///
+    /// Integers may be given in decimal, or in hexadecimal, octal, or binary
+    /// with a `0x`, `0o`, or `0b` prefix, respectively. Negative decimal
+    /// integers are stored in two's complement. An integer must fit into the
+    /// width of the type, either as a signed or an unsigned number. Logic
+    /// values list their bits with the highest-indexed wire first, for example
+    /// `01XZ`. With a `0b` prefix, they may be shorter than the type and are
+    /// extended as in VCD files: with `X` or `Z` if that is the leading bit,
+    /// and with zeros otherwise. Times use the assembly syntax, for example
+    /// `10ns`. Returns `None` if the string is not a valid value of the type.
+    pub fn parse(ty: &Type, text: &str) -> Option<Value> {
+        use crate::ty::TypeKind::*;
+        let text = text.trim();
+        match ty.as_ref() {
+            TimeType => crate::assembly::parse_time(text).ok().map(Into::into),
+            IntType(w) => {
+                let (radix, digits) = match text.get(..2) {
+                    Some("0x") | Some("0X") => (16, &text[2..]),
+                    Some("0o") | Some("0O") => (8, &text[2..]),
+                    Some("0b") | Some("0B") => (2, &text[2..]),
+                    _ => (10, text),
+                };
+                let value = BigInt::parse_bytes(digits.replace('_', "").as_bytes(), radix)?;
+                if radix != 10 && value.is_negative() {
+                    return None;
+                }
+                let min = match *w {
+                    0 => BigInt::zero(),
+                    w => -(BigInt::one() << (w - 1)),
+                };
+                if value < min || value >= BigInt::one() << *w {
+                    return None;
+                }
+                Some(IntValue::from_signed(*w, value).into())
+            }
+            LogicType(w) => {
+                let mut value = match text.get(..2) {
+                    Some("0b") | Some("0B") => LogicValue::parse(&text[2..])?,
+                    _ => {
+                        return LogicValue::parse(text)
+                            .filter(|v| v.width() == *w)
+                            .map(Into::into)
+                    }
+                };
+                let fill = match value.0.last() {
+                    Some(&bit) if bit == LogicBit::X || bit == LogicBit::Z => bit,
+                    _ => LogicBit::Zero,
+                };
+                if value.width() == 0 || value.width() > *w {
+                    return None;
+                }
+                value.0.resize(*w, fill);
+                Some(value.into())
+            }
+            _ => None,
+        }
+    }
+
+    /// Check if the value is zero.
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Value::Array(..) | Value::Struct(..) | Value::Void => false,
+            Value::Time(v) => v.time().is_zero() && v.delta() == 0 && v.epsilon() == 0,
+            Value::Int(v) => v.is_zero(),
+            Value::Logic(v) => v.is_zero(),
+        }
+    }
+
+    /// Check if the value is one.
+    pub fn is_one(&self) -> bool {
+        match self {
+            Value::Array(..) | Value::Struct(..) | Value::Void => false,
+            Value::Time(_) => false,
+            Value::Int(v) => v.is_one(),
+            Value::Logic(v) => v.is_one(),
+        }
+    }
+}
+
+/// Opcode implementations.
+impl Value {
+    /// Execute a comparison opcode.
+    ///
+    /// Integers support all comparisons, time values support the unsigned
+    /// relational operators, and all values can be compared for equality.
+    /// Returns `None` if the comparison is not defined for the given values.
+    pub fn compare_op(op: Opcode, lhs: &Value, rhs: &Value) -> Option<IntValue> {
+        let v = match (op, lhs, rhs) {
+            (_, Value::Int(lhs), Value::Int(rhs)) => {
+                return Some(IntValue::compare_op(op, lhs, rhs))
+            }
+            (Opcode::Eq, _, _) => lhs == rhs,
+            (Opcode::Neq, _, _) => lhs != rhs,
+            (Opcode::Ult, Value::Time(lhs), Value::Time(rhs)) => lhs < rhs,
+            (Opcode::Ugt, Value::Time(lhs), Value::Time(rhs)) => lhs > rhs,
+            (Opcode::Ule, Value::Time(lhs), Value::Time(rhs)) => lhs <= rhs,
+            (Opcode::Uge, Value::Time(lhs), Value::Time(rhs)) => lhs >= rhs,
+            _ => return None,
+        };
+        Some(IntValue::from_usize(1, v as usize))
+    }
+
+    /// Resolve the values of two drivers of a signal.
+    pub fn resolve(resolution: Resolution, lhs: &Value, rhs: &Value) -> Value {
+        match (resolution, lhs, rhs) {
+            (_, Value::Logic(lhs), Value::Logic(rhs)) => lhs.resolve(rhs, resolution).into(),
+            (Resolution::WiredAnd, Value::Int(lhs), Value::Int(rhs)) => lhs.and(rhs).into(),
+            (Resolution::WiredOr, Value::Int(lhs), Value::Int(rhs)) => lhs.or(rhs).into(),
+            (_, Value::Int(_), Value::Logic(_)) | (_, Value::Logic(_), Value::Int(_)) => {
+                Value::int_or_unknown(lhs.to_logic().resolve(&rhs.to_logic(), resolution))
+            }
+            _ => panic!("cannot resolve {} and {} with {}", lhs, rhs, resolution),
+        }
+    }
+}
+
+/// Integers with unknown bits.
+///
+/// With X propagation enabled, an integer of which some bits are unknown is
+/// held as a logic value of the same width. Arithmetic and comparisons yield
+/// an all-`X` result if any bit of their operands is unknown, whereas the
+/// bitwise operators act on the individual bits.
+impl Value {
+    /// Create the unknown value of a type.
+    ///
+    /// Integers and logic values are all `X`. Types that cannot be unknown map
+    /// to their zero value.
+    pub fn unknown(ty: &Type) -> Value {
+        use crate::ty::TypeKind::*;
+        match ty.as_ref() {
+            IntType(w) | LogicType(w) => LogicValue::new_uniform(*w, LogicBit::X).into(),
+            ArrayType(l, ty) => ArrayValue::new_uniform(*l, Value::unknown(ty)).into(),
+            StructType(tys) => StructValue::new(tys.iter().map(Value::unknown).collect()).into(),
+            _ => Value::zero(ty),
+        }
+    }
+
+    /// Check whether any bit of the value is neither a logic zero nor one.
+    pub fn is_unknown(&self) -> bool {
+        match self {
+            Value::Logic(v) => v.0.iter().any(|bit| bit.to_bool().is_none()),
+            Value::Array(v) => v.packed_bits().is_none() && v.iter().any(|v| v.is_unknown()),
+            Value::Struct(v) => v.0.iter().any(Value::is_unknown),
+            _ => false,
+        }
+    }
+
+    /// Get the bits of an integer or logic value.
+    pub fn to_logic(&self) -> LogicValue {
+        match self {
+            Value::Int(v) => v.to_logic(),
+            Value::Logic(v) => v.clone(),
+            _ => panic!("{} has no logic bits", self),
+        }
+    }
+
+    /// Collect the bits of a value, least significant bit first.
+    ///
+    /// Arrays and structs contribute the bits of their elements and fields in
+    /// order.
+    pub(crate) fn flatten(&self, bits: &mut Vec<LogicBit>) {
+        match self {
+            Value::Int(v) => bits.extend((0..v.width()).map(|i| LogicBit::from(v.bit(i)))),
+            Value::Logic(v) => bits.extend(&v.0),
+            Value::Array(v) => v.iter().for_each(|v| v.flatten(bits)),
+            Value::Struct(v) => v.0.iter().for_each(|v| v.flatten(bits)),
+            Value::Void | Value::Time(_) => (),
+        }
+    }
+
+    /// Get an integer or logic value as an integer, if all of its bits are
+    /// known.
+    pub fn to_known_int(&self) -> Option<IntValue> {
+        match self {
+            Value::Int(v) => Some(v.clone()),
+            Value::Logic(v) => IntValue::from_logic(v),
+            _ => None,
+        }
+    }
+
+    /// Create an integer from logic bits, or keep the logic bits if some of
+    /// them are unknown.
+    pub fn int_or_unknown(bits: LogicValue) -> Value {
+        match IntValue::from_logic(&bits) {
+            Some(v) => v.into(),
+            None => bits.into(),
+        }
+    }
+
+    /// Turn the logic values at the integer positions of a type back into
+    /// integers once all of their bits are known.
+    pub fn into_known(self, ty: &Type) -> Value {
+        use crate::ty::TypeKind::*;
+        match (self, ty.as_ref()) {
+            (Value::Logic(v), IntType(_)) => Value::int_or_unknown(v),
+            (Value::Array(v), ArrayType(_, ty)) if v.packed_bits().is_none() => {
+                ArrayValue::new(v.iter().map(|v| v.into_known(ty)).collect()).into()
+            }
+            (Value::Struct(v), StructType(tys)) => StructValue::new(
+                v.0.into_iter()
+                    .zip(tys)
+                    .map(|(v, ty)| v.into_known(ty))
+                    .collect(),
+            )
+            .into(),
+            (v, _) => v,
+        }
+    }
+
+    /// Execute a unary integer opcode.
+    pub fn int_unary_op(op: Opcode, arg: &Value) -> Value {
+        if let Value::Int(arg) = arg {
+            return IntValue::unary_op(op, arg).into();
+        }
+        let arg = arg.to_logic();
+        match op {
+            Opcode::Not => Value::int_or_unknown(arg.not()),
+            _ => Value::unknown(&arg.ty()),
+        }
+    }
+
+    /// Execute a binary integer opcode. Returns `None` if a division or
+    /// remainder has a zero divisor.
+    pub fn int_binary_op(op: Opcode, lhs: &Value, rhs: &Value) -> Option<Value> {
+        if let (Value::Int(lhs), Value::Int(rhs)) = (lhs, rhs) {
+            return IntValue::binary_op(op, lhs, rhs).map(Into::into);
+        }
+        Some(match op {
+            Opcode::And | Opcode::Or | Opcode::Xor => {
+                Value::int_or_unknown(LogicValue::binary_op(op, &lhs.to_logic(), &rhs.to_logic()))
+            }
+            _ => match (lhs.to_known_int(), rhs.to_known_int()) {
+                (Some(lhs), Some(rhs)) => IntValue::binary_op(op, &lhs, &rhs)?.into(),
+                _ => Value::unknown(&lhs.to_logic().ty()),
+            },
+        })
+    }
+
+    /// Execute a comparison opcode on integers.
+    pub fn int_compare_op(op: Opcode, lhs: &Value, rhs: &Value) -> Value {
+        match (lhs.to_known_int(), rhs.to_known_int()) {
+            (Some(lhs), Some(rhs)) => IntValue::compare_op(op, &lhs, &rhs).into(),
+            _ => Value::unknown(&crate::ty::int_ty(1)),
+        }
+    }
+}
+
+impl From<TimeValue> for Value {
+    fn from(v: TimeValue) -> Value {
+        Value::Time(v)
+    }
+}
+
+impl From<IntValue> for Value {
+    fn from(v: IntValue) -> Value {
+        Value::Int(v)
+    }
+}
+
+impl From<LogicValue> for Value {
+    fn from(v: LogicValue) -> Value {
+        Value::Logic(v)
+    }
+}
+
+impl From<ArrayValue> for Value {
+    fn from(v: ArrayValue) -> Value {
+        Value::Array(v)
+    }
+}
+
+impl From<StructValue> for Value {
+    fn from(v: StructValue) -> Value {
+        Value::Struct(v)
+    }
+}
+
+impl From<crate::value::Value> for Value {
+    fn from(v: crate::value::Value) -> Value {
+        use crate::value::Value as V;
+        match v {
+            V::Void => Value::Void,
+            V::Time(v) => Value::Time(v),
+            V::Int(v) => Value::Int(v.into()),
+            V::Logic(v) => Value::Logic(v),
+            V::Array(v) => ArrayValue::new(v.0.into_iter().map(Into::into).collect()).into(),
+            V::Struct(v) => StructValue::new(v.0.into_iter().map(Into::into).collect()).into(),
+        }
+    }
+}
+
+impl From<Value> for crate::value::Value {
+    fn from(v: Value) -> crate::value::Value {
+        use crate::value::Value as V;
+        match v {
+            Value::Void => V::Void,
+            Value::Time(v) => V::Time(v),
+            Value::Int(v) => V::Int(v.into()),
+            Value::Logic(v) => V::Logic(v),
+            Value::Array(v) => {
+                V::Array(crate::value::ArrayValue(v.iter().map(Into::into).collect()))
+            }
+            Value::Struct(v) => V::Struct(crate::value::StructValue(
+                v.0.into_iter().map(Into::into).collect(),
+            )),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Time(v) => write!(f, "time {}", v),
+            Value::Int(v) => Display::fmt(v, f),
+            Value::Logic(v) => Debug::fmt(v, f),
+            Value::Array(v) => Display::fmt(v, f),
+            Value::Struct(v) => Display::fmt(v, f),
+        }
+    }
+}
+
+impl Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// An integer value.
+///
+/// Values of up to 64 bits are stored in a single machine word, wider values
+/// as a contiguous sequence of words. Bits beyond the width of the value are
+/// always zero.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IntValue {
+    width: usize,
+    bits: Bits,
+}
+
+/// The storage of an integer value.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum Bits {
+    /// A value of up to 64 bits.
+    Word(u64),
+    /// A wider value, least significant word first.
+    Wide(Box<[u64]>),
+}
+
+/// The number of words needed to store a number of bits.
+fn num_words(width: usize) -> usize {
+    width.div_ceil(64)
+}
+
+/// A mask of the lower `width` bits of a word.
+fn mask(width: usize) -> u64 {
+    if width >= 64 {
+        !0
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// A mask of the bits of the most significant word of a value.
+fn top_mask(width: usize) -> u64 {
+    match width % 64 {
+        0 => !0,
+        r => mask(r),
+    }
+}
+
+impl IntValue {
+    /// Create a new integer value from a `usize`.
+    pub fn from_usize(width: usize, value: usize) -> Self {
+        Self::from_u64(width, value as u64)
+    }
+
+    /// Create a new integer value from a `u64`.
+    pub fn from_u64(width: usize, value: u64) -> Self {
+        if width <= 64 {
+            Self {
+                width,
+                bits: Bits::Word(value & mask(width)),
+            }
+        } else {
+            Self::from_words(width, vec![value])
+        }
+    }
+
+    /// Create a new integer value from its words, least significant first.
+    /// Missing words are zero, and excess bits are discarded.
+    fn from_words(width: usize, mut words: Vec<u64>) -> Self {
+        if width <= 64 {
+            return Self::from_u64(width, words.first().copied().unwrap_or(0));
+        }
+        words.resize(num_words(width), 0);
+        *words.last_mut().unwrap() &= top_mask(width);
+        Self {
+            width,
+            bits: Bits::Wide(words.into_boxed_slice()),
+        }
+    }
+
+    /// Create a new integer value from a signed `BigInt` value.
+    pub fn from_signed(width: usize, value: BigInt) -> Self {
+        let modulus = BigInt::one() << width;
+        let mut v = value % &modulus;
+        if v.is_negative() {
+            v += modulus;
+        }
+        assert!(!v.is_negative());
+        Self::from_unsigned(width, v.to_biguint().unwrap())
+    }
+
+    /// Create a new integer value from an unsigned `BigUint` value.
+    pub fn from_unsigned(width: usize, value: BigUint) -> Self {
+        Self::from_words(width, value.to_u64_digits())
+    }
+
+    /// Create a new integer value from an `i64`, sign-extending it to the
+    /// width.
+    fn from_i64(width: usize, value: i64) -> Self {
+        if width <= 64 {
+            Self::from_u64(width, value as u64)
+        } else {
+            Self::from_signed(width, value.into())
+        }
+    }
+
+    /// The width of the value in bits.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The words of the value, least significant first.
+    fn words(&self) -> &[u64] {
+        match &self.bits {
+            Bits::Word(w) => std::slice::from_ref(w),
+            Bits::Wide(ws) => ws,
+        }
+    }
+
+    /// The words of the value, least significant first.
+    fn words_mut(&mut self) -> &mut [u64] {
+        match &mut self.bits {
+            Bits::Word(w) => std::slice::from_mut(w),
+            Bits::Wide(ws) => ws,
+        }
+    }
+
+    /// The value as a word, if it is at most 64 bits wide.
+    fn word(&self) -> Option<u64> {
+        match self.bits {
+            Bits::Word(w) => Some(w),
+            Bits::Wide(_) => None,
+        }
+    }
+
+    /// The value of a word-sized value, sign-extended to an `i64`.
+    fn sext(width: usize, w: u64) -> i64 {
+        if width == 0 {
+            0
+        } else {
+            ((w << (64 - width)) as i64) >> (64 - width)
+        }
+    }
+
+    /// Convert the value to an unsigned `BigUint`.
+    pub fn to_unsigned(&self) -> BigUint {
+        self.words()
+            .iter()
+            .rev()
+            .fold(BigUint::zero(), |acc, &w| (acc << 64u32) | BigUint::from(w))
+    }
+
+    /// Convert the value to a signed `BigInt`.
+    pub fn to_signed(&self) -> BigInt {
+        let value = self.to_unsigned().to_bigint().unwrap();
+        if self.sign_bit() {
+            value - (BigInt::one() << self.width)
+        } else {
+            value
+        }
+    }
+
+    /// Convert the value to a usize.
+    pub fn to_usize(&self) -> usize {
+        let words = self.words();
+        assert!(
+            words[1..].iter().all(|&w| w == 0),
+            "{} does not fit into a usize",
+            self
+        );
+        words[0].to_usize().unwrap()
+    }
+
+    /// Get a single bit of the value.
+    pub fn bit(&self, index: usize) -> bool {
+        self.words()
+            .get(index / 64)
+            .map(|w| w >> (index % 64) & 1 != 0)
+            .unwrap_or(false)
+    }
+
+    /// Convert the value to logic bits.
+    pub fn to_logic(&self) -> LogicValue {
+        LogicValue::new((0..self.width).map(|i| self.bit(i).into()).collect())
+    }
+
+    /// Convert logic bits to an integer, if all bits carry a logic zero or one.
+    pub fn from_logic(value: &LogicValue) -> Option<Self> {
+        let mut bits = BigUint::zero();
+        for (i, bit) in value.0.iter().enumerate() {
+            if bit.to_bool()? {
+                bits.set_bit(i as u64, true);
+            }
+        }
+        Some(Self::from_unsigned(value.width(), bits))
+    }
+
+    /// Check whether the most significant bit is set.
+    fn sign_bit(&self) -> bool {
+        self.width > 0 && self.bit(self.width - 1)
+    }
+
+    /// Check if the value is zero.
+    pub fn is_zero(&self) -> bool {
+        self.words().iter().all(|&w| w == 0)
+    }
+
+    /// Check if the value is one.
+    pub fn is_one(&self) -> bool {
+        let words = self.words();
+        words[0] == 1 && words[1..].iter().all(|&w| w == 0)
+    }
+
+    /// Apply an operation to the corresponding words of two values.
+    fn zip_words(&self, other: &Self, f: impl Fn(u64, u64) -> u64) -> IntValue {
+        assert_eq!(self.width, other.width);
+        match (self.word(), other.word()) {
+            (Some(a), Some(b)) => Self::from_u64(self.width, f(a, b)),
+            _ => Self::from_words(
+                self.width,
+                self.words()
+                    .iter()
+                    .zip(other.words())
+                    .map(|(&a, &b)| f(a, b))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Compare the unsigned magnitude of two values.
+    fn cmp_unsigned(&self, other: &Self) -> Ordering {
+        self.words().iter().rev().cmp(other.words().iter().rev())
+    }
+
+    /// Compare the signed value of two values.
+    fn cmp_signed(&self, other: &Self) -> Ordering {
+        match (self.sign_bit(), other.sign_bit()) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => self.cmp_unsigned(other),
+        }
+    }
+
+    /// Format the value in a radix that is a power of two.
+    fn fmt_radix(&self, f: &mut std::fmt::Formatter, bits: usize) -> std::fmt::Result {
+        let digit = |i: usize| {
+            let mut d = 0;
+            for b in (0..bits).rev() {
+                d = d << 1 | self.bit(i * bits + b) as u32;
+            }
+            std::char::from_digit(d, 1 << bits).unwrap()
+        };
+        let num = std::cmp::max(1, self.width.div_ceil(bits));
+        let digits: String = (0..num)
+            .rev()
+            .skip_while(|&i| i > 0 && digit(i) == '0')
+            .map(digit)
+            .collect();
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+impl From<crate::value::IntValue> for IntValue {
+    fn from(v: crate::value::IntValue) -> IntValue {
+        IntValue::from_unsigned(v.width, v.value)
+    }
+}
+
+impl From<IntValue> for crate::value::IntValue {
+    fn from(v: IntValue) -> crate::value::IntValue {
+        crate::value::IntValue::from_unsigned(v.width, v.to_unsigned())
+    }
+}
+
+impl PartialOrd for IntValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IntValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.width
+            .cmp(&other.width)
+            .then_with(|| self.cmp_unsigned(other))
+    }
+}
+
+impl Display for IntValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.word() {
+            Some(w) => write!(f, "i{} {}", self.width, w),
+            None => write!(f, "i{} {}", self.width, self.to_unsigned()),
+        }
+    }
+}
+
+impl Debug for IntValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Binary for IntValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_radix(f, 1)
+    }
+}
+
+impl std::fmt::LowerHex for IntValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_radix(f, 4)
+    }
+}
+
+/// Slicing.
+impl IntValue {
+    /// Extract a slice of bits from the value. Bits beyond the width of the
+    /// value are zero.
+    pub fn extract_slice(&self, off: usize, len: usize) -> IntValue {
+        if let Some(w) = self.word() {
+            return Self::from_u64(len, w.checked_shr(off as u32).unwrap_or(0));
+        }
+        let words = self.words();
+        let word_at = |pos: usize| {
+            let (i, s) = (pos / 64, pos % 64);
+            let lo = words.get(i).map(|w| w >> s).unwrap_or(0);
+            let hi = match s {
+                0 => 0,
+                _ => words.get(i + 1).map(|w| w << (64 - s)).unwrap_or(0),
+            };
+            lo | hi
+        };
+        Self::from_words(
+            len,
+            (0..num_words(len)).map(|i| word_at(off + i * 64)).collect(),
+        )
+    }
+
+    /// Insert a slice of bits into the value, in place. Bits beyond the width
+    /// of the value are discarded.
+    pub fn insert_slice(&mut self, off: usize, len: usize, value: &IntValue) {
+        assert_eq!(len, value.width);
+        let width = self.width;
+        let words = self.words_mut();
+        let mut put = |pos: usize, num: usize, bits: u64| {
+            let (i, s) = (pos / 64, pos % 64);
+            let m = mask(num);
+            if let Some(w) = words.get_mut(i) {
+                *w = *w & !(m << s) | (bits & m) << s;
+            }
+            if s + num > 64 {
+                if let Some(w) = words.get_mut(i + 1) {
+                    *w = *w & !(m >> (64 - s)) | (bits & m) >> (64 - s);
+                }
+            }
+        };
+        for (i, &bits) in value.words().iter().enumerate() {
+            let pos = off + i * 64;
+            if pos >= width {
+                break;
+            }
+            put(pos, std::cmp::min(64, len - i * 64), bits);
+        }
+        if let Some(last) = words.last_mut() {
+            *last &= top_mask(width);
+        }
+    }
+}
+
+/// Unary operators.
+impl IntValue {
+    /// Compute `not`.
+    pub fn not(&self) -> IntValue {
+        match self.word() {
+            Some(w) => Self::from_u64(self.width, !w),
+            None => Self::from_words(self.width, self.words().iter().map(|w| !w).collect()),
+        }
+    }
+
+    /// Compute `neg`.
+    pub fn neg(&self) -> IntValue {
+        Self::from_u64(self.width, 0).sub(self)
+    }
+}
+
+/// Binary operators.
+impl IntValue {
+    /// Compute `add`.
+    pub fn add(&self, other: &Self) -> IntValue {
+        if let (Some(a), Some(b)) = (self.word(), other.word()) {
+            return Self::from_u64(self.width, a.wrapping_add(b));
+        }
+        let mut carry = false;
+        self.zip_words_carry(other, |a, b| {
+            let (s, c1) = a.overflowing_add(b);
+            let (s, c2) = s.overflowing_add(carry as u64);
+            carry = c1 || c2;
+            s
+        })
+    }
+
+    /// Compute `sub`.
+    pub fn sub(&self, other: &Self) -> IntValue {
+        if let (Some(a), Some(b)) = (self.word(), other.word()) {
+            return Self::from_u64(self.width, a.wrapping_sub(b));
+        }
+        let mut borrow = false;
+        self.zip_words_carry(other, |a, b| {
+            let (d, b1) = a.overflowing_sub(b);
+            let (d, b2) = d.overflowing_sub(borrow as u64);
+            borrow = b1 || b2;
+            d
+        })
+    }
+
+    /// Apply an operation that carries state from one word to the next, least
+    /// significant word first.
+    fn zip_words_carry(&self, other: &Self, mut f: impl FnMut(u64, u64) -> u64) -> IntValue {
+        assert_eq!(self.width, other.width);
+        Self::from_words(
+            self.width,
+            self.words()
+                .iter()
+                .zip(other.words())
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+        )
+    }
+
+    /// Compute `and`.
+    pub fn and(&self, other: &Self) -> IntValue {
+        self.zip_words(other, |a, b| a & b)
+    }
+
+    /// Compute `or`.
+    pub fn or(&self, other: &Self) -> IntValue {
+        self.zip_words(other, |a, b| a | b)
+    }
+
+    /// Compute `xor`.
+    pub fn xor(&self, other: &Self) -> IntValue {
+        self.zip_words(other, |a, b| a ^ b)
+    }
+
+    /// Compute `umul`.
+    pub fn umul(&self, other: &Self) -> IntValue {
+        match (self.word(), other.word()) {
+            (Some(a), Some(b)) => Self::from_u64(self.width, a.wrapping_mul(b)),
+            _ => Self::from_unsigned(self.width, self.to_unsigned() * other.to_unsigned()),
+        }
+    }
+
+    /// Compute `udiv`. The divisor must not be zero.
+    pub fn udiv(&self, other: &Self) -> IntValue {
+        match (self.word(), other.word()) {
+            (Some(a), Some(b)) => Self::from_u64(self.width, a / b),
+            _ => Self::from_unsigned(self.width, self.to_unsigned() / other.to_unsigned()),
+        }
+    }
+
+    /// Compute `umod`. The divisor must not be zero.
+    pub fn umod(&self, other: &Self) -> IntValue {
+        self.urem(other)
+    }
+
+    /// Compute `urem`. The divisor must not be zero.
+    pub fn urem(&self, other: &Self) -> IntValue {
+        match (self.word(), other.word()) {
+            (Some(a), Some(b)) => Self::from_u64(self.width, a % b),
+            _ => Self::from_unsigned(self.width, self.to_unsigned() % other.to_unsigned()),
+        }
+    }
+
+    /// Apply a signed operation, either on sign-extended words or on `BigInt`.
+    fn signed_op(
+        &self,
+        other: &Self,
+        word: impl Fn(i64, i64) -> i64,
+        wide: impl Fn(BigInt, BigInt) -> BigInt,
+    ) -> IntValue {
+        match (self.word(), other.word()) {
+            (Some(a), Some(b)) => Self::from_i64(
+                self.width,
+                word(Self::sext(self.width, a), Self::sext(other.width, b)),
+            ),
+            _ => Self::from_signed(self.width, wide(self.to_signed(), other.to_signed())),
+        }
+    }
+
+    /// Compute `smul`.
+    pub fn smul(&self, other: &Self) -> IntValue {
+        self.signed_op(other, i64::wrapping_mul, |a, b| a * b)
+    }
+
+    /// Compute `sdiv`. The divisor must not be zero.
+    pub fn sdiv(&self, other: &Self) -> IntValue {
+        self.signed_op(other, i64::wrapping_div, |a, b| a / b)
+    }
+
+    /// Compute `smod`, whose result has the sign of the divisor. The divisor
+    /// must not be zero.
+    pub fn smod(&self, other: &Self) -> IntValue {
+        self.signed_op(
+            other,
+            |a, b| {
+                let r = a.wrapping_rem(b);
+                if r != 0 && (r < 0) != (b < 0) {
+                    r.wrapping_add(b)
+                } else {
+                    r
+                }
+            },
+            |a, b| {
+                let r = &a % &b;
+                if !r.is_zero() && r.is_negative() != b.is_negative() {
+                    r + b
+                } else {
+                    r
+                }
+            },
+        )
+    }
+
+    /// Compute `srem`, whose result has the sign of the dividend. The divisor
+    /// must not be zero.
+    pub fn srem(&self, other: &Self) -> IntValue {
+        self.signed_op(other, i64::wrapping_rem, |a, b| a % b)
+    }
+}
+
+/// Comparisons.
+impl IntValue {
+    /// Compute `==`.
+    pub fn eq(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.bits == other.bits
+    }
+
+    /// Compute `!=`.
+    pub fn neq(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.bits != other.bits
+    }
+
+    /// Compute unsigned `<`.
+    pub fn ult(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_unsigned(other) == Ordering::Less
+    }
+
+    /// Compute unsigned `>`.
+    pub fn ugt(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_unsigned(other) == Ordering::Greater
+    }
+
+    /// Compute unsigned `<=`.
+    pub fn ule(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_unsigned(other) != Ordering::Greater
+    }
+
+    /// Compute unsigned `>=`.
+    pub fn uge(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_unsigned(other) != Ordering::Less
+    }
+
+    /// Compute signed `<`.
+    pub fn slt(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_signed(other) == Ordering::Less
+    }
+
+    /// Compute signed `>`.
+    pub fn sgt(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_signed(other) == Ordering::Greater
+    }
+
+    /// Compute signed `<=`.
+    pub fn sle(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_signed(other) != Ordering::Greater
+    }
+
+    /// Compute signed `>=`.
+    pub fn sge(&self, other: &Self) -> bool {
+        assert_eq!(self.width, other.width);
+        self.cmp_signed(other) != Ordering::Less
+    }
+}
+
+/// Opcode implementations.
+impl IntValue {
+    /// Execute a unary opcode.
+    pub fn unary_op(op: Opcode, arg: &IntValue) -> IntValue {
+        // trace!("{} ({}, {})", op, lhs, rhs);
+        match op {
+            Opcode::Not => arg.not(),
+            Opcode::Neg => arg.neg(),
+            _ => panic!("{} is not a unary op", op),
+        }
+    }
+
+    /// Execute a binary opcode. Returns `None` if a division or remainder has a
+    /// zero divisor.
+    pub fn binary_op(op: Opcode, lhs: &IntValue, rhs: &IntValue) -> Option<IntValue> {
+        trace!("{} ({}, {})", op, lhs, rhs);
+        let division = matches!(
+            op,
+            Opcode::Sdiv | Opcode::Smod | Opcode::Srem | Opcode::Udiv | Opcode::Umod | Opcode::Urem
+        );
+        if division && rhs.is_zero() {
+            return None;
+        }
+        Some(match op {
+            Opcode::Add => lhs.add(rhs),
+            Opcode::Sub => lhs.sub(rhs),
+            Opcode::And => lhs.and(rhs),
+            Opcode::Or => lhs.or(rhs),
+            Opcode::Xor => lhs.xor(rhs),
+            Opcode::Smul => lhs.smul(rhs),
+            Opcode::Sdiv => lhs.sdiv(rhs),
+            Opcode::Smod => lhs.smod(rhs),
+            Opcode::Srem => lhs.srem(rhs),
+            Opcode::Umul => lhs.umul(rhs),
+            Opcode::Udiv => lhs.udiv(rhs),
+            Opcode::Umod => lhs.umod(rhs),
+            Opcode::Urem => lhs.urem(rhs),
+            _ => panic!("{} is not a binary op", op),
+        })
+    }
+
+    /// Execute a comparison opcode.
+    pub fn compare_op(op: Opcode, lhs: &IntValue, rhs: &IntValue) -> IntValue {
+        // trace!("{} ({}, {})", op, lhs, rhs);
+        let v = match op {
+            Opcode::Eq => lhs.eq(rhs),
+            Opcode::Neq => lhs.neq(rhs),
+            Opcode::Ult => lhs.ult(rhs),
+            Opcode::Ugt => lhs.ugt(rhs),
+            Opcode::Ule => lhs.ule(rhs),
+            Opcode::Uge => lhs.uge(rhs),
+            Opcode::Slt => lhs.slt(rhs),
+            Opcode::Sgt => lhs.sgt(rhs),
+            Opcode::Sle => lhs.sle(rhs),
+            Opcode::Sge => lhs.sge(rhs),
+            _ => panic!("{} is not a compare op", op),
+        };
+        IntValue::from_usize(1, v as usize)
+    }
+}
+
+/// An array value.
+///
+/// Arrays of integers store the bits of their elements contiguously, element
+/// `i` of width `w` occupying bits `i*w` to `(i+1)*w`. All other arrays store
+/// their elements individually.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArrayValue(Elements);
+
+/// The storage of an array value.
+#[derive(Clone, Serialize, Deserialize)]
+enum Elements {
+    /// Integer elements of equal width.
+    Packed {
+        len: usize,
+        width: usize,
+        bits: IntValue,
+    },
+    /// Any other elements.
+    Values(Vec<Value>),
+}
+
+impl ArrayValue {
+    /// Create a new uniform array.
+    pub fn new_uniform(length: usize, value: Value) -> Self {
+        match value {
+            Value::Int(v) => {
+                let width = v.width;
+                let mut bits = IntValue::from_u64(length * width, 0);
+                if !v.is_zero() {
+                    for i in 0..length {
+                        bits.insert_slice(i * width, width, &v);
+                    }
+                }
+                ArrayValue(Elements::Packed {
+                    len: length,
+                    width,
+                    bits,
+                })
+            }
+            value => ArrayValue(Elements::Values(vec![value; length])),
+        }
+    }
+
+    /// Create a new array.
+    pub fn new(values: Vec<Value>) -> Self {
+        let width = match values.first() {
+            Some(Value::Int(v)) => v.width,
+            _ => return ArrayValue(Elements::Values(values)),
+        };
+        if !values
+            .iter()
+            .all(|v| v.get_int().map(|v| v.width) == Some(width))
+        {
+            return ArrayValue(Elements::Values(values));
+        }
+        let mut bits = IntValue::from_u64(values.len() * width, 0);
+        for (i, v) in values.iter().enumerate() {
+            bits.insert_slice(i * width, width, v.unwrap_int());
+        }
+        ArrayValue(Elements::Packed {
+            len: values.len(),
+            width,
+            bits,
+        })
+    }
+
+    /// The number of elements in the array.
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Elements::Packed { len, .. } => *len,
+            Elements::Values(vs) => vs.len(),
+        }
+    }
+
+    /// Check whether the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the elements of the array.
+    pub fn iter(&self) -> impl Iterator<Item = Value> + '_ {
+        (0..self.len()).map(move |i| self.extract_field(i))
+    }
+
+    /// Access the element width and the contiguous bits of an integer array.
+    pub(crate) fn packed_mut(&mut self) -> Option<(usize, &mut IntValue)> {
+        match &mut self.0 {
+            Elements::Packed { width, bits, .. } => Some((*width, bits)),
+            Elements::Values(_) => None,
+        }
+    }
+
+    /// Access the contiguous bits of an integer array.
+    pub(crate) fn packed_bits(&self) -> Option<&IntValue> {
+        match &self.0 {
+            Elements::Packed { bits, .. } => Some(bits),
+            Elements::Values(_) => None,
+        }
+    }
+
+    /// Store the elements of an integer array individually, such that they
+    /// can hold unknown bits.
+    pub(crate) fn unpack(&mut self) {
+        if let Elements::Packed { .. } = self.0 {
+            self.0 = Elements::Values(self.iter().collect());
+        }
+    }
+
+    /// Access an element of an array that is not packed.
+    pub(crate) fn field_mut(&mut self, idx: usize) -> Option<&mut Value> {
+        match &mut self.0 {
+            Elements::Packed { .. } => None,
+            Elements::Values(vs) => Some(&mut vs[idx]),
+        }
+    }
+}
+
+impl PartialEq for ArrayValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (
+                Elements::Packed {
+                    len: la,
+                    width: wa,
+                    bits: ba,
+                },
+                Elements::Packed {
+                    len: lb,
+                    width: wb,
+                    bits: bb,
+                },
+            ) => la == lb && wa == wb && ba == bb,
+            _ => self.len() == other.len() && self.iter().eq(other.iter()),
+        }
+    }
+}
+
+impl Eq for ArrayValue {}
+
+impl Display for ArrayValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut first = true;
+        write!(f, "[")?;
+        for v in self.iter() {
+            if !first {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", v)?;
+            first = false;
+        }
+        write!(f, "]")
+    }
+}
+
+impl Debug for ArrayValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Slicing.
+impl ArrayValue {
+    /// Extract a single element from the array.
+    pub fn extract_field(&self, idx: usize) -> Value {
+        match &self.0 {
+            Elements::Packed { width, bits, .. } => bits.extract_slice(idx * width, *width).into(),
+            Elements::Values(vs) => vs[idx].clone(),
+        }
+    }
+
+    /// Extract a slice of elements from the array.
+    pub fn extract_slice(&self, off: usize, len: usize) -> ArrayValue {
+        match &self.0 {
+            Elements::Packed { width, bits, .. } => ArrayValue(Elements::Packed {
+                len,
+                width: *width,
+                bits: bits.extract_slice(off * width, len * width),
+            }),
+            Elements::Values(vs) => ArrayValue(Elements::Values(vs[off..off + len].to_vec())),
+        }
+    }
+
+    /// Insert a single element into the array, in place.
+    pub fn insert_field(&mut self, idx: usize, value: Value) {
+        match (&mut self.0, value) {
+            (Elements::Packed { width, bits, .. }, Value::Int(v)) => {
+                bits.insert_slice(idx * *width, *width, &v)
+            }
+            (Elements::Values(vs), value) => vs[idx] = value,
+            (_, value) => {
+                self.unpack();
+                self.insert_field(idx, value);
+            }
+        }
+    }
+
+    /// Insert a slice of elements into the array, in place.
+    pub fn insert_slice(&mut self, off: usize, len: usize, value: &ArrayValue) {
+        assert_eq!(len, value.len());
+        match (&mut self.0, &value.0) {
+            (Elements::Packed { width, bits, .. }, Elements::Packed { bits: from, .. }) => {
+                bits.insert_slice(off * *width, len * *width, from)
+            }
+            (Elements::Values(vs), Elements::Values(from)) => {
+                vs[off..off + len].clone_from_slice(from)
+            }
+            _ => {
+                for (i, v) in value.iter().enumerate() {
+                    self.insert_field(off + i, v);
+                }
+            }
+        }
+    }
+}
+
+/// A struct value.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructValue(pub Vec<Value>);
+
+impl StructValue {
+    /// Create a new struct.
+    pub fn new(values: Vec<Value>) -> Self {
+        StructValue(values)
+    }
+}
+
+impl Display for StructValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut first = true;
+        write!(f, "{{")?;
+        for v in &self.0 {
+            if !first {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", v)?;
+            first = false;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl Debug for StructValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Slicing.
+impl StructValue {
+    /// Extract a single field from the struct.
+    pub fn extract_field(&self, idx: usize) -> Value {
+        self.0[idx].clone()
+    }
+
+    /// Insert a field into the struct.
+    pub fn insert_field(&mut self, idx: usize, value: Value) {
+        self.0[idx] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(width: usize, value: isize) -> IntValue {
+        IntValue::from_signed(width, value.into())
+    }
+
+    #[test]
+    fn signed() {
+        for &width in &[8, 100] {
+            let a = int(width, -7);
+            let b = int(width, 2);
+            assert_eq!(a.to_signed(), BigInt::from(-7));
+            assert_eq!(a.sub(&b), int(width, -9));
+            assert_eq!(b.sub(&a), int(width, 9));
+            assert_eq!(a.neg(), int(width, 7));
+            assert_eq!(a.smul(&b), int(width, -14));
+            assert_eq!(a.sdiv(&b), int(width, -3));
+            assert_eq!(a.srem(&b), int(width, -1));
+            assert_eq!(a.smod(&b), int(width, 1));
+            assert!(a.slt(&b) && a.ugt(&b));
+        }
+    }
+
+    #[test]
+    fn wide() {
+        let a = IntValue::from_unsigned(100, (BigUint::one() << 99) + BigUint::from(5u32));
+        let b = IntValue::from_usize(100, 3);
+        assert_eq!(
+            a.add(&b).to_unsigned(),
+            (BigUint::one() << 99) + BigUint::from(8u32)
+        );
+        assert_eq!(a.add(&a), IntValue::from_usize(100, 10));
+        assert_eq!(a.not().not(), a);
+        assert_eq!(format!("{:x}", b.not()), "f".repeat(24) + "c");
+        assert_eq!(format!("{:04b}", b), "0011");
+        assert!(a.bit(99) && !a.bit(98));
+    }
+
+    #[test]
+    fn zero_divisor() {
+        for &width in &[8, 100] {
+            let (a, zero) = (int(width, -7), int(width, 0));
+            for &op in &[
+                Opcode::Sdiv,
+                Opcode::Smod,
+                Opcode::Srem,
+                Opcode::Udiv,
+                Opcode::Umod,
+                Opcode::Urem,
+            ] {
+                assert_eq!(IntValue::binary_op(op, &a, &zero), None);
+            }
+            assert_eq!(
+                IntValue::binary_op(Opcode::Udiv, &zero, &a),
+                Some(zero.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn slices() {
+        let mut a = IntValue::from_unsigned(130, BigUint::one() << 129);
+        assert_eq!(a.extract_slice(126, 4), IntValue::from_usize(4, 8));
+        a.insert_slice(60, 8, &IntValue::from_usize(8, 0xff));
+        assert_eq!(a.extract_slice(59, 10), IntValue::from_usize(10, 0x1fe));
+        assert_eq!(a.extract_slice(129, 3), IntValue::from_usize(3, 1));
+
+        let mut b = IntValue::from_usize(16, 0x1234);
+        b.insert_slice(4, 8, &IntValue::from_usize(8, 0xab));
+        assert_eq!(b, IntValue::from_usize(16, 0x1ab4));
+    }
+
+    #[test]
+    fn packed_arrays() {
+        let elems: Vec<Value> = (0..5).map(|i| IntValue::from_usize(3, i).into()).collect();
+        let mut a = ArrayValue::new(elems.clone());
+        assert!(a.packed_bits().is_some());
+        assert_eq!(a.iter().collect::<Vec<_>>(), elems);
+        assert_eq!(a.extract_slice(1, 3), ArrayValue::new(elems[1..4].to_vec()));
+        a.insert_field(2, IntValue::from_usize(3, 7).into());
+        assert_eq!(a.extract_field(2), IntValue::from_usize(3, 7).into());
+        assert_eq!(a.extract_field(3), elems[3]);
+        assert_eq!(
+            ArrayValue::new_uniform(3, IntValue::from_usize(2, 1).into()).to_string(),
+            "[i2 1, i2 1, i2 1]"
+        );
+    }
+
+    #[test]
+    fn unknown_ints() {
+        let x: Value = LogicValue::parse("01XX").unwrap().into();
+        let five: Value = IntValue::from_usize(4, 5).into();
+        assert!(x.is_unknown() && !five.is_unknown());
+        assert_eq!(
+            Value::int_binary_op(Opcode::Add, &x, &five).unwrap(),
+            Value::unknown(&crate::ty::int_ty(4))
+        );
+        assert_eq!(
+            Value::int_binary_op(Opcode::And, &x, &five)
+                .unwrap()
+                .to_string(),
+            "l4 \"010X\""
+        );
+        assert_eq!(
+            Value::int_binary_op(Opcode::And, &x, &IntValue::from_usize(4, 12).into()),
+            Some(IntValue::from_usize(4, 4).into())
+        );
+        assert_eq!(
+            Value::int_compare_op(Opcode::Eq, &x, &five),
+            Value::unknown(&crate::ty::int_ty(1))
+        );
+
+        // Writing known bits turns the value back into an integer.
+        let known: Value = LogicValue::parse("0101").unwrap().into();
+        assert_eq!(known.into_known(&crate::ty::int_ty(4)), five);
+        let mut a = ArrayValue::new(vec![five.clone(), five.clone()]);
+        a.insert_field(1, x.clone());
+        assert_eq!(a.extract_field(1), x);
+        assert_eq!(a.extract_field(0), five);
+    }
+
+    #[test]
+    fn parse() {
+        let ty = crate::ty::int_ty(8);
+        let parse = |text| Value::parse(&ty, text);
+        assert_eq!(parse("255"), Some(int(8, 255).into()));
+        assert_eq!(parse("-128"), Some(int(8, -128).into()));
+        assert_eq!(parse("0xff"), Some(int(8, 255).into()));
+        assert_eq!(parse("256"), None);
+        assert_eq!(parse("300"), None);
+        assert_eq!(parse("-129"), None);
+        assert_eq!(parse("0x1_00"), None);
+        assert_eq!(Value::parse(&crate::ty::int_ty(0), "1"), None);
+
+        let ty = crate::ty::logic_ty(4);
+        let parse = |text| Value::parse(&ty, text);
+        let logic = |text| Some(LogicValue::parse(text).unwrap().into());
+        assert_eq!(parse("01XZ"), logic("01XZ"));
+        assert_eq!(parse("01X"), None);
+        assert_eq!(parse("0b1X"), logic("001X"));
+        assert_eq!(parse("0bX1"), logic("XXX1"));
+        assert_eq!(parse("0bZ"), logic("ZZZZ"));
+        assert_eq!(parse("0b10101"), None);
+        assert_eq!(parse("0b"), None);
+    }
+
+    #[test]
+    fn conversions() {
+        use crate::value as v;
+        let ints: Vec<v::Value> = (0..3)
+            .map(|i| v::IntValue::from_usize(70, i).into())
+            .collect();
+        let value: v::Value = v::StructValue(vec![
+            v::ArrayValue(ints).into(),
+            v::LogicValue::parse("01XZ").unwrap().into(),
+            v::Value::Void,
+        ])
+        .into();
+        let sim: Value = value.clone().into();
+        assert!(sim.unwrap_struct().0[0]
+            .unwrap_array()
+            .packed_bits()
+            .is_some());
+        assert_eq!(v::Value::from(sim), value);
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! X propagation
+//!
+//! Signals start out with the initial value given in the design, or zero for
+//! the ports of the simulated unit. This hides designs that rely on state they
+//! never reset. With X propagation enabled, the ports of the simulated unit and
+//! the signals stored by `reg` instructions instead start out as `X`, and
+//! integers carry unknown bits through the simulation as logic values. See the
+//! `value` module for how instructions act on such integers.
+//!
+//! Branches and conditional drives cannot proceed pessimistically on an
+//! unknown condition. They treat the condition as false, and record a warning
+//! for the instruction.
+
+use crate::{
+    ir::{Inst, Unit},
+    sim::value::TimeValue,
+};
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+/// An instruction that observed an unknown value it could not propagate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XpropWarning {
+    /// The name of the unit containing the instruction.
+    pub unit: String,
+    /// The instruction.
+    pub inst: String,
+    /// A description of the unknown value.
+    pub message: String,
+    /// The time at which the instruction first observed an unknown value.
+    pub time: TimeValue,
+    /// The number of times the instruction observed an unknown value.
+    pub count: u64,
+}
+
+impl fmt::Display for XpropWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} in {} at {}: `{}`",
+            self.message, self.unit, self.time, self.inst
+        )?;
+        if self.count > 1 {
+            write!(f, " ({} times)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The X propagation state of a running simulation.
+#[derive(Default)]
+pub(crate) struct XpropState {
+    warnings: Mutex<Warnings>,
+}
+
+/// The warnings recorded so far.
+#[derive(Default)]
+struct Warnings {
+    /// The warnings, in order of their first occurrence.
+    list: Vec<XpropWarning>,
+    /// The index of each instruction's warning, by unit and instruction.
+    index: HashMap<(String, String), usize>,
+}
+
+impl XpropState {
+    /// Record that an instruction observed an unknown value.
+    pub fn warn(&self, unit: Unit, inst: Inst, message: &str, time: &TimeValue) {
+        let key = (unit.name().to_string(), inst.dump(&unit).to_string());
+        let mut warnings = self.warnings.lock().unwrap();
+        if let Some(&i) = warnings.index.get(&key) {
+            warnings.list[i].count += 1;
+            return;
+        }
+        let i = warnings.list.len();
+        warnings.index.insert(key.clone(), i);
+        warnings.list.push(XpropWarning {
+            unit: key.0,
+            inst: key.1,
+            message: message.to_string(),
+            time: time.clone(),
+            count: 1,
+        });
+    }
+
+    /// The warnings recorded so far.
+    pub fn warnings(&self) -> Vec<XpropWarning> {
+        self.warnings.lock().unwrap().list.clone()
+    }
+}
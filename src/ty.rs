@@ -21,6 +21,8 @@ pub enum TypeKind {
     IntType(usize),
     /// Enumerated types like `n42`.
     EnumType(usize),
+    /// Logic types like `l32`.
+    LogicType(usize),
     /// Pointer types like `i32*`.
     PointerType(Type),
     /// Signal types like `i32$`.
@@ -42,6 +44,7 @@ impl std::fmt::Display for TypeKind {
             TimeType => write!(f, "time"),
             IntType(l) => write!(f, "i{}", l),
             EnumType(l) => write!(f, "n{}", l),
+            LogicType(l) => write!(f, "l{}", l),
             PointerType(ref ty) => write!(f, "{}*", ty),
             SignalType(ref ty) => write!(f, "{}$", ty),
             ArrayType(l, ref ty) => write!(f, "[{} x {}]", l, ty),
@@ -76,6 +79,15 @@ impl TypeKind {
         }
     }
 
+    /// Unwrap the type to its logic bit width, or panic if the type is not a
+    /// logic type.
+    pub fn unwrap_logic(&self) -> usize {
+        match *self {
+            LogicType(size) => size,
+            _ => panic!("unwrap_logic called on {}", self),
+        }
+    }
+
     /// Unwrap the type to its pointer data type, or panic if the type is not a
     /// pointer. E.g. yields the `i8` type in `i8*`.
     pub fn unwrap_pointer(&self) -> &Type {
@@ -162,6 +174,14 @@ impl TypeKind {
         }
     }
 
+    /// Check if this is a logic type.
+    pub fn is_logic(&self) -> bool {
+        match *self {
+            LogicType(..) => true,
+            _ => false,
+        }
+    }
+
     /// Check if this is a pointer type.
     pub fn is_pointer(&self) -> bool {
         match *self {
@@ -215,13 +235,14 @@ impl TypeKind {
     /// This is the number of:
     /// - bits in an integer
     /// - states in an enum
+    /// - bits in a logic value
     /// - elements in an array
     /// - fields in a struct
     ///
     /// Returns zero for all other types.
     pub fn len(&self) -> usize {
         match *self {
-            IntType(l) | EnumType(l) | ArrayType(l, _) => l,
+            IntType(l) | EnumType(l) | LogicType(l) | ArrayType(l, _) => l,
             StructType(ref f) => f.len(),
             _ => 0,
         }
@@ -248,6 +269,11 @@ pub fn enum_ty(size: usize) -> Type {
     Type::new(EnumType(size))
 }
 
+/// Create a logic type of the requested size.
+pub fn logic_ty(size: usize) -> Type {
+    Type::new(LogicType(size))
+}
+
 /// Create a pointer type with the requested data type.
 pub fn pointer_ty(ty: Type) -> Type {
     Type::new(PointerType(ty))
@@ -0,0 +1,479 @@
+// Copyright (c) 2017-2021 Fabian Schuiki
+
+//! Logic values
+//!
+//! This module implements nine-valued logic as defined by IEEE 1164.
+
+use crate::ir::prelude::*;
+use crate::ty::{logic_ty, Type};
+use crate::value::IntValue;
+use std::fmt::{Debug, Display};
+
+/// A single nine-valued logic bit.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LogicBit {
+    /// `U`, uninitialized.
+    U,
+    /// `X`, strong drive, unknown logic value.
+    X,
+    /// `0`, strong drive, logic zero.
+    Zero,
+    /// `1`, strong drive, logic one.
+    One,
+    /// `Z`, high impedance.
+    Z,
+    /// `W`, weak drive, unknown logic value.
+    W,
+    /// `L`, weak drive, logic zero.
+    L,
+    /// `H`, weak drive, logic one.
+    H,
+    /// `-`, don't care.
+    DontCare,
+}
+
+impl LogicBit {
+    /// Parse a logic bit from its character representation.
+    pub fn from_char(c: char) -> Option<LogicBit> {
+        Some(match c {
+            'U' => LogicBit::U,
+            'X' => LogicBit::X,
+            '0' => LogicBit::Zero,
+            '1' => LogicBit::One,
+            'Z' => LogicBit::Z,
+            'W' => LogicBit::W,
+            'L' => LogicBit::L,
+            'H' => LogicBit::H,
+            '-' => LogicBit::DontCare,
+            _ => return None,
+        })
+    }
+
+    /// Get the character representation of the logic bit.
+    pub fn to_char(self) -> char {
+        match self {
+            LogicBit::U => 'U',
+            LogicBit::X => 'X',
+            LogicBit::Zero => '0',
+            LogicBit::One => '1',
+            LogicBit::Z => 'Z',
+            LogicBit::W => 'W',
+            LogicBit::L => 'L',
+            LogicBit::H => 'H',
+            LogicBit::DontCare => '-',
+        }
+    }
+
+    /// Get the character of the bit among the four states of Verilog and VCD.
+    ///
+    /// Weak zeros and ones map to `0` and `1`, and all other bits except `Z`
+    /// to `x`.
+    pub fn to_vcd_char(self) -> char {
+        match self {
+            LogicBit::Zero | LogicBit::L => '0',
+            LogicBit::One | LogicBit::H => '1',
+            LogicBit::Z => 'z',
+            _ => 'x',
+        }
+    }
+
+    /// Strip the drive strength from the bit, mapping it onto one of `U`, `X`,
+    /// `0`, and `1`.
+    ///
+    /// This corresponds to the `To_X01` function of IEEE 1164, except that `U`
+    /// is preserved, which is what the truth tables of the logic operators
+    /// require.
+    pub fn to_ux01(self) -> LogicBit {
+        match self {
+            LogicBit::U => LogicBit::U,
+            LogicBit::Zero | LogicBit::L => LogicBit::Zero,
+            LogicBit::One | LogicBit::H => LogicBit::One,
+            LogicBit::X | LogicBit::Z | LogicBit::W | LogicBit::DontCare => LogicBit::X,
+        }
+    }
+
+    /// Resolve the bits of two drivers of a wire.
+    ///
+    /// This corresponds to the `resolved` function of IEEE 1164: strong drives
+    /// override weak ones, which override `Z`, and conflicting drives of the
+    /// same strength yield an unknown value.
+    pub fn resolve(self, other: LogicBit) -> LogicBit {
+        use LogicBit::*;
+        match (self, other) {
+            (U, _) | (_, U) => U,
+            (X, _) | (_, X) | (DontCare, _) | (_, DontCare) => X,
+            (Z, b) | (b, Z) => b,
+            (a, b) if a == b => a,
+            (Zero, One) | (One, Zero) => X,
+            (Zero, _) | (_, Zero) => Zero,
+            (One, _) | (_, One) => One,
+            _ => W,
+        }
+    }
+
+    /// Convert the bit to a boolean, if it carries a logic zero or one.
+    pub fn to_bool(self) -> Option<bool> {
+        match self.to_ux01() {
+            LogicBit::Zero => Some(false),
+            LogicBit::One => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for LogicBit {
+    fn from(v: bool) -> Self {
+        if v {
+            LogicBit::One
+        } else {
+            LogicBit::Zero
+        }
+    }
+}
+
+impl std::ops::Not for LogicBit {
+    type Output = LogicBit;
+
+    fn not(self) -> LogicBit {
+        match self.to_ux01() {
+            LogicBit::Zero => LogicBit::One,
+            LogicBit::One => LogicBit::Zero,
+            x => x,
+        }
+    }
+}
+
+impl std::ops::BitAnd for LogicBit {
+    type Output = LogicBit;
+
+    fn bitand(self, other: LogicBit) -> LogicBit {
+        match (self.to_ux01(), other.to_ux01()) {
+            (LogicBit::Zero, _) | (_, LogicBit::Zero) => LogicBit::Zero,
+            (LogicBit::U, _) | (_, LogicBit::U) => LogicBit::U,
+            (LogicBit::X, _) | (_, LogicBit::X) => LogicBit::X,
+            _ => LogicBit::One,
+        }
+    }
+}
+
+impl std::ops::BitOr for LogicBit {
+    type Output = LogicBit;
+
+    fn bitor(self, other: LogicBit) -> LogicBit {
+        match (self.to_ux01(), other.to_ux01()) {
+            (LogicBit::One, _) | (_, LogicBit::One) => LogicBit::One,
+            (LogicBit::U, _) | (_, LogicBit::U) => LogicBit::U,
+            (LogicBit::X, _) | (_, LogicBit::X) => LogicBit::X,
+            _ => LogicBit::Zero,
+        }
+    }
+}
+
+impl std::ops::BitXor for LogicBit {
+    type Output = LogicBit;
+
+    fn bitxor(self, other: LogicBit) -> LogicBit {
+        match (self.to_ux01(), other.to_ux01()) {
+            (LogicBit::U, _) | (_, LogicBit::U) => LogicBit::U,
+            (LogicBit::X, _) | (_, LogicBit::X) => LogicBit::X,
+            (a, b) if a == b => LogicBit::Zero,
+            _ => LogicBit::One,
+        }
+    }
+}
+
+impl Display for LogicBit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl Debug for LogicBit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// A logic value.
+///
+/// The bits are stored with the lowest-indexed wire first. Note that the
+/// string representation lists the highest-indexed wire first, such that
+/// `"L0LZ"` has `Z` as its bit 0.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LogicValue(pub Vec<LogicBit>);
+
+impl LogicValue {
+    /// Create a new logic value from a list of bits, lowest-indexed wire first.
+    pub fn new(bits: Vec<LogicBit>) -> Self {
+        LogicValue(bits)
+    }
+
+    /// Create a logic value with every bit set to `bit`.
+    pub fn new_uniform(width: usize, bit: LogicBit) -> Self {
+        LogicValue(vec![bit; width])
+    }
+
+    /// Create a zero value.
+    pub fn zero(width: usize) -> Self {
+        Self::new_uniform(width, LogicBit::Zero)
+    }
+
+    /// Parse a logic value from a string such as `"01XZ"`, with the
+    /// highest-indexed wire first.
+    pub fn parse(s: &str) -> Option<Self> {
+        s.chars()
+            .rev()
+            .map(LogicBit::from_char)
+            .collect::<Option<Vec<_>>>()
+            .map(LogicValue)
+    }
+
+    /// Create a logic value from the bits of an integer value.
+    pub fn from_int(value: &IntValue) -> Self {
+        LogicValue(
+            (0..value.width)
+                .map(|i| value.value.bit(i as u64).into())
+                .collect(),
+        )
+    }
+
+    /// Convert the value to an integer, if all bits carry a logic zero or one.
+    pub fn to_int(&self) -> Option<IntValue> {
+        let mut v = IntValue::zero(self.width());
+        for (i, bit) in self.0.iter().enumerate() {
+            v.value.set_bit(i as u64, bit.to_bool()?);
+        }
+        Some(v)
+    }
+
+    /// Get the number of bits in the value.
+    pub fn width(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the value is zero.
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|&b| b == LogicBit::Zero)
+    }
+
+    /// Check if the value is one.
+    pub fn is_one(&self) -> bool {
+        self.0
+            .iter()
+            .enumerate()
+            .all(|(i, &b)| b == (i == 0).into())
+    }
+
+    /// Get the type of the value.
+    pub fn ty(&self) -> Type {
+        logic_ty(self.width())
+    }
+}
+
+impl Display for LogicValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "\"")?;
+        for bit in self.0.iter().rev() {
+            write!(f, "{}", bit)?;
+        }
+        write!(f, "\"")
+    }
+}
+
+impl Debug for LogicValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "l{} {}", self.width(), self)
+    }
+}
+
+/// Slicing.
+impl LogicValue {
+    /// Extract a slice of bits from the value.
+    pub fn extract_slice(&self, off: usize, len: usize) -> LogicValue {
+        LogicValue::new(self.0[off..off + len].to_vec())
+    }
+
+    /// Insert a slice of bits into the value.
+    pub fn insert_slice(&mut self, off: usize, len: usize, value: &LogicValue) {
+        assert_eq!(len, value.width());
+        self.0[off..off + len].copy_from_slice(&value.0);
+    }
+}
+
+/// Unary operators.
+impl LogicValue {
+    /// Compute `not`.
+    pub fn not(&self) -> LogicValue {
+        LogicValue(self.0.iter().map(|&b| !b).collect())
+    }
+}
+
+/// Binary operators.
+impl LogicValue {
+    /// Compute `and`.
+    pub fn and(&self, other: &Self) -> LogicValue {
+        self.zip_with(other, std::ops::BitAnd::bitand)
+    }
+
+    /// Compute `or`.
+    pub fn or(&self, other: &Self) -> LogicValue {
+        self.zip_with(other, std::ops::BitOr::bitor)
+    }
+
+    /// Compute `xor`.
+    pub fn xor(&self, other: &Self) -> LogicValue {
+        self.zip_with(other, std::ops::BitXor::bitxor)
+    }
+
+    fn zip_with(&self, other: &Self, f: impl Fn(LogicBit, LogicBit) -> LogicBit) -> LogicValue {
+        assert_eq!(self.width(), other.width());
+        LogicValue(
+            self.0
+                .iter()
+                .zip(other.0.iter())
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+        )
+    }
+}
+
+/// Resolution.
+impl LogicValue {
+    /// Resolve the values of two drivers of a signal.
+    ///
+    /// Tristate resolution follows IEEE 1164. Wired-and and wired-or combine
+    /// the bits with `and` and `or`, respectively, except that a driver that
+    /// drives `Z` does not contribute to a bit.
+    pub fn resolve(&self, other: &Self, resolution: Resolution) -> LogicValue {
+        self.zip_with(other, |a, b| match (resolution, a, b) {
+            (Resolution::Tristate, a, b) => a.resolve(b),
+            (_, LogicBit::Z, b) | (_, b, LogicBit::Z) => b,
+            (Resolution::WiredAnd, a, b) => a & b,
+            (Resolution::WiredOr, a, b) => a | b,
+        })
+    }
+}
+
+/// Opcode implementations.
+impl LogicValue {
+    /// Execute a unary opcode.
+    pub fn try_unary_op(op: Opcode, arg: &LogicValue) -> Option<LogicValue> {
+        Some(match op {
+            Opcode::Not => arg.not(),
+            _ => return None,
+        })
+    }
+
+    /// Execute a unary opcode.
+    pub fn unary_op(op: Opcode, arg: &LogicValue) -> LogicValue {
+        match Self::try_unary_op(op, arg) {
+            Some(r) => r,
+            None => panic!("{} is not a unary op on logic values", op),
+        }
+    }
+
+    /// Execute a binary opcode.
+    pub fn try_binary_op(op: Opcode, lhs: &LogicValue, rhs: &LogicValue) -> Option<LogicValue> {
+        Some(match op {
+            Opcode::And => lhs.and(rhs),
+            Opcode::Or => lhs.or(rhs),
+            Opcode::Xor => lhs.xor(rhs),
+            _ => return None,
+        })
+    }
+
+    /// Execute a binary opcode.
+    pub fn binary_op(op: Opcode, lhs: &LogicValue, rhs: &LogicValue) -> LogicValue {
+        match Self::try_binary_op(op, lhs, rhs) {
+            Some(r) => r,
+            None => panic!("{} is not a binary op on logic values", op),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l(s: &str) -> LogicValue {
+        LogicValue::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_and_print() {
+        let v = l("L0LZ");
+        assert_eq!(v.width(), 4);
+        assert_eq!(v.0[0], LogicBit::Z);
+        assert_eq!(v.0[3], LogicBit::L);
+        assert_eq!(format!("{}", v), "\"L0LZ\"");
+        assert_eq!(LogicValue::parse("01A"), None);
+        let vcd: String = l("UX01ZWLH-")
+            .0
+            .iter()
+            .rev()
+            .map(|b| b.to_vcd_char())
+            .collect();
+        assert_eq!(vcd, "xx01zx01x");
+    }
+
+    #[test]
+    fn not() {
+        assert_eq!(l("UX01ZWLH-").not(), l("UX10XX10X"));
+    }
+
+    #[test]
+    fn and() {
+        assert_eq!(l("UUUUUUUUU").and(&l("UX01ZWLH-")), l("UU0UUU0UU"));
+        assert_eq!(l("XXXXXXXXX").and(&l("UX01ZWLH-")), l("UX0XXX0XX"));
+        assert_eq!(l("000000000").and(&l("UX01ZWLH-")), l("000000000"));
+        assert_eq!(l("111111111").and(&l("UX01ZWLH-")), l("UX01XX01X"));
+        assert_eq!(l("ZZZZZZZZZ").and(&l("UX01ZWLH-")), l("UX0XXX0XX"));
+    }
+
+    #[test]
+    fn or() {
+        assert_eq!(l("UUUUUUUUU").or(&l("UX01ZWLH-")), l("UUU1UUU1U"));
+        assert_eq!(l("XXXXXXXXX").or(&l("UX01ZWLH-")), l("UXX1XXX1X"));
+        assert_eq!(l("000000000").or(&l("UX01ZWLH-")), l("UX01XX01X"));
+        assert_eq!(l("111111111").or(&l("UX01ZWLH-")), l("111111111"));
+        assert_eq!(l("HHHHHHHHH").or(&l("UX01ZWLH-")), l("111111111"));
+    }
+
+    #[test]
+    fn xor() {
+        assert_eq!(l("UUUUUUUUU").xor(&l("UX01ZWLH-")), l("UUUUUUUUU"));
+        assert_eq!(l("XXXXXXXXX").xor(&l("UX01ZWLH-")), l("UXXXXXXXX"));
+        assert_eq!(l("000000000").xor(&l("UX01ZWLH-")), l("UX01XX01X"));
+        assert_eq!(l("111111111").xor(&l("UX01ZWLH-")), l("UX10XX10X"));
+    }
+
+    #[test]
+    fn resolve() {
+        let tri = |a: &str, b: &str| l(a).resolve(&l(b), Resolution::Tristate);
+        assert_eq!(tri("UUUUUUUUU", "UX01ZWLH-"), l("UUUUUUUUU"));
+        assert_eq!(tri("XXXXXXXXX", "UX01ZWLH-"), l("UXXXXXXXX"));
+        assert_eq!(tri("000000000", "UX01ZWLH-"), l("UX0X0000X"));
+        assert_eq!(tri("111111111", "UX01ZWLH-"), l("UXX11111X"));
+        assert_eq!(tri("ZZZZZZZZZ", "UX01ZWLH-"), l("UX01ZWLHX"));
+        assert_eq!(tri("WWWWWWWWW", "UX01ZWLH-"), l("UX01WWWWX"));
+        assert_eq!(tri("LLLLLLLLL", "UX01ZWLH-"), l("UX01LWLWX"));
+        assert_eq!(tri("HHHHHHHHH", "UX01ZWLH-"), l("UX01HWWHX"));
+        assert_eq!(tri("---------", "UX01ZWLH-"), l("UXXXXXXXX"));
+        assert_eq!(
+            l("01Z1").resolve(&l("Z101"), Resolution::WiredAnd),
+            l("0101")
+        );
+        assert_eq!(
+            l("01Z0").resolve(&l("Z011"), Resolution::WiredOr),
+            l("0111")
+        );
+    }
+
+    #[test]
+    fn int_conversion() {
+        let v = IntValue::from_usize(4, 0b0110);
+        assert_eq!(LogicValue::from_int(&v), l("0110"));
+        assert_eq!(l("LHH0").to_int(), Some(v));
+        assert_eq!(l("0Z10").to_int(), None);
+    }
+}
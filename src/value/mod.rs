@@ -6,12 +6,14 @@
 
 mod array;
 mod int;
+mod logic;
 mod r#struct;
 mod time;
 
 pub use self::time::*;
 pub use array::*;
 pub use int::*;
+pub use logic::*;
 pub use r#struct::*;
 
 use crate::ty::Type;
@@ -24,6 +26,7 @@ pub enum Value {
     Void,
     Time(TimeValue),
     Int(IntValue),
+    Logic(LogicValue),
     Array(ArrayValue),
     Struct(StructValue),
 }
@@ -36,6 +39,7 @@ impl Value {
             VoidType => Value::Void,
             IntType(w) => IntValue::zero(*w).into(),
             EnumType(_) => unimplemented!("zero value for {}", ty),
+            LogicType(w) => LogicValue::zero(*w).into(),
             ArrayType(l, ty) => ArrayValue::zero(*l, ty).into(),
             StructType(tys) => StructValue::zero(tys).into(),
             _ => panic!("no zero value for {}", ty),
@@ -68,6 +72,19 @@ impl Value {
         self.get_int().expect("value is not an integer")
     }
 
+    /// If this value is a logic value, access it.
+    pub fn get_logic(&self) -> Option<&LogicValue> {
+        match self {
+            Value::Logic(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Unwrap this value as a logic value, or panic.
+    pub fn unwrap_logic(&self) -> &LogicValue {
+        self.get_logic().expect("value is not a logic value")
+    }
+
     /// If this value is an array, access it.
     pub fn get_array(&self) -> Option<&ArrayValue> {
         match self {
@@ -101,6 +118,7 @@ impl Value {
             Value::Void => void_ty(),
             Value::Time(v) => v.ty(),
             Value::Int(v) => v.ty(),
+            Value::Logic(v) => v.ty(),
             Value::Array(v) => v.ty(),
             Value::Struct(v) => v.ty(),
         }
@@ -111,6 +129,7 @@ impl Value {
         match self {
             Value::Int(v) => v.is_zero(),
             Value::Time(v) => v.is_zero(),
+            Value::Logic(v) => v.is_zero(),
             Value::Void | Value::Array(..) | Value::Struct(..) => false,
         }
     }
@@ -119,6 +138,7 @@ impl Value {
     pub fn is_one(&self) -> bool {
         match self {
             Value::Int(v) => v.is_one(),
+            Value::Logic(v) => v.is_one(),
             Value::Void | Value::Time(_) | Value::Array(..) | Value::Struct(..) => false,
         }
     }
@@ -136,6 +156,12 @@ impl From<IntValue> for Value {
     }
 }
 
+impl From<LogicValue> for Value {
+    fn from(v: LogicValue) -> Value {
+        Value::Logic(v)
+    }
+}
+
 impl From<ArrayValue> for Value {
     fn from(v: ArrayValue) -> Value {
         Value::Array(v)
@@ -154,6 +180,7 @@ impl Display for Value {
             Value::Void => write!(f, "void"),
             Value::Time(v) => write!(f, "time {}", v),
             Value::Int(v) => Display::fmt(v, f),
+            Value::Logic(v) => write!(f, "l{} {}", v.width(), v),
             Value::Array(v) => Display::fmt(v, f),
             Value::Struct(v) => Display::fmt(v, f),
         }
@@ -4,7 +4,10 @@
 //!
 //! This module implements time arithmetic.
 
-use crate::ty::{time_ty, Type};
+use crate::{
+    ir::Module,
+    ty::{time_ty, Type},
+};
 use num::{traits::*, BigInt, BigRational};
 use std::fmt::{Debug, Display};
 
@@ -118,6 +121,110 @@ fn write_ratio_as_si(ratio: &BigRational, f: &mut std::fmt::Formatter) -> std::f
     Ok(())
 }
 
+/// A unit of time, such as the one in which a tracer records time.
+///
+/// Timescales are powers of ten seconds between one femtosecond and one hundred
+/// seconds, as supported by VCD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timescale(i32);
+
+impl Timescale {
+    /// The finest timescale, one femtosecond.
+    pub const MIN: Timescale = Timescale(-15);
+    /// The coarsest timescale, one hundred seconds.
+    pub const MAX: Timescale = Timescale(2);
+
+    /// Create a timescale of `10^exponent` seconds.
+    pub fn from_exponent(exponent: i32) -> Option<Self> {
+        let ts = Timescale(exponent);
+        if ts >= Self::MIN && ts <= Self::MAX {
+            Some(ts)
+        } else {
+            None
+        }
+    }
+
+    /// Create a timescale from a time such as `1ns` or `100ps`.
+    ///
+    /// Returns `None` if the time is not a power of ten seconds in the
+    /// supported range.
+    pub fn from_time(time: &TimeValue) -> Option<Self> {
+        if time.delta() != 0 || time.epsilon() != 0 {
+            return None;
+        }
+        (Self::MIN.0..=Self::MAX.0)
+            .map(Timescale)
+            .find(|ts| &ts.seconds() == time.time())
+    }
+
+    /// Determine the timescale for the simulation of a module.
+    ///
+    /// This is the coarsest timescale in which all time constants in the module
+    /// can be represented exactly, and is therefore never coarser than the
+    /// smallest delay. Modules without delays use picoseconds.
+    pub fn for_module(module: &Module) -> Self {
+        let mut timescale = None;
+        for unit in module.units() {
+            for inst in unit.all_insts() {
+                let time = match unit[inst].get_const_time() {
+                    Some(t) if !t.time().is_zero() => t.time(),
+                    _ => continue,
+                };
+                let exact = (Self::MIN.0..=Self::MAX.0)
+                    .rev()
+                    .map(Timescale)
+                    .find(|ts| (time / ts.seconds()).is_integer())
+                    .unwrap_or(Self::MIN);
+                timescale = Some(timescale.map_or(exact, |ts| std::cmp::min(ts, exact)));
+            }
+        }
+        timescale.unwrap_or_default()
+    }
+
+    /// The base-ten exponent of the timescale in seconds.
+    pub fn exponent(&self) -> i32 {
+        self.0
+    }
+
+    /// The length of the timescale in seconds.
+    pub fn seconds(&self) -> BigRational {
+        let scale = BigInt::from(10).pow(self.0.unsigned_abs());
+        if self.0 < 0 {
+            BigRational::new(One::one(), scale)
+        } else {
+            BigRational::from(scale)
+        }
+    }
+
+    /// Convert a physical time in seconds to a number of time units, rounded
+    /// towards zero.
+    pub fn ticks(&self, time: &BigRational) -> BigInt {
+        (time / self.seconds()).trunc().to_integer()
+    }
+}
+
+impl Default for Timescale {
+    fn default() -> Self {
+        Timescale(-12)
+    }
+}
+
+impl std::fmt::Display for Timescale {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let unit = self.0.div_euclid(3);
+        let mult = 10u32.pow((self.0 - unit * 3) as u32);
+        let unit = match unit {
+            -5 => "fs",
+            -4 => "ps",
+            -3 => "ns",
+            -2 => "us",
+            -1 => "ms",
+            _ => "s",
+        };
+        write!(f, "{}{}", mult, unit)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,4 +279,39 @@ mod tests {
 
         assert_eq!(make(1, 3, 0, 0), "333.333333333ms");
     }
+
+    fn time(s: &str) -> TimeValue {
+        crate::assembly::parse_time(s).unwrap()
+    }
+
+    #[test]
+    fn timescale() {
+        assert_eq!(Timescale::default().to_string(), "1ps");
+        assert_eq!(Timescale::MIN.to_string(), "1fs");
+        assert_eq!(Timescale::MAX.to_string(), "100s");
+        assert_eq!(Timescale::from_exponent(-8).unwrap().to_string(), "10ns");
+        assert_eq!(
+            Timescale::from_time(&time("100ps")),
+            Timescale::from_exponent(-10)
+        );
+        assert_eq!(Timescale::from_time(&time("3ns")), None);
+        assert_eq!(
+            Timescale::from_exponent(-9)
+                .unwrap()
+                .ticks(time("25.5ns").time()),
+            BigInt::from(25)
+        );
+
+        let module = crate::assembly::parse_module(
+            "
+            entity @top () -> () {
+                %0 = const time 20ns
+                %1 = const time 1.5us
+                %2 = const time 0s 1d
+            }
+            ",
+        )
+        .unwrap();
+        assert_eq!(Timescale::for_module(&module).to_string(), "10ns");
+    }
 }
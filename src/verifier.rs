@@ -8,9 +8,10 @@
 
 use crate::{
     ir::{prelude::*, InstData, UnitFlags, ValueData},
-    ty::{array_ty, int_ty, pointer_ty, signal_ty, time_ty, void_ty, Type},
+    ty::{array_ty, int_ty, logic_ty, pointer_ty, signal_ty, time_ty, void_ty, Type},
 };
 use std::{
+    collections::HashMap,
     fmt::Display,
     ops::{Deref, DerefMut},
 };
@@ -18,10 +19,12 @@ use std::{
 /// An IR verifier.
 ///
 /// The `Verifier` acts as a context to call the various IR checking functions
-/// on. It keeps track of errors.
+/// on. It keeps track of errors, and of warnings about constructs that are
+/// well-formed but likely not intended.
 #[derive(Default)]
 pub struct Verifier {
     errors: VerifierErrors,
+    warnings: VerifierErrors,
     unit_name: Option<String>,
     flags: UnitFlags,
     return_type: Option<Type>,
@@ -115,10 +118,56 @@ impl Verifier {
             }
         }
 
+        // Check that signals with multiple drivers are resolved.
+        if unit.is_entity() {
+            self.verify_drivers(unit);
+        }
+
         self.unit_name = None;
         self.return_type = None;
     }
 
+    /// Check that the signals of an entity that are driven from more than one
+    /// place have a resolution function.
+    ///
+    /// Every `drv` and `reg` instruction and every `inst` output is a separate
+    /// driver. Only drives of the signal itself are counted, not drives of a
+    /// slice or field of it. Signals passed into the entity are resolved by the
+    /// unit that declares them and are not checked.
+    fn verify_drivers(&mut self, unit: Unit) {
+        let mut drivers: HashMap<Value, usize> = HashMap::new();
+        for inst in unit.all_insts() {
+            let data = &unit[inst];
+            let targets = match data.opcode() {
+                Opcode::Drv | Opcode::DrvCond | Opcode::Reg => &data.args()[0..1],
+                Opcode::Inst => data.output_args(),
+                _ => continue,
+            };
+            for &target in targets {
+                *drivers.entry(target).or_default() += 1;
+            }
+        }
+        for inst in unit.all_insts() {
+            let data = &unit[inst];
+            if data.opcode() != Opcode::Sig || data.resolution().is_some() {
+                continue;
+            }
+            let count = drivers.get(&unit.inst_result(inst)).copied().unwrap_or(0);
+            if count > 1 {
+                self.warnings.push(VerifierError {
+                    unit: self.unit_name.clone(),
+                    object: Some(inst.dump(&unit).to_string()),
+                    message: format!("signal has {} drivers but no resolution function", count),
+                });
+            }
+        }
+    }
+
+    /// Access the warnings collected so far.
+    pub fn warnings(&self) -> &VerifierErrors {
+        &self.warnings
+    }
+
     /// Finish verification and return the result.
     ///
     /// Consumes the verifier.
@@ -254,6 +303,7 @@ impl<'a> InstVerifier<'a> {
         match unit[inst].opcode() {
             Opcode::ConstInt => {}
             Opcode::ConstTime => {}
+            Opcode::ConstLogic => {}
             Opcode::Alias => {}
             Opcode::ArrayUniform => {}
             Opcode::Array => {
@@ -357,7 +407,7 @@ impl<'a> InstVerifier<'a> {
                 // TODO: properly check argument types match the declaration
             }
             Opcode::Sig => {
-                self.assert_inst_unary(inst);
+                self.assert_inst_signal(inst);
                 self.verify_sig_inst(inst);
             }
             Opcode::Prb => {
@@ -365,11 +415,11 @@ impl<'a> InstVerifier<'a> {
                 self.verify_prb_inst(inst);
             }
             Opcode::Drv => {
-                self.assert_inst_ternary(inst);
+                self.assert_inst_drive(inst, 3);
                 self.verify_drv_inst(inst);
             }
             Opcode::DrvCond => {
-                self.assert_inst_quaternary(inst);
+                self.assert_inst_drive(inst, 4);
                 self.verify_drv_inst(inst);
             }
             Opcode::Var => {
@@ -462,13 +512,26 @@ impl<'a> InstVerifier<'a> {
         }
     }
 
-    /// Assert that an instruction has quaternary format.
-    fn assert_inst_quaternary(&mut self, inst: Inst) {
+    /// Assert that an instruction has signal format.
+    fn assert_inst_signal(&mut self, inst: Inst) {
+        match &self.unit()[inst] {
+            InstData::Signal { .. } => (),
+            fmt => panic!(
+                "{0:?} ({0}) should have signal format, but has {1:?}",
+                fmt.opcode(),
+                fmt
+            ),
+        }
+    }
+
+    /// Assert that an instruction has drive format with a number of arguments.
+    fn assert_inst_drive(&mut self, inst: Inst, num_args: usize) {
         match &self.unit()[inst] {
-            InstData::Quaternary { .. } => (),
+            InstData::Drive { args, .. } if args.len() == num_args => (),
             fmt => panic!(
-                "{0:?} ({0}) should have quaternary format, but has {1:?}",
+                "{0:?} ({0}) should have drive format with {1} arguments, but has {2:?}",
                 fmt.opcode(),
+                num_args,
                 fmt
             ),
         }
@@ -631,7 +694,18 @@ impl<'a> InstVerifier<'a> {
     /// Verify that an instruction's return type is compatible with bitwise
     /// operations.
     fn verify_bitwise_compatible_ty(&mut self, inst: Inst) {
-        self.verify_arith_compatible_ty(inst);
+        let ty = self.unit.inst_type(inst);
+        if ty.is_int() || ty.is_logic() {
+            return;
+        }
+        if ty.is_signal() && (ty.unwrap_signal().is_int() || ty.unwrap_signal().is_logic()) {
+            return;
+        }
+        self.verifier.errors.push(VerifierError {
+            unit: self.verifier.unit_name.clone(),
+            object: Some(inst.dump(&self.unit).to_string()),
+            message: format!("return type must be iN, lN, iN$, or lN$ (but is {})", ty),
+        });
     }
 
     /// Verify that an instruction's return type is compatible with arithmetic
@@ -705,6 +779,9 @@ impl<'a> InstVerifier<'a> {
         if base_inner_ty.is_int() && hidden_inner_ty.is_int() {
             return;
         }
+        if base_inner_ty.is_logic() && hidden_inner_ty.is_logic() {
+            return;
+        }
         if base_inner_ty.is_array()
             && hidden_inner_ty.is_array()
             && base_inner_ty.unwrap_array() == hidden_inner_ty.unwrap_array()
@@ -858,11 +935,29 @@ impl<'a> InstVerifier<'a> {
                 });
             }
             Some(int_ty(length))
+        } else if target_ty.is_logic() {
+            let size = target_ty.unwrap_logic();
+            if size < offset + length {
+                self.verifier.errors.push(VerifierError {
+                    unit: self.verifier.unit_name.clone(),
+                    object: Some(inst.dump(&self.unit).to_string()),
+                    message: format!(
+                        "access {}..{} out of logic bounds 0..{}",
+                        offset,
+                        offset + length,
+                        size
+                    ),
+                });
+            }
+            Some(logic_ty(length))
         } else {
             self.verifier.errors.push(VerifierError {
                 unit: self.verifier.unit_name.clone(),
                 object: Some(inst.dump(&self.unit).to_string()),
-                message: format!("target must be of array or iN type (but is {})", target_ty),
+                message: format!(
+                    "target must be of array, iN, or lN type (but is {})",
+                    target_ty
+                ),
             });
             None
         };
@@ -920,6 +1015,20 @@ impl<'a> InstVerifier<'a> {
             });
         }
         self.verify_args_match_ty(inst, ty.unwrap_signal());
+        if let Some(resolution) = self.unit()[inst].resolution() {
+            let ty = ty.unwrap_signal();
+            let resolvable = match resolution {
+                Resolution::Tristate => ty.is_logic(),
+                Resolution::WiredAnd | Resolution::WiredOr => ty.is_int() || ty.is_logic(),
+            };
+            if !resolvable {
+                self.verifier.errors.push(VerifierError {
+                    unit: self.verifier.unit_name.clone(),
+                    object: Some(inst.dump(&self.unit).to_string()),
+                    message: format!("type {} cannot be resolved with {}", ty, resolution),
+                });
+            }
+        }
     }
 
     /// Verify that the types of a prb instruction line up.
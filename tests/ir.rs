@@ -41,3 +41,64 @@ fn call_with_return_value() {
         println!("{}", builder.unit());
     });
 }
+
+#[test]
+fn drv_delay_modes_round_trip() {
+    let input = "entity @test (i1$ %a, i1 %c) -> (i1$ %b) {
+    %delay = const time 1ns
+    %a.prb = prb i1$ %a
+    drv i1$ %b, %a.prb, %delay
+    drv inertial i1$ %b if %c, %a.prb, %delay
+    drv transport i1$ %b, %a.prb, %delay
+    drv transport i1$ %b if %c, %a.prb, %delay
+}
+";
+    let module = llhd::assembly::parse_module(input).unwrap();
+    let unit = module.units().next().unwrap();
+    let modes: Vec<_> = unit
+        .all_insts()
+        .filter_map(|inst| unit[inst].delay_mode())
+        .collect();
+    assert_eq!(
+        modes,
+        vec![
+            DelayMode::Inertial,
+            DelayMode::Inertial,
+            DelayMode::Transport,
+            DelayMode::Transport
+        ]
+    );
+    let output = llhd::assembly::write_module_string(&module);
+    assert_eq!(output, input.replace("drv inertial", "drv"));
+}
+
+#[test]
+fn sig_resolution_round_trip() {
+    let input = "entity @test () -> () {
+    %z = const l4 \"ZZZZ\"
+    %0 = const i4 0
+    %a = sig l4 %z
+    %b = sig tri l4 %z
+    %c = sig wand i4 %0
+    %d = sig wor l4 %z
+}
+";
+    let module = llhd::assembly::parse_module(input).unwrap();
+    let unit = module.units().next().unwrap();
+    let resolutions: Vec<_> = unit
+        .all_insts()
+        .filter(|&inst| unit[inst].opcode() == Opcode::Sig)
+        .map(|inst| unit[inst].resolution())
+        .collect();
+    assert_eq!(
+        resolutions,
+        vec![
+            None,
+            Some(Resolution::Tristate),
+            Some(Resolution::WiredAnd),
+            Some(Resolution::WiredOr)
+        ]
+    );
+    let output = llhd::assembly::write_module_string(&module);
+    assert_eq!(output, input);
+}
@@ -0,0 +1,629 @@
+use llhd::sim::{
+    coverage::Coverage,
+    stimulus::Stimulus,
+    tracediff::{diff, DiffOptions, Trace},
+    tracer::{DumpTracer, Tracer},
+    value::{IntValue, LogicValue, Value},
+    Backend, SignalRef, Simulation, SimulationError, State,
+};
+use llhd::value::LogicBit;
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+const COUNTER: &str = "
+    proc @counter (i1$ %en) -> (i8$ %count) {
+    entry:
+        %one = const i8 1
+        %delay = const time 1ns
+        %en.prb = prb i1$ %en
+        br %en.prb, %idle, %inc
+    inc:
+        %count.prb = prb i8$ %count
+        %next = add i8 %count.prb, %one
+        drv i8$ %count, %next, %delay
+        wait %entry for %delay
+    idle:
+        wait %entry, %en
+    }
+
+    entity @top () -> () {
+        %0 = const i1 0
+        %1 = const i8 0
+        %en = sig i1 %0
+        %count = sig i8 %1
+        inst @counter (i1$ %en) -> (i8$ %count)
+    }
+";
+
+fn parse(input: &str) -> llhd::ir::Module {
+    llhd::assembly::parse_module(input).unwrap()
+}
+
+/// Run a test on a fresh simulation of a module with each backend.
+fn for_each_backend<'a>(module: &'a llhd::ir::Module, mut f: impl FnMut(Simulation<'a>)) {
+    for &backend in &[Backend::Interpreter, Backend::Bytecode] {
+        let mut sim = Simulation::new(module).unwrap();
+        sim.set_backend(backend);
+        f(sim);
+    }
+}
+
+#[test]
+fn poke_and_peek() {
+    let module = parse(COUNTER);
+    let mut sim = Simulation::new(&module).unwrap();
+    assert_eq!(
+        sim.peek("@top.count"),
+        Some(&IntValue::from_usize(8, 0).into())
+    );
+    assert_eq!(sim.peek("@top.@counter.count"), sim.peek("@top.count"));
+    assert_eq!(sim.peek("@top.missing"), None);
+
+    // Nothing happens while the counter is disabled.
+    sim.run().unwrap();
+    assert_eq!(
+        sim.peek("@top.count"),
+        Some(&IntValue::from_usize(8, 0).into())
+    );
+
+    // Enable the counter and let it run for a few cycles.
+    sim.poke("@top.en", IntValue::from_usize(1, 1).into())
+        .unwrap();
+    let until = llhd::assembly::parse_time("3ns").unwrap();
+    while sim.time().time() <= until.time() {
+        assert!(sim.step().unwrap());
+    }
+    assert_eq!(
+        sim.peek("@top.count"),
+        Some(&IntValue::from_usize(8, 3).into())
+    );
+}
+
+#[test]
+fn poke_unknown_signal() {
+    let module = parse(COUNTER);
+    let mut sim = Simulation::new(&module).unwrap();
+    assert_eq!(
+        sim.poke("@top.missing", IntValue::from_usize(1, 1).into()),
+        Err(SimulationError::UnknownSignal("@top.missing".to_string()))
+    );
+}
+
+#[test]
+fn attach_tracer() {
+    struct CountingTracer(Rc<RefCell<Vec<usize>>>);
+
+    impl Tracer for CountingTracer {
+        fn init(&mut self, _: &State) {}
+        fn step(&mut self, _: &State, changed: &HashSet<SignalRef>) {
+            self.0.borrow_mut().push(changed.len());
+        }
+        fn finish(&mut self, _: &State) {}
+    }
+
+    let module = parse(COUNTER);
+    let mut sim = Simulation::new(&module).unwrap();
+    let changes = Rc::new(RefCell::new(vec![]));
+    sim.add_tracer(Box::new(CountingTracer(changes.clone())));
+    sim.poke("@top.en", IntValue::from_usize(1, 1).into())
+        .unwrap();
+    for _ in 0..3 {
+        sim.step().unwrap();
+    }
+    sim.finish();
+    assert_eq!(*changes.borrow(), vec![1, 1, 1]);
+}
+
+#[test]
+fn initialize_and_stimulus() {
+    let module = parse(COUNTER);
+    let mut sim = Simulation::new(&module).unwrap();
+    sim.initialize("count", IntValue::from_usize(8, 10).into())
+        .unwrap();
+    let mut stimulus = Stimulus::new();
+    let time = |s| llhd::assembly::parse_time(s).unwrap();
+    stimulus.add(time("2ns"), "en", "1");
+    stimulus.add(time("4ns"), "@top.en", "0");
+    sim.apply_stimulus(&stimulus).unwrap();
+    sim.run().unwrap();
+    assert_eq!(
+        sim.peek("@top.count"),
+        Some(&IntValue::from_usize(8, 12).into())
+    );
+
+    let mut stimulus = Stimulus::new();
+    stimulus.add(time("0ns"), "en", "foo");
+    assert_eq!(
+        sim.apply_stimulus(&stimulus),
+        Err(SimulationError::InvalidValue {
+            signal: "en".to_string(),
+            value: "foo".to_string(),
+        })
+    );
+}
+
+#[test]
+fn unknown_stimulus() {
+    let module = parse("entity @top (i4$ %a, l4$ %b) -> () {}");
+    let time = |s| llhd::assembly::parse_time(s).unwrap();
+    let mut stimulus = Stimulus::new();
+    stimulus.add(time("1ns"), "b", "0bX1");
+    let mut sim = Simulation::new(&module).unwrap();
+    sim.apply_stimulus(&stimulus).unwrap();
+    sim.run().unwrap();
+    assert_eq!(
+        sim.peek("@top.b"),
+        Some(&LogicValue::parse("XXX1").unwrap().into())
+    );
+
+    stimulus.add(time("2ns"), "a", "0bZ1");
+    let mut sim = Simulation::new(&module).unwrap();
+    assert_eq!(
+        sim.apply_stimulus(&stimulus),
+        Err(SimulationError::UnknownBits {
+            signal: "a".to_string(),
+            time: time("2ns"),
+        })
+    );
+    sim.enable_xprop();
+    sim.apply_stimulus(&stimulus).unwrap();
+    sim.run().unwrap();
+    assert_eq!(
+        sim.peek("@top.a"),
+        Some(&LogicValue::parse("ZZZ1").unwrap().into())
+    );
+}
+
+#[test]
+fn select_top_and_run_until() {
+    let module = parse(COUNTER);
+    assert_eq!(
+        Simulation::with_top(&module, "missing").err(),
+        Some(SimulationError::UnknownUnit("missing".to_string()))
+    );
+
+    let mut sim = Simulation::with_top(&module, "counter").unwrap();
+    sim.initialize("en", IntValue::from_usize(1, 1).into())
+        .unwrap();
+    let until = llhd::assembly::parse_time("5ns").unwrap();
+    assert!(sim.run_until(&until).unwrap());
+    assert_eq!(sim.peek("count"), Some(&IntValue::from_usize(8, 5).into()));
+    assert_eq!(sim.time(), &llhd::assembly::parse_time("6ns").unwrap());
+}
+
+#[test]
+fn checkpoint_and_restore() {
+    let module = parse(COUNTER);
+    let time = |s| llhd::assembly::parse_time(s).unwrap();
+
+    // Run one simulation without interruption.
+    let mut full = Simulation::new(&module).unwrap();
+    full.enable_coverage();
+    full.enable_profile();
+    full.poke("en", IntValue::from_usize(1, 1).into()).unwrap();
+    full.run_until(&time("10ns")).unwrap();
+
+    // Run another one halfway, save it, and resume it from the checkpoint.
+    let mut sim = Simulation::new(&module).unwrap();
+    sim.enable_coverage();
+    sim.enable_profile();
+    sim.poke("en", IntValue::from_usize(1, 1).into()).unwrap();
+    sim.run_until(&time("4ns")).unwrap();
+    let mut checkpoint = vec![];
+    sim.save_checkpoint(&mut checkpoint).unwrap();
+    drop(sim);
+    let mut sim = Simulation::restore(&module, checkpoint.as_slice()).unwrap();
+    assert_eq!(sim.time(), &time("5ns"));
+    assert_eq!(sim.peek("count"), Some(&IntValue::from_usize(8, 4).into()));
+    sim.run_until(&time("10ns")).unwrap();
+    assert_eq!(sim.peek("count"), full.peek("count"));
+    assert_eq!(sim.time(), full.time());
+    assert_eq!(sim.steps(), full.steps());
+
+    // The coverage and profile collected before the checkpoint carry over.
+    assert_eq!(sim.coverage(), full.coverage());
+    let wakeups = |sim: &Simulation| {
+        let instances = sim.profile().unwrap().instances.into_iter();
+        let mut wakeups: Vec<_> = instances.map(|i| (i.path, i.activity.wakeups)).collect();
+        wakeups.sort();
+        wakeups
+    };
+    assert_eq!(wakeups(&sim), wakeups(&full));
+
+    // Garbage is rejected.
+    assert!(matches!(
+        Simulation::restore(&module, &b"garbage!garbage!"[..]),
+        Err(SimulationError::Checkpoint(_))
+    ));
+}
+
+#[test]
+fn inspect_instances() {
+    let module = parse(COUNTER);
+    let mut sim = Simulation::new(&module).unwrap();
+    let count = sim.signal("@top.@counter.count").unwrap();
+    assert_eq!(sim.signal_name(count), Some("@top.count".to_string()));
+
+    sim.poke("en", IntValue::from_usize(1, 1).into()).unwrap();
+    sim.step().unwrap();
+    let insts = sim.instances();
+    let counter = insts.iter().find(|i| i.unit == "@counter").unwrap();
+    assert_eq!(counter.path, "@top.@counter");
+    assert_eq!(counter.state, "waiting until 1ns");
+    assert_eq!(counter.block.as_deref(), Some("inc"));
+    assert!(counter.inst.as_deref().unwrap().starts_with("wait"));
+    let top = insts.iter().find(|i| i.unit == "@top").unwrap();
+    assert_eq!(top.block, None);
+}
+
+#[test]
+fn bytecode_backend() {
+    let module = parse(
+        "
+        func @double (i8 %x) i8 {
+        entry:
+            %y = add i8 %x, %x
+            ret i8 %y
+        }
+
+        entity @comb (i1$ %clk, i8$ %count) -> (i8$ %double, i8$ %q) {
+            %delay = const time 0s 1e
+            %count.prb = prb i8$ %count
+            %clk.prb = prb i1$ %clk
+            %y = call i8 @double (i8 %count.prb)
+            drv i8$ %double, %y, %delay
+            reg i8$ %q, [%y, rise %clk.prb]
+        }
+
+        proc @clock () -> (i1$ %clk) {
+        entry:
+            %half = const time 500ps
+            %clk.prb = prb i1$ %clk
+            %next = not i1 %clk.prb
+            drv i1$ %clk, %next, %half
+            wait %entry for %half
+        }
+
+        entity @top () -> () {
+            %0 = const i1 0
+            %1 = const i8 0
+            %clk = sig i1 %0
+            %count = sig i8 %1
+            %double = sig i8 %1
+            %q = sig i8 %1
+            inst @clock () -> (i1$ %clk)
+            inst @comb (i1$ %clk, i8$ %count) -> (i8$ %double, i8$ %q)
+        }
+    ",
+    );
+    let mut interp = Simulation::new(&module).unwrap();
+    let mut bytecode = Simulation::new(&module).unwrap();
+    bytecode.set_backend(Backend::Bytecode);
+    for step in 0..50 {
+        if step % 4 == 0 {
+            let count = IntValue::from_usize(8, step / 4);
+            interp.poke("count", count.clone().into()).unwrap();
+            bytecode.poke("count", count.into()).unwrap();
+        }
+        assert_eq!(interp.step().unwrap(), bytecode.step().unwrap());
+        assert_eq!(interp.time(), bytecode.time());
+        for name in &["clk", "count", "double", "q"] {
+            assert_eq!(interp.peek(name), bytecode.peek(name), "{}", name);
+        }
+    }
+    assert_ne!(bytecode.peek("q"), Some(&IntValue::from_usize(8, 0).into()));
+}
+
+#[test]
+fn coverage_and_merge() {
+    let module = parse(COUNTER);
+    let run = |enable: bool| {
+        let mut covs = vec![];
+        for_each_backend(&module, |mut sim| {
+            sim.enable_coverage();
+            if enable {
+                sim.poke("en", IntValue::from_usize(1, 1).into()).unwrap();
+            }
+            sim.run_until(&llhd::assembly::parse_time("3ns").unwrap())
+                .unwrap();
+            covs.push(sim.coverage().unwrap());
+        });
+        assert_eq!(covs[0], covs[1]);
+        covs.pop().unwrap()
+    };
+    let blocks = |cov: &Coverage| {
+        let counter = &cov.root.subscopes[0];
+        assert_eq!(counter.name, "@counter");
+        let blocks = &counter.blocks.as_ref().unwrap().blocks;
+        blocks.iter().map(|&(_, n)| n).collect::<Vec<_>>()
+    };
+
+    // Counting enters all but the idle block, and toggles the lowest bit of
+    // the count.
+    let mut counting = run(true);
+    assert_eq!(blocks(&counting), vec![4, 0, 4]);
+    assert_eq!(counting.root.toggle_totals(), (2, 18));
+    assert_eq!(
+        counting.root.signals["count"].rose[..3],
+        [true, true, false]
+    );
+    assert_eq!(
+        counting.root.signals["count"].fell[..3],
+        [true, false, false]
+    );
+
+    // Idling enters the idle block only, and the merged report covers all
+    // blocks.
+    let idle = run(false);
+    assert_eq!(blocks(&idle), vec![1, 1, 0]);
+    counting
+        .merge(&Coverage::parse(&idle.to_json()).unwrap())
+        .unwrap();
+    assert_eq!(blocks(&counting), vec![5, 1, 4]);
+    assert_eq!(counting.root.block_totals(), (3, 3));
+    assert!(counting
+        .to_string()
+        .starts_with("@top: 2/18 bits toggled (11.1%), 3/3 blocks entered (100.0%)\n"));
+
+    // Reports of other designs are rejected.
+    let mut other = idle.clone();
+    other.root.name = "@other".to_string();
+    assert!(counting.merge(&other).is_err());
+}
+
+#[test]
+fn extern_functions() {
+    let module = parse(
+        "
+        declare @llhd.assert (i1) void
+        declare @square (i8) i8
+        declare @record (i8$) void
+
+        proc @top () -> (i8$ %out) {
+        entry:
+            %three = const i8 3
+            %delay = const time 1ns
+            %y = call i8 @square (i8 %three)
+            %ok = ult i8 %y, %three
+            call void @llhd.assert (i1 %ok)
+            drv i8$ %out, %y, %delay
+            wait %next for %delay
+        next:
+            call void @record (i8$ %out)
+            wait %next for %delay
+        }
+    ",
+    );
+    let recorded = std::sync::Mutex::new(vec![]);
+    let recorded_ref = &recorded;
+    for_each_backend(&module, |mut sim| {
+        // Calls of undefined functions fail, as do failed assertions.
+        assert!(matches!(
+            sim.step(),
+            Err(SimulationError::Extern { ref function, .. }) if function == "@square"
+        ));
+        sim.register_extern("square", |call| {
+            let x = call.args()[0].unwrap_int().to_usize();
+            Ok(Some(IntValue::from_usize(8, x * x).into()))
+        });
+        assert_eq!(
+            sim.step().unwrap_err().to_string(),
+            "@llhd.assert: assertion failed at 0s (instance @top)"
+        );
+
+        // Functions may override the intrinsics, record signal values, and
+        // end the simulation.
+        sim.register_extern("llhd.assert", |_| Ok(None));
+        sim.register_extern("@record", move |call| {
+            recorded_ref.lock().unwrap().push(call.args()[0].clone());
+            call.finish();
+            Ok(None)
+        });
+        sim.run().unwrap();
+        assert_eq!(sim.time(), &llhd::assembly::parse_time("1ns").unwrap());
+    });
+    assert_eq!(
+        recorded.into_inner().unwrap(),
+        vec![IntValue::from_usize(8, 9).into(); 2]
+    );
+}
+
+#[test]
+fn compare_traces() {
+    fn trace(input: &str) -> Trace {
+        let module = parse(input);
+        let mut dump = vec![];
+        let mut sim = Simulation::new(&module).unwrap();
+        sim.add_tracer(Box::new(DumpTracer::new(&mut dump)));
+        sim.poke("@top.en", IntValue::from_usize(1, 1).into())
+            .unwrap();
+        let until = llhd::assembly::parse_time("4ns").unwrap();
+        while sim.time().time() <= until.time() {
+            sim.step().unwrap();
+        }
+        sim.finish();
+        drop(sim);
+        Trace::parse(&String::from_utf8(dump).unwrap()).unwrap()
+    }
+
+    // Swapping the operands of the increment does not change the trace.
+    let reference = trace(COUNTER);
+    let options = DiffOptions::new();
+    let swapped = trace(&COUNTER.replace("%count.prb, %one", "%one, %count.prb"));
+    assert!(reference.changes("top.count").len() > 3);
+    assert_eq!(diff(&reference, &swapped, &options), vec![]);
+
+    // Counting by two diverges at the second increment.
+    let by_two = trace(&COUNTER.replace("const i8 1", "const i8 2"));
+    let divergences = diff(&reference, &by_two, &options);
+    assert_eq!(
+        divergences
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec!["top.count differs at 1ns: 0x1 != 0x2"]
+    );
+}
+
+#[test]
+fn profile_activity() {
+    let module = parse(COUNTER);
+    for_each_backend(&module, |mut sim| {
+        assert!(sim.profile().is_none());
+        sim.enable_profile();
+        sim.poke("@top.en", IntValue::from_usize(1, 1).into())
+            .unwrap();
+        for _ in 0..4 {
+            sim.step().unwrap();
+        }
+        let profile = sim.profile().unwrap();
+
+        let counter = profile
+            .instances
+            .iter()
+            .find(|i| i.path == "@top.@counter")
+            .unwrap();
+        assert_eq!(counter.unit, "@counter");
+        assert_eq!(counter.activity.wakeups, 4);
+        assert_eq!(counter.activity.instructions, 4 * 8);
+
+        let units = profile.units();
+        assert_eq!(units.len(), 2);
+        assert!(units.iter().all(|u| u.instances == 1));
+
+        let signals: Vec<_> = profile
+            .signals
+            .iter()
+            .map(|s| (s.name.as_str(), s.events, s.wakeups))
+            .collect();
+        assert_eq!(signals, vec![("@top.count", 3, 3), ("@top.en", 1, 0)]);
+    });
+}
+
+#[test]
+fn delta_limit() {
+    let module = parse(
+        "
+        proc @toggle () -> (i1$ %s) {
+        entry:
+            %eps = const time 0s 1e
+            %p = prb i1$ %s
+            %n = not i1 %p
+            drv i1$ %s, %n, %eps
+            wait %entry, %s
+        }
+    ",
+    );
+    for_each_backend(&module, |mut sim| {
+        sim.set_delta_limit(Some(20));
+        match sim.run() {
+            Err(SimulationError::DeltaLimit {
+                limit,
+                signals,
+                instances,
+            }) => {
+                assert_eq!(limit, 20);
+                assert_eq!(signals, vec!["@toggle.s"]);
+                assert_eq!(instances, vec!["@toggle"]);
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(sim.steps(), 21);
+        assert_eq!(sim.time().time(), &num::zero());
+    });
+
+    // Without a limit, the simulation keeps going.
+    let mut sim = Simulation::new(&module).unwrap();
+    sim.set_delta_limit(None);
+    for _ in 0..100 {
+        assert!(sim.step().unwrap());
+    }
+}
+
+#[test]
+fn xprop() {
+    let module = parse(
+        "
+        entity @top (i8$ %a) -> (i8$ %sum, i8$ %masked, i1$ %big) {
+            %delay = const time 1ns
+            %zero = const i8 0
+            %one = const i8 1
+            %a.prb = prb i8$ %a
+            %sum.val = add i8 %a.prb, %one
+            %masked.val = and i8 %a.prb, %zero
+            %big.val = ugt i8 %a.prb, %one
+            drv i8$ %sum, %sum.val, %delay
+            drv i8$ %masked, %masked.val, %delay
+            drv i1$ %big, %big.val, %delay
+        }
+    ",
+    );
+    let x = |width| Value::Logic(LogicValue::new_uniform(width, LogicBit::X));
+    let int = |width, value| Some(IntValue::from_usize(width, value).into());
+    for_each_backend(&module, |mut sim| {
+        sim.enable_xprop();
+        assert_eq!(sim.peek("@top.a"), Some(&x(8)));
+        sim.run().unwrap();
+        assert_eq!(sim.peek("@top.sum"), Some(&x(8)));
+        assert_eq!(sim.peek("@top.masked").cloned(), int(8, 0));
+        assert_eq!(sim.peek("@top.big"), Some(&x(1)));
+
+        // Known inputs make the outputs known again.
+        sim.poke("@top.a", IntValue::from_usize(8, 5).into())
+            .unwrap();
+        sim.run().unwrap();
+        assert_eq!(sim.peek("@top.sum").cloned(), int(8, 6));
+        assert_eq!(sim.peek("@top.big").cloned(), int(1, 1));
+        assert_eq!(sim.xprop_warnings(), Some(vec![]));
+    });
+}
+
+#[test]
+fn division_by_zero() {
+    let module = parse(
+        "
+        entity @top (i8$ %a) -> (i8$ %q) {
+            %delay = const time 1ns
+            %seven = const i8 7
+            %a.prb = prb i8$ %a
+            %q.val = udiv i8 %seven, %a.prb
+            drv i8$ %q, %q.val, %delay
+        }
+    ",
+    );
+    // Without X propagation, a zero divisor is an error.
+    for_each_backend(&module, |mut sim| match sim.run() {
+        Err(SimulationError::DivisionByZero {
+            instance,
+            unit,
+            inst,
+        }) => {
+            assert_eq!(instance, "@top");
+            assert_eq!(unit, "@top");
+            assert_eq!(inst, "%q.val = udiv i8 %seven, %a.prb");
+        }
+        r => panic!("expected division by zero, got {:?}", r.map(|_| ())),
+    });
+
+    // With X propagation, the quotient is unknown until the divisor is not
+    // zero anymore.
+    for_each_backend(&module, |mut sim| {
+        sim.enable_xprop();
+        sim.poke("@top.a", IntValue::from_usize(8, 0).into())
+            .unwrap();
+        sim.run().unwrap();
+        assert_eq!(
+            sim.peek("@top.q"),
+            Some(&Value::Logic(LogicValue::new_uniform(8, LogicBit::X)))
+        );
+        let warnings = sim.xprop_warnings().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "division by zero");
+        sim.poke("@top.a", IntValue::from_usize(8, 2).into())
+            .unwrap();
+        sim.run().unwrap();
+        assert_eq!(
+            sim.peek("@top.q").cloned(),
+            Some(IntValue::from_usize(8, 3).into())
+        );
+    });
+}